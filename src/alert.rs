@@ -0,0 +1,80 @@
+use std::thread;
+use std::time::Duration;
+use notify_rust::Notification;
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, Sink};
+
+/// Shows a desktop notification. Failures are ignored: it is only a cue, and some desktops
+/// have no notification service.
+pub fn notify(summary: &str, body: &str) {
+    let (summary, body) = (summary.to_string(), body.to_string());
+    // Showing can wait on the notification service, so keep it off the UI thread
+    thread::spawn(move || {
+        let _ = Notification::new().appname("Chess").summary(&summary).body(&body).show();
+    });
+}
+
+/// Plays a short two-tone chime on the default audio output, if there is one
+pub fn chime() {
+    play_tones(&[(660.0, 120), (880.0, 120)], 0.2);
+}
+
+/// What a sound effect marks. Each has tones of its own, so that they can be told apart
+/// without looking at the board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SoundEffect {
+    Move,
+    Capture,
+    Castling,
+    Check,
+    GameEnd,
+}
+
+impl SoundEffect {
+    /// The effect for a move in SAN, as the app writes it with its check suffix
+    pub fn of_move(san: &str) -> Self {
+        if san.ends_with('+') || san.ends_with('#') {
+            SoundEffect::Check
+        } else if san.starts_with("O-O") {
+            SoundEffect::Castling
+        } else if san.contains('x') {
+            SoundEffect::Capture
+        } else {
+            SoundEffect::Move
+        }
+    }
+
+    /// Frequency in Hz and length in milliseconds of each tone, played one after the other
+    fn tones(self) -> &'static [(f32, u64)] {
+        match self {
+            SoundEffect::Move => &[(440.0, 50)],
+            SoundEffect::Capture => &[(330.0, 50), (220.0, 70)],
+            SoundEffect::Castling => &[(520.0, 40), (0.0, 30), (520.0, 40)],
+            SoundEffect::Check => &[(880.0, 70), (990.0, 90)],
+            SoundEffect::GameEnd => &[(523.0, 120), (659.0, 120), (784.0, 240)],
+        }
+    }
+}
+
+/// Plays a sound effect at `volume`, from 0 for silence to 1 for full
+pub fn play_effect(effect: SoundEffect, volume: f64) {
+    play_tones(effect.tones(), 0.3 * volume.clamp(0.0, 1.0) as f32);
+}
+
+/// Plays sine tones one after the other on the default audio output, if there is one. A
+/// frequency of 0 is a pause.
+fn play_tones(tones: &'static [(f32, u64)], amplitude: f32) {
+    if amplitude <= 0.0 {
+        return;
+    }
+    thread::spawn(move || {
+        // The stream stops playing when dropped, so it lives until the sink is done
+        let Ok((_stream, handle)) = OutputStream::try_default() else { return };
+        let Ok(sink) = Sink::try_new(&handle) else { return };
+        for &(frequency, millis) in tones {
+            let level = if frequency > 0.0 { amplitude } else { 0.0 };
+            sink.append(SineWave::new(frequency.max(1.0)).take_duration(Duration::from_millis(millis)).amplify(level));
+        }
+        sink.sleep_until_end();
+    });
+}
@@ -0,0 +1,58 @@
+use druid::{Lens, WidgetExt, WindowDesc};
+use crate::app::{starting_board, AppState, GameTab};
+use crate::board::chess_board::ChessBoard;
+use crate::board::game_view::game_view;
+use crate::game::fen::{parse_fen, to_fen};
+use crate::game::game_state::GameState;
+use crate::menu::menu_bar;
+use crate::theme::themed;
+
+/// Gives a board the analysis board's position in place of the game shown, with the
+/// computer's play turned off and the analysis board's own view. The settings are the app's
+/// own, so that changes to them on either board show on both.
+pub struct AnalysisLens;
+
+impl Lens<AppState, AppState> for AnalysisLens {
+    fn with<V, F: FnOnce(&AppState) -> V>(&self, data: &AppState, f: F) -> V {
+        f(&analysis_view(data).0)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut AppState) -> V>(&self, data: &mut AppState, f: F) -> V {
+        let (mut view, mut game) = analysis_view(data);
+        let result = f(&mut view);
+        view.swap_game(&mut game);
+        std::mem::swap(&mut view.board_view, &mut view.analysis_board_view);
+        view.computer.enabled = data.computer.enabled;
+        view.analysis_board = data.analysis_board.is_some().then_some(game);
+        *data = view;
+        result
+    }
+}
+
+/// The app's state with the analysis board's position and view swapped in, and the game it
+/// replaced
+fn analysis_view(data: &AppState) -> (AppState, GameTab) {
+    let mut view = data.clone();
+    let mut game = data.analysis_board.clone().unwrap_or_else(|| GameTab::new(GameState::new(), starting_board()));
+    view.swap_game(&mut game);
+    std::mem::swap(&mut view.board_view, &mut view.analysis_board_view);
+    view.computer.enabled = false;
+    (view, game)
+}
+
+/// The position shown on the main board, browsed to or live, as a game of its own that starts
+/// there: moves played on the analysis board leave the game alone
+pub fn analysis_position(data: &AppState) -> Result<GameTab, String> {
+    let (state, board) = data.shown_position();
+    let (state, board) = parse_fen(&to_fen(&state, &board))?;
+    Ok(GameTab::new(state, board.into()))
+}
+
+/// A window with a board of its own, where the position it was opened with can be played on
+/// for both sides, analyzed and marked up
+pub fn analysis_window() -> WindowDesc<AppState> {
+    WindowDesc::new(themed(game_view(ChessBoard::analysis_board()).lens(AnalysisLens)))
+        .title("Analysis board")
+        .menu(menu_bar)
+        .window_size((760.0, 640.0))
+}
@@ -1,17 +1,408 @@
-use druid::Data;
+use std::sync::Arc;
+use druid::im::Vector;
+use druid::{Data, Lens};
+use crate::board::diagram::DiagramOptions;
+use crate::board::engine_panel::EngineReport;
+use crate::board::palette::{AnimationSpeed, BoardTheme, CustomColors, HighlightPalette, MoveIndicator};
+use crate::engine::ComputerOptions;
+use crate::game::annotation::{MarkColor, Marks};
+use crate::game::clock::{Clock, TimeControl};
+use crate::game::fen::{parse_fen, STARTING_FEN};
 use crate::game::game_state::GameState;
+use crate::game::pgn::{PgnGame, Position};
+use crate::game::player::Player;
+use crate::game::variation::SideLine;
+use crate::game::start::StartPosition;
+use crate::pieces::{Piece, PieceColor};
+use crate::theme::Appearance;
 
+/// A game open in a tab that is not shown, or on the analysis board. The game shown keeps its
+/// fields in AppState itself, where the board and the dialogs work on it.
 #[derive(Clone, Data)]
+pub struct GameTab {
+    game_state: GameState,
+    board: Vector<Option<Piece>>,
+    selected_square: Option<usize>,
+    viewed_ply: Option<usize>,
+    viewed_position: Option<Arc<Position>>,
+    clock: Option<Clock>,
+    lesson: Option<usize>,
+    arrows: Vector<(usize, usize, MarkColor)>,
+    circles: Vector<(usize, MarkColor)>,
+    variation: Option<Arc<Variation>>,
+}
+
+/// The game a line is being tried from, which waits meanwhile, and the ply the line starts at
+#[derive(Clone)]
+pub struct Variation {
+    game: GameTab,
+    ply: usize,
+}
+
+impl GameTab {
+    /// A game starting from `game_state` and `board`, without a clock
+    pub fn new(game_state: GameState, board: Vector<Option<Piece>>) -> Self {
+        Self {
+            game_state,
+            board,
+            selected_square: None,
+            viewed_ply: None,
+            viewed_position: None,
+            clock: None,
+            lesson: None,
+            arrows: Vector::new(),
+            circles: Vector::new(),
+            variation: None,
+        }
+    }
+}
+
+/// What a board window shows of its own work besides the game: the move being typed, the
+/// engine's findings and what the mouse is over. The main window and the analysis board each
+/// have their own.
+#[derive(Clone, Data, Default)]
+pub struct BoardView {
+    pub typed: String, // Move being typed in SAN, played with Enter
+    pub typed_rejected: bool, // Whether Enter found the move typed illegal, until the next key
+    pub peeking: bool, // Whether the peek key is held, showing the pieces in blindfold mode
+    pub analysing: bool, // Whether analysis mode is on
+    pub engine_report: Option<Arc<EngineReport>>, // Deepest report of the analysis, once there is one
+    pub reviewing: bool, // Whether a review of the game is running
+    pub hovered: Option<usize>, // Square under the mouse
+    pub promotion: Option<(usize, usize)>, // Pawn move waiting for the user to pick the piece it promotes to
+}
+
+#[derive(Clone, Data, Lens)]
 pub struct AppState {
     pub game_state: GameState,
+    pub board: Vector<Option<Piece>>, // Pieces of the live position by square, a8 first and h1 last
     pub selected_square: Option<usize>,
+    pub viewed_ply: Option<usize>, // Moves played in the position shown while browsing the game; None shows the live position
+    pub viewed_position: Option<Arc<Position>>, // The position viewed_ply shows, replayed from the moves
+    pub appearance: Appearance, // Light or dark windows
+    pub system_dark: bool, // Whether the system was in dark mode when the app started
+    pub low_vision: bool, // High-contrast squares, larger pieces and a larger move list
+    pub board_theme: BoardTheme, // Colors of the squares, unless low-vision mode overrides them
+    pub custom_colors: CustomColors, // Of the custom theme, and to replace highlight colors
+    pub highlight_palette: HighlightPalette,
+    pub piece_set: String, // Image set the pieces are drawn with; empty draws the built-in pieces
+    pub glyph_pieces: bool, // Built-in pieces drawn with the Unicode chess symbols instead of shapes
+    pub move_indicator: MoveIndicator,
+    pub animation: AnimationSpeed, // How fast the piece of each new move slides to its square
+    pub auto_queen: bool, // Promote pawns to a queen without asking which piece
+    pub figurine_notation: bool, // Show pieces as figurines in the move history
+    pub diagram: DiagramOptions,
+    pub left_handed: bool, // Move list on the left of the board
+    pub show_coordinates: bool, // File letters and rank numbers around the board
+    pub mirror_coordinates: bool, // Rank labels on the right of the board
+    pub show_threats: bool, // Warn about the mover's pieces that are attacked and underdefended
+    pub show_eval: bool, // Static evaluation in the status line
+    pub show_exchanges: bool, // Exchange calculator popup over a capture under the mouse
+    pub show_variation_tree: bool, // Panel with the game's side lines beside the board
+    pub blindfold: bool, // Pieces hidden, for playing from memory with typed moves
+    pub computer: ComputerOptions, // Play vs Computer mode
+    pub start_position: StartPosition, // Where the New game dialog starts the next game
+    pub start_fen: String, // Typed in the New game dialog for StartPosition::Fen
+    pub time_control: TimeControl, // Picked in the New game dialog
+    pub clock: Option<Clock>, // Time left in the current game, when it is played with a clock
+    pub lesson: Option<usize>, // Index into LESSONS while the rules tutorial runs
+    pub bulletin_games: Vector<bool>, // Games of the game list picked for the printed bulletin
+    pub turn_notification: bool, // Notify when the computer has moved while the window is in the background
+    pub turn_sound: bool, // Chime then as well
+    pub sound_effects: bool, // Sounds for moves, captures, castling, checks and the end of a game
+    pub sound_volume: f64, // Of the sound effects, from 0 to 1
+    pub analysis_lines: usize, // Best moves the analysis mode shows
+    pub tablebase_moves: bool, // Color the legal moves of the selected piece by their tablebase outcome
+    pub auto_rotate: bool, // Turn the board to the side to move after each move, for two players on one device
+    pub mark_color: MarkColor, // Color of the next arrow or circle drawn with the right mouse button
+    pub arrows: Vector<(usize, usize, MarkColor)>, // Drawn on the board, from and to square
+    pub circles: Vector<(usize, MarkColor)>, // Circled squares
+    pub variation: Option<Arc<Variation>>, // While moves are tried from a position browsed to, instead of the game
+    pub other_tabs: Vector<GameTab>, // Games open besides the one shown, in tab order
+    pub active_tab: usize, // Position of the game shown among the tabs
+    pub analysis_board: Option<GameTab>, // Position worked on in the analysis board window, while it is open
+    pub board_view: BoardView, // Of the main window
+    pub analysis_board_view: BoardView, // Of the analysis board window
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             game_state: GameState::new(),
+            board: starting_board(),
             selected_square: None,
+            viewed_ply: None,
+            viewed_position: None,
+            appearance: Appearance::System,
+            system_dark: false,
+            low_vision: false,
+            board_theme: BoardTheme::Grey,
+            custom_colors: CustomColors::new(),
+            highlight_palette: HighlightPalette::Standard,
+            piece_set: String::new(),
+            glyph_pieces: false,
+            move_indicator: MoveIndicator::Dots,
+            animation: AnimationSpeed::Normal,
+            auto_queen: false,
+            figurine_notation: false,
+            diagram: DiagramOptions::new(),
+            left_handed: false,
+            show_coordinates: true,
+            mirror_coordinates: false,
+            show_threats: false,
+            show_eval: false,
+            show_exchanges: false,
+            show_variation_tree: false,
+            blindfold: false,
+            computer: ComputerOptions::new(),
+            start_position: StartPosition::Standard,
+            start_fen: String::new(),
+            time_control: TimeControl::Unlimited,
+            clock: None,
+            lesson: None,
+            bulletin_games: Vector::new(),
+            turn_notification: true,
+            turn_sound: false,
+            sound_effects: true,
+            sound_volume: 0.7,
+            analysis_lines: 3,
+            tablebase_moves: false,
+            auto_rotate: false,
+            mark_color: MarkColor::Green,
+            arrows: Vector::new(),
+            circles: Vector::new(),
+            variation: None,
+            other_tabs: Vector::new(),
+            active_tab: 0,
+            analysis_board: None,
+            board_view: BoardView::default(),
+            analysis_board_view: BoardView::default(),
+        }
+    }
+
+    /// Whether the windows are dark, as picked in the settings or else as the system is set
+    pub fn dark_mode(&self) -> bool {
+        match self.appearance {
+            Appearance::System => self.system_dark,
+            Appearance::Light => false,
+            Appearance::Dark => true,
         }
     }
-}
\ No newline at end of file
+
+    /// The live position as the board vector the rules and the engines work on
+    pub fn current_board(&self) -> Vec<Option<Piece>> {
+        self.board.iter().copied().collect()
+    }
+
+    /// The position shown: the one browsed to in the move list, otherwise the live one
+    pub fn shown_position(&self) -> Position {
+        match (&self.viewed_position, self.viewed_ply) {
+            (Some(position), Some(_)) => Position::clone(position),
+            _ => (self.game_state.clone(), self.current_board()),
+        }
+    }
+
+    /// Shows the position after `ply` moves of the game, replayed from its moves; the last ply
+    /// goes back to the live position
+    pub fn view_ply(&mut self, ply: usize) -> Result<(), String> {
+        if ply >= self.game_state.san_moves.len() {
+            self.viewed_ply = None;
+            self.viewed_position = None;
+            self.show_marks();
+            return Ok(());
+        }
+        let mut positions = self.pgn().replay_positions()?;
+        self.viewed_position = Some(Arc::new(positions.swap_remove(ply)));
+        self.viewed_ply = Some(ply);
+        self.selected_square = None;
+        self.show_marks();
+        Ok(())
+    }
+
+    /// Draws the arrows and circles kept with the move that led to the position shown
+    pub fn show_marks(&mut self) {
+        let ply = self.viewed_ply.unwrap_or(self.game_state.san_moves.len());
+        let marks = ply.checked_sub(1).and_then(|last| self.game_state.move_marks.get(last).cloned().flatten()).unwrap_or_default();
+        self.arrows = marks.arrows;
+        self.circles = marks.circles;
+    }
+
+    /// Keeps the arrows and circles drawn with the move that led to the position shown, so that
+    /// they come back with it and are saved in PGN
+    pub fn record_marks(&mut self) {
+        let ply = self.viewed_ply.unwrap_or(self.game_state.san_moves.len());
+        let marks = Marks { arrows: self.arrows.clone(), circles: self.circles.clone() };
+        if let Some(slot) = ply.checked_sub(1).and_then(|last| self.game_state.move_marks.get_mut(last)) {
+            *slot = (!marks.is_empty()).then_some(marks);
+        }
+    }
+
+    /// The PGN record of the game, with the times its clock showed after each move
+    pub fn pgn(&self) -> PgnGame {
+        let mut game = PgnGame::from_game(&self.game_state);
+        if let Some(clock) = &self.clock {
+            game.set_clock(clock);
+        }
+        game
+    }
+
+    /// Who plays `color`: the player named by the game, otherwise the computer or the user when
+    /// playing the computer
+    pub fn player_label(&self, color: PieceColor) -> String {
+        let player = match color {
+            PieceColor::White => &self.game_state.white_player,
+            PieceColor::Black => &self.game_state.black_player,
+        };
+        match player {
+            Some(player) => player.label(),
+            None if self.computer.enabled && self.computer.color == color => "Computer".to_string(),
+            None if self.computer.enabled => "You".to_string(),
+            None if color == PieceColor::White => "White".to_string(),
+            None => "Black".to_string(),
+        }
+    }
+
+    /// Whether the board is shown with Black at the bottom: as picked for the game, otherwise
+    /// from the side of the user when playing the computer
+    pub fn flipped(&self) -> bool {
+        self.game_state.flipped.unwrap_or(self.computer.enabled && self.computer.color == PieceColor::White)
+    }
+
+    /// Number of games open, counting the one shown
+    pub fn tab_count(&self) -> usize {
+        self.other_tabs.len() + 1
+    }
+
+    /// What the tabs are called, in order: the players when they are known, otherwise the
+    /// number of the tab
+    pub fn tab_labels(&self) -> Vector<String> {
+        let mut states: Vec<&GameState> = self.other_tabs.iter().map(|tab| &tab.game_state).collect();
+        states.insert(self.active_tab, &self.game_state);
+        states.iter().enumerate().map(|(i, state)| tab_label(state, i + 1)).collect()
+    }
+
+    /// Opens a new game in a tab after the one shown, and shows it
+    pub fn open_tab(&mut self) {
+        let shown = self.take_game();
+        self.other_tabs.insert(self.active_tab, shown);
+        self.active_tab += 1;
+    }
+
+    /// Shows the game of the tab at `index`, keeping the one shown in its own tab
+    pub fn switch_tab(&mut self, index: usize) {
+        if index == self.active_tab || index >= self.tab_count() {
+            return;
+        }
+        let shown = self.take_game();
+        self.other_tabs.insert(self.active_tab, shown);
+        let tab = self.other_tabs.remove(index);
+        self.show_game(tab);
+        self.active_tab = index;
+    }
+
+    /// Closes the game shown and shows the tab after it, or the one before for the last tab. The
+    /// only game open is replaced by a new one.
+    pub fn close_tab(&mut self) {
+        self.take_game();
+        if !self.other_tabs.is_empty() {
+            self.active_tab = self.active_tab.min(self.other_tabs.len() - 1);
+            let tab = self.other_tabs.remove(self.active_tab);
+            self.show_game(tab);
+        }
+    }
+
+    /// Moves the game shown out into a tab, leaving a new game in its place. The tab shows its
+    /// live position once it comes back, as the board forgets the one browsed to.
+    fn take_game(&mut self) -> GameTab {
+        let mut tab = GameTab::new(GameState::new(), starting_board());
+        self.swap_game(&mut tab);
+        tab.selected_square = None;
+        tab.viewed_ply = None;
+        tab.viewed_position = None;
+        tab
+    }
+
+    fn show_game(&mut self, mut tab: GameTab) {
+        self.swap_game(&mut tab);
+    }
+
+    /// Exchanges the game shown with the one in `tab`
+    pub fn swap_game(&mut self, tab: &mut GameTab) {
+        std::mem::swap(&mut self.game_state, &mut tab.game_state);
+        std::mem::swap(&mut self.board, &mut tab.board);
+        std::mem::swap(&mut self.selected_square, &mut tab.selected_square);
+        std::mem::swap(&mut self.viewed_ply, &mut tab.viewed_ply);
+        std::mem::swap(&mut self.viewed_position, &mut tab.viewed_position);
+        std::mem::swap(&mut self.clock, &mut tab.clock);
+        std::mem::swap(&mut self.lesson, &mut tab.lesson);
+        std::mem::swap(&mut self.arrows, &mut tab.arrows);
+        std::mem::swap(&mut self.circles, &mut tab.circles);
+        std::mem::swap(&mut self.variation, &mut tab.variation);
+    }
+
+    /// Sets the game aside to try moves from `position`, the one after `ply` moves of it. The
+    /// game comes back as it was when the line is kept or discarded.
+    pub fn try_variation(&mut self, ply: usize, (game_state, board): Position) {
+        let mut game = GameTab::new(game_state, board.into());
+        game.arrows = self.arrows.clone();
+        game.circles = self.circles.clone();
+        self.swap_game(&mut game);
+        game.selected_square = None;
+        game.viewed_ply = None;
+        game.viewed_position = None;
+        self.variation = Some(Arc::new(Variation { game, ply }));
+    }
+
+    /// Move number the line tried starts at, as shown in the move list
+    pub fn variation_start(&self) -> Option<usize> {
+        self.variation.as_ref().map(|variation| variation.ply)
+    }
+
+    /// The game itself, under any lines being tried from it
+    pub fn root_game(&self) -> &GameState {
+        let mut game = &self.game_state;
+        let mut variation = self.variation.as_ref();
+        while let Some(tried) = variation {
+            game = &tried.game.game_state;
+            variation = tried.game.variation.as_ref();
+        }
+        game
+    }
+
+    /// Returns to the game, dropping the line tried
+    pub fn discard_variation(&mut self) {
+        if let Some(variation) = self.variation.take() {
+            let mut game = Arc::unwrap_or_clone(variation).game;
+            self.swap_game(&mut game);
+        }
+    }
+
+    /// Returns to the game, keeping the moves tried as a side line of it for PGN. Nothing is
+    /// kept when the moves were taken back past the position the line started from, or when
+    /// the game has that line already.
+    pub fn keep_variation(&mut self) {
+        let Some(ply) = self.variation_start() else { return };
+        let moves = self.game_state.san_moves.clone();
+        self.discard_variation();
+        let starts_here = moves.len() > ply && moves.iter().take(ply).eq(self.game_state.san_moves.iter().take(ply));
+        let line = SideLine { parent: None, ply, moves: moves.skip(ply) };
+        if starts_here && ply < self.game_state.san_moves.len() && !self.game_state.variations.contains(&line) {
+            self.game_state.variations.push_back(line);
+        }
+    }
+}
+
+/// The pieces at the start of a standard game
+pub fn starting_board() -> Vector<Option<Piece>> {
+    parse_fen(STARTING_FEN).expect("the starting position is valid FEN").1.into()
+}
+
+/// The players of a game as "White - Black" when either is known, otherwise "Game" and `number`
+fn tab_label(state: &GameState, number: usize) -> String {
+    let name = |player: &Option<Player>| player.as_ref().map_or("?".to_string(), |player| player.name.clone());
+    if state.white_player.is_none() && state.black_player.is_none() {
+        format!("Game {}", number)
+    } else {
+        format!("{} - {}", name(&state.white_player), name(&state.black_player))
+    }
+}
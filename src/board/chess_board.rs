@@ -1,78 +1,383 @@
-use druid::{Widget, Color, RenderContext};
-use druid::piet::{Text, TextLayoutBuilder};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::Rng;
+use druid::im::Vector;
+use druid::{commands, Application, BoxConstraints, Data, ExtEventSink, FileDialogOptions, FileInfo, FileSpec, KbKey, Selector, Target, TimerToken, Widget, WidgetPod, Color, HotKey, RenderContext, SysMods};
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use crate::alert::{chime, notify, play_effect, SoundEffect};
 use crate::app::AppState;
+use crate::book::polyglot::{polyglot_key, BookMove, PolyglotBook};
+use crate::bulletin::export_bulletin;
+use crate::dialog::{error_dialog, game_list_dialog, history_dialog, message_dialog, NEW_GAME, OPEN_LISTED_GAME, PRINT_BULLETIN, RESTORE_SNAPSHOT};
+use crate::engine::{accepts_draw, computer_move, predicted_reply};
+use crate::engine::analysis_cache::AnalysisCache;
+use crate::engine::eval::evaluate;
+use crate::engine::search::{analyse, best_move, Analysis};
+use crate::engine::external::load_engines;
+use crate::engine::review::{review_game, Review, ReviewedMove};
+use crate::engine::tablebase::{Tablebase, TablebaseResult};
+use crate::game::annotation::MoveQuality;
+use crate::game::start::{chess960_fen, StartPosition};
+use crate::game::epd::EpdRecord;
+use crate::game::fen::{parse_fen, to_fen, STARTING_FEN};
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::import::{detect_format, import_text, ImportFormat};
+use crate::game::notation::{parse_san, ParsedMove};
+use crate::game::pgn::{parse_pgn_games, PgnGame};
+use crate::game::save::{load_game, save_game};
+use crate::game::threats::threatened_pieces;
+use crate::game::variation::{mainline_ply, moves_to};
+use crate::lessons::LESSONS;
+use crate::menu::{CLOSE_TAB, COPY_EPD, COPY_FEN, COPY_PGN, FLIP_BOARD, NEW_TAB, OPEN_CHESS_COM, OPEN_GAME, OPEN_LICHESS, PASTE_POSITION, PGN_FILE, REDO, REVIEW_GAME, SAVE_GAME, SHOW_EXPORT_DIAGRAM, SHOW_EXPORT_GIF, SHOW_HINT, SHOW_HISTORY, SHOW_PRINT_SCORESHEET, START_LESSONS, TOGGLE_ANALYSIS, TOGGLE_BOOK_HINT, TOGGLE_HUD, UNDO};
 use crate::pieces::*;
-use super::chess_square::ChessSquare;
+use crate::scoresheet::export_scoresheet;
+use crate::share::{chess_com_analysis_url, lichess_analysis_url, open_in_browser};
+use crate::tabs::SWITCH_TAB;
+use crate::timeline::{age, Timeline};
+use super::diagram::{export_diagram, export_gif};
+use super::engine_panel::{EngineReport, ANALYSIS_LINE_MOVES, PLAY_ANALYSIS_MOVE};
+use super::hud::FrameStats;
+use super::move_list::{Navigation, SHOW_MOVE_REVIEW};
+use super::palette::{move_quality_color, tablebase_color, MoveIndicator};
+use super::piece_set::{switch_piece_set, PieceSet};
+use super::piece_shapes::piece_shapes;
+use super::popups::{ExchangePopup, PromotionPicker, PICK_PROMOTION};
+use super::toolbar::{GameAction, GAME_ACTION};
+use super::variation_tree::SHOW_LINE_MOVE;
 
+/// Sent by the save panel of the "Export diagram" action
+const EXPORT_DIAGRAM: Selector<FileInfo> = Selector::new("chess_rust.export-diagram");
+
+/// Sent by the save panel of the "Export GIF" action
+const EXPORT_GIF: Selector<FileInfo> = Selector::new("chess_rust.export-gif");
+
+/// Sent by the save panel of the "Print scoresheet" action
+const EXPORT_SCORESHEET: Selector<FileInfo> = Selector::new("chess_rust.export-scoresheet");
+
+/// Sent by the save panel of the "Print bulletin" action of the game list
+const EXPORT_BULLETIN: Selector<FileInfo> = Selector::new("chess_rust.export-bulletin");
+
+
+/// Sent from the search thread with the search number and the move found, or why the external
+/// engine failed
+const COMPUTER_MOVE: Selector<(u64, Result<Option<ParsedMove>, String>)> = Selector::new("chess_rust.computer-move");
+
+/// Sent from the pondering thread with its search number and the key of the position after the
+/// reply it expects
+const PONDERING: Selector<(u64, u64)> = Selector::new("chess_rust.pondering");
+
+/// Sent from the hint search thread with the key of the position searched and the move found
+const HINT: Selector<(u64, Option<ParsedMove>)> = Selector::new("chess_rust.hint");
+
+/// Sent from the review thread with the moves of the game reviewed and the findings about them
+const REVIEW: Selector<(Vector<String>, Result<Review, String>)> = Selector::new("chess_rust.review");
+
+/// Sent from the analysis thread with the analysis number and what it found at the last depth
+const ANALYSIS: Selector<(u64, Analysis)> = Selector::new("chess_rust.analysis");
+
+/// Sent from the analysis thread with the analysis number when the external engine fails
+const ANALYSIS_FAILED: Selector<(u64, String)> = Selector::new("chess_rust.analysis-failed");
+
+/// Sent to the board itself when the piece set picked in the settings cannot be loaded
+const PIECE_SET_FAILED: Selector<String> = Selector::new("chess_rust.piece-set-failed");
+
+/// Sent to the board itself when the game ends, to review it
+const START_REVIEW: Selector = Selector::new("chess_rust.start-review");
+
+/// Plies searched for a hint; enough to see simple tactics and quick to answer
+const HINT_DEPTH: u32 = 3;
+
+/// Opening book looked up for the book move hint, relative to the working directory
+const BOOK_PATH: &str = "book.bin";
+
+/// Folder of the Syzygy endgame tablebases, relative to the working directory
+const TABLEBASE_PATH: &str = "syzygy";
+
+/// File the analyses of the positions looked at are kept in, relative to the working directory
+const ANALYSIS_CACHE_PATH: &str = "analysis_cache.jsonl";
+
+/// Pause after a move before the board turns to the other player, so that the move can be seen
+const ROTATE_DELAY: Duration = Duration::from_millis(700);
+
+/// How often the game clock is updated
+const CLOCK_TICK: Duration = Duration::from_millis(100);
+
+/// Characters a move typed in SAN can have; zeros are taken for the O of castling
+const SAN_CHARACTERS: &str = "abcdefgh12345678NBRQKOox=+#-0";
+
+/// Letters picking the piece of a pending promotion from the keyboard
+const PROMOTION_KEYS: [(&str, PieceType); 4] = [("q", PieceType::Queen), ("r", PieceType::Rook), ("b", PieceType::Bishop), ("n", PieceType::Knight)];
+
+/// Key held to see the pieces in blindfold mode
+const PEEK_KEY: &str = " ";
+
+/// Room above the board for the frame of a line tried and the keyboard cursor
+const BOARD_MARGIN: f64 = 6.0;
+
+/// Height of the file letters below the board
+const COORDINATES_HEIGHT: f64 = 20.0;
+
+/// Room for the rank labels on either side of the board
+const RANK_LABELS_WIDTH: f64 = 20.0;
+
+/// Square size the board asks for where the space it is given is unbounded
+const PREFERRED_SQUARE_SIZE: f64 = 60.0;
+
+/// The computer thinking on the user's time about its answer to the reply it expects
+struct Ponder {
+    search: u64, // Number of the search for the answer
+    stop: Arc<AtomicBool>, // Set to give up the search, once the guess is missed or the game changes
+    key: Option<u64>, // Key of the position after the expected reply, once it is guessed
+    found: Option<Result<Option<ParsedMove>, String>>, // The answer, once found
+}
+
+/// Where the board sits in the widget. Computed from the widget's current size and scale each
+/// time it is needed, so that a resize or scale change between two events never leaves stale
+/// coordinates. Squares are a whole number of device pixels and start on a device pixel, so
+/// that their edges stay sharp on high-DPI displays and clicks land on the square painted under
+/// them.
+struct Geometry {
+    square_size: f64,
+    board_width: f64,
+    x_offset: f64,
+    y_offset: f64,
+}
+
+impl Geometry {
+    fn new(size: druid::Size, scale: druid::Scale) -> Self {
+        // The widget is laid out as tall as its board, so rounding gives back the squares it fitted
+        let square_size = Self::square_pixels(size, scale, f64::round) / scale.x();
+        let board_width = 8.0 * square_size;
+        let x_offset = ((size.width - board_width) / 2.0).max(0.0);
+        Self {
+            square_size,
+            board_width,
+            x_offset: (x_offset * scale.x()).round() / scale.x(),
+            y_offset: (BOARD_MARGIN * scale.y()).round() / scale.y(),
+        }
+    }
+
+    /// Device pixels of a square of the board fitted into `size` with its labels around it,
+    /// taking the height's share of a square to pixels with `to_pixels`
+    fn square_pixels(size: druid::Size, scale: druid::Scale, to_pixels: fn(f64) -> f64) -> f64 {
+        let across = ((size.width - 2.0 * RANK_LABELS_WIDTH) / 8.0 * scale.x()).floor();
+        let down = to_pixels((size.height - BOARD_MARGIN - COORDINATES_HEIGHT) / 8.0 * scale.y());
+        across.min(down).max(0.0)
+    }
+
+    /// The height of a widget with a board of `square_size` and the labels around it
+    fn height(square_size: f64) -> f64 {
+        BOARD_MARGIN + 8.0 * square_size + COORDINATES_HEIGHT
+    }
+
+    /// Board square under a point of the widget, if any
+    fn square_at(&self, pos: druid::Point, flipped: bool) -> Option<usize> {
+        let board_x = pos.x - self.x_offset;
+        let board_y = pos.y - self.y_offset;
+        if board_x >= 0.0 && board_x < self.board_width && board_y >= 0.0 && board_y < self.board_width {
+            let (row, col) = oriented((board_y / self.square_size) as usize, (board_x / self.square_size) as usize, flipped);
+            Some(row * 8 + col)
+        } else {
+            None
+        }
+    }
+}
 
 pub struct ChessBoard {
-    squares: Vec<ChessSquare>,
+    book: Option<Arc<PolyglotBook>>, // Shared with the search thread
+    show_book_hint: bool,
+    tablebase: Option<Tablebase>,
+    listed_games: Vec<PgnGame>, // Games of the last multi-game PGN, shown in the game list
+    computer_timer: TimerToken, // Pending reply of the computer
+    rotate_timer: TimerToken, // Pending turn of the board to the side to move
+    clock_timer: TimerToken, // Next tick of the game clock
+    clock_tick: Option<(Instant, usize)>, // When the clock last ran, with the moves played by then
+    search: Option<(u64, Arc<AtomicBool>)>, // Number of the search running for the computer, if any, and what stops it
+    searches: u64, // Searches started, so that results for an abandoned position are dropped
+    ponder: Option<Ponder>,
+    timeline: Timeline,
+    hud: Option<FrameStats>, // Debug HUD with paint timings, shown when set
+    hint: Option<(u64, ParsedMove)>, // Engine's suggestion and the key of its position
+    analysis: Option<Arc<AtomicBool>>, // Stops the running analysis; set while analysis mode is on
+    analyses: u64, // Analyses started, so that reports about an earlier position are dropped
+    analysis_cache: AnalysisCache, // Deepest analysis of the positions analysed before
+    review: Option<(Vector<String>, Review)>, // Moves of the game last reviewed and the findings
+    mark_start: Option<usize>, // Square the right mouse button went down on, where an arrow starts
+    promotion_picker: WidgetPod<AppState, PromotionPicker>, // Over the promotion square while a promotion is pending
+    exchange_popup: WidgetPod<AppState, ExchangePopup>, // Beside the capture under the mouse
+    animation: Option<(usize, usize, Instant)>, // Squares of the move whose piece is sliding, and when it set off
+    piece_set: Option<PieceSet>, // Images the pieces are drawn with, instead of the built-in shapes
+    scratch: bool, // Whether this is the analysis board, which works on a position of its own and has no tabs
+    cursor: Option<usize>, // Square of the keyboard cursor, once the arrow keys brought it up
+    targets: Vec<usize>, // Squares the selected piece can move to, worked out when the selection or the position changes
+    tablebase_targets: Vec<(usize, TablebaseResult)>, // The tablebase's verdict on each of them, when shown
 }
 
 impl ChessBoard {
     pub fn new() -> Self {
-        let mut squares = Vec::with_capacity(64);
-        for row in 0..8 {
-            for col in 0..8 {
-                let is_light = (row + col) % 2 == 0;
-                let piece = match row {
-                    0 => Some(Piece {
-                        piece_type: match col {
-                            0 | 7 => PieceType::Rook,
-                            1 | 6 => PieceType::Knight,
-                            2 | 5 => PieceType::Bishop,
-                            3 => PieceType::Queen,
-                            4 => PieceType::King,
-                            _ => unreachable!(),
-                        },
-                        color: PieceColor::Black,
-                    }),
-                    1 => Some(Piece {
-                        piece_type: PieceType::Pawn,
-                        color: PieceColor::Black,
-                    }),
-                    6 => Some(Piece {
-                        piece_type: PieceType::Pawn,
-                        color: PieceColor::White,
-                    }),
-                    7 => Some(Piece {
-                        piece_type: match col {
-                            0 | 7 => PieceType::Rook,
-                            1 | 6 => PieceType::Knight,
-                            2 | 5 => PieceType::Bishop,
-                            3 => PieceType::Queen,
-                            4 => PieceType::King,
-                            _ => unreachable!(),
-                        },
-                        color: PieceColor::White,
-                    }),
-                    _ => None,
-                };
-                squares.push(ChessSquare::new(is_light, piece));
-            }
+        Self {
+            book: PolyglotBook::open(BOOK_PATH).ok().map(Arc::new),
+            show_book_hint: false,
+            tablebase: Tablebase::open(TABLEBASE_PATH).ok(),
+            listed_games: Vec::new(),
+            computer_timer: TimerToken::INVALID,
+            rotate_timer: TimerToken::INVALID,
+            clock_timer: TimerToken::INVALID,
+            clock_tick: None,
+            search: None,
+            searches: 0,
+            ponder: None,
+            timeline: Timeline::new(),
+            hud: None,
+            hint: None,
+            analysis: None,
+            analyses: 0,
+            analysis_cache: AnalysisCache::open(ANALYSIS_CACHE_PATH),
+            review: None,
+            mark_start: None,
+            promotion_picker: WidgetPod::new(PromotionPicker::new()),
+            exchange_popup: WidgetPod::new(ExchangePopup::new()),
+            animation: None,
+            piece_set: None,
+            scratch: false,
+            cursor: None,
+            targets: Vec::new(),
+            tablebase_targets: Vec::new(),
         }
-        Self { squares }
     }
 
-    pub fn get_piece_at(&self, idx: usize) -> Option<Piece> {
-        if idx >= 64 {
-            return None;
+    /// The board of the analysis window
+    pub fn analysis_board() -> Self {
+        Self { scratch: true, ..Self::new() }
+    }
+
+    /// Acts on a square clicked, or picked with the keyboard cursor: selects a piece of the side
+    /// to move, or moves the selected piece there. Picking up a piece of the side to move in a
+    /// position browsed to starts a line tried from there, leaving the game as it is.
+    fn press_square(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, square_idx: usize) {
+        if let Some(ply) = data.viewed_ply {
+            let (state, board) = data.shown_position();
+            match board[square_idx] {
+                Some(piece) if piece.color == state.current_turn && data.lesson.is_none() => {
+                    data.try_variation(ply, (state, board));
+                    self.forget_position(data);
+                }
+                _ => return,
+            }
         }
-        self.squares[idx].piece
+        if let Some(selected) = data.selected_square {
+            if selected == square_idx {
+                // Clicking the same square deselects it
+                data.selected_square = None;
+            } else if !data.auto_queen && self.is_promotion(selected, square_idx, data) {
+                // Ask which piece the pawn becomes
+                data.board_view.promotion = Some((selected, square_idx));
+            } else if self.make_move(selected, square_idx, PieceType::Queen, data) {
+                data.selected_square = None;
+                self.check_lesson(ctx, data);
+            }
+        } else if let Some(piece) = data.board[square_idx] {
+            // Select a piece of the current player's color, unless the computer plays it
+            if piece.color == data.game_state.current_turn && !Self::computer_to_move(data) {
+                data.selected_square = Some(square_idx);
+            }
+        }
+        ctx.request_paint();
     }
 
-    fn get_possible_moves(&self, square_idx: usize, game_state: &AppState) -> Vec<usize> {
-        let _piece = match self.get_piece_at(square_idx) {
-            Some(p) => p,
-            None => return vec![],
+    /// Moves the keyboard cursor by `rows` and `cols` as the board is shown, stopping at its
+    /// edges. The cursor appears on the selected piece, or else on the king of the side to move.
+    fn move_cursor(&mut self, data: &AppState, rows: isize, cols: isize) {
+        let flipped = data.flipped();
+        let Some(square) = self.cursor else {
+            let turn = data.game_state.current_turn;
+            self.cursor = data.selected_square.or_else(|| {
+                data.board.iter().position(|occupant| matches!(occupant, Some(p) if p.piece_type == PieceType::King && p.color == turn))
+            });
+            return;
         };
+        let (row, col) = oriented(square / 8, square % 8, flipped);
+        let (row, col) = ((row as isize + rows).clamp(0, 7) as usize, (col as isize + cols).clamp(0, 7) as usize);
+        let (row, col) = oriented(row, col, flipped);
+        self.cursor = Some(row * 8 + col);
+    }
+
+    /// Takes a key of a move typed in SAN: letters, digits and signs add to it, Backspace takes
+    /// the last one back, Escape drops the move and Enter plays it. A move typed in a position
+    /// browsed to starts a line tried from there, as picking up a piece does. Returns whether
+    /// the key was taken.
+    fn type_move(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, key_event: &druid::KeyEvent) -> bool {
+        if key_event.mods.ctrl() || key_event.mods.meta() || key_event.mods.alt() {
+            return false;
+        }
+        data.board_view.typed_rejected = false;
+        match &key_event.key {
+            KbKey::Character(text) if text.chars().all(|c| SAN_CHARACTERS.contains(c)) => data.board_view.typed.push_str(text),
+            KbKey::Backspace if !data.board_view.typed.is_empty() => {
+                data.board_view.typed.pop();
+            }
+            KbKey::Escape if !data.board_view.typed.is_empty() => data.board_view.typed.clear(),
+            KbKey::Enter if !data.board_view.typed.is_empty() => {
+                // Castling is often typed with zeros
+                let san = data.board_view.typed.replace('0', "O");
+                let (state, board) = data.shown_position();
+                let found = parse_san(&state, &board, &san).filter(|_| !Self::computer_to_move(data));
+                let Some((from, to, promotion)) = found else {
+                    data.board_view.typed_rejected = true;
+                    return true;
+                };
+                if let Some(ply) = data.viewed_ply {
+                    if data.lesson.is_some() {
+                        data.board_view.typed_rejected = true;
+                        return true;
+                    }
+                    data.try_variation(ply, (state, board));
+                    self.forget_position(data);
+                }
+                if self.make_move(from.0 * 8 + from.1, to.0 * 8 + to.1, promotion, data) {
+                    data.selected_square = None;
+                    data.board_view.typed.clear();
+                    self.check_lesson(ctx, data);
+                } else {
+                    data.board_view.typed_rejected = true;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Shows the position after `count` moves of a line of the game, as picked in the variation
+    /// tree: browsed to on the mainline, otherwise tried from where the side line leaves it
+    fn show_line_move(&mut self, data: &mut AppState, line: Option<usize>, count: usize) -> Result<(), String> {
+        while data.variation.is_some() {
+            data.discard_variation();
+        }
+        self.forget_position(data);
+        let Some(index) = line else { return data.view_ply(count) };
+        let start = mainline_ply(&data.game_state.variations, index);
+        let mut game = PgnGame::from_game(&data.game_state);
+        game.moves = moves_to(&data.game_state.san_moves, &data.game_state.variations, line, count);
+        game.comments.truncate(start);
+        game.qualities.truncate(start);
+        game.clocks.truncate(start);
+        game.evals.truncate(start);
+        game.marks.truncate(start);
+        game.variations.clear();
+        let position = game.replay()?;
+        data.try_variation(start, position);
+        Ok(())
+    }
 
-        // Convert squares to board representation for game state
-        let mut board = Vec::with_capacity(64);
-        for square in &self.squares {
-            board.push(square.piece);
+    fn get_possible_moves(&self, square_idx: usize, data: &AppState) -> Vec<usize> {
+        if data.board[square_idx].is_none() {
+            return vec![];
         }
 
+        let board = data.current_board();
+
         let row = square_idx / 8;
         let col = square_idx % 8;
         let from = (row, col);
@@ -82,7 +387,7 @@ impl ChessBoard {
         for to_row in 0..8 {
             for to_col in 0..8 {
                 let to = (to_row, to_col);
-                if game_state.game_state.is_valid_move(from, to, &board) {
+                if data.game_state.is_valid_move(from, to, &board) {
                     valid_moves.push(to_row * 8 + to_col);
                 }
             }
@@ -91,281 +396,1309 @@ impl ChessBoard {
         valid_moves
     }
 
-    fn make_move(&mut self, from_idx: usize, to_idx: usize, game_state: &mut AppState) -> bool {
-        // Convert squares to board representation for game state
-        let mut board = Vec::with_capacity(64);
-        for square in &self.squares {
-            board.push(square.piece);
+    /// Whether moving the piece on `from_idx` to `to_idx` is a legal pawn move to the last rank
+    fn is_promotion(&self, from_idx: usize, to_idx: usize, data: &AppState) -> bool {
+        matches!(data.board[from_idx], Some(piece) if piece.piece_type == PieceType::Pawn)
+            && (to_idx / 8 == 0 || to_idx / 8 == 7)
+            && data.game_state.is_valid_move((from_idx / 8, from_idx % 8), (to_idx / 8, to_idx % 8), &data.current_board())
+    }
+
+    /// Plays the pending promotion with the piece picked
+    fn promote(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, piece_type: PieceType) {
+        let Some((from, to)) = data.board_view.promotion.take() else { return };
+        if self.make_move(from, to, piece_type, data) {
+            data.selected_square = None;
+            self.check_lesson(ctx, data);
         }
+        ctx.request_paint();
+    }
+
+    fn make_move(&mut self, from_idx: usize, to_idx: usize, promotion: PieceType, data: &mut AppState) -> bool {
+        let mut board = data.current_board();
 
         let from = (from_idx / 8, from_idx % 8);
         let to = (to_idx / 8, to_idx % 8);
 
-        if game_state.game_state.make_move(from, to, &mut board) {
-            // Update the chess board with the new state
-            for (i, piece) in board.into_iter().enumerate() {
-                self.squares[i].piece = piece;
-            }
+        if data.game_state.make_move_with_promotion(from, to, promotion, &mut board) {
+            data.board = board.into();
             true
         } else {
             false
         }
     }
+
+    /// Replaces the position with a FEN or PGN taken from the clipboard
+    fn paste_position(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState) {
+        let text = match Application::global().clipboard().get_string() {
+            Some(text) => text,
+            None => {
+                ctx.new_window(error_dialog("Paste failed", "The clipboard does not contain any text."));
+                return;
+            }
+        };
+
+        self.open_text(ctx, data, &text, "Pasted from the clipboard", "Paste failed");
+    }
+
+    /// Loads imported text, letting the user pick a game when it is a PGN with several.
+    /// `label` describes where the text came from in the session timeline.
+    fn open_text(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, text: &str, label: &str, error_title: &str) {
+        if detect_format(text) == Some(ImportFormat::Pgn) {
+            if let Ok(games) = parse_pgn_games(text) {
+                if games.len() > 1 {
+                    ctx.new_window(game_list_dialog(&games));
+                    data.bulletin_games = games.iter().map(|_| true).collect();
+                    self.listed_games = games;
+                    return;
+                }
+            }
+        }
+
+        match import_text(text) {
+            Ok((game_state, board)) => self.set_position(ctx, data, game_state, board, label),
+            Err(message) => ctx.new_window(error_dialog(error_title, &message)),
+        }
+    }
+
+    /// Sets up a lesson of the rules tutorial, or ends the tutorial past the last one
+    fn start_lesson(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, index: usize) {
+        match LESSONS.get(index) {
+            Some(lesson) => {
+                let (game_state, board) = parse_fen(lesson.fen).expect("lesson positions are valid FEN");
+                self.set_position(ctx, data, game_state, board, &format!("Rules lesson: {}", lesson.title));
+                data.lesson = Some(index);
+            }
+            None => {
+                data.lesson = None;
+                ctx.new_window(message_dialog("Rules tutorial", "You have completed all the lessons. Enjoy your games!"));
+            }
+        }
+    }
+
+    /// Checks the move just played in a lesson, moving on when it reached the goal and
+    /// setting the position up again otherwise
+    fn check_lesson(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState) {
+        let Some(index) = data.lesson else { return };
+        let lesson = &LESSONS[index];
+        if lesson.goal.reached(&data.game_state) {
+            ctx.new_window(message_dialog(&format!("{} - well done!", lesson.title), lesson.explanation));
+            self.start_lesson(ctx, data, index + 1);
+        } else {
+            ctx.new_window(message_dialog(&format!("{} - try again", lesson.title), lesson.hint));
+            self.start_lesson(ctx, data, index);
+        }
+    }
+
+    /// Whether it is the computer's turn in an unfinished game
+    pub(super) fn computer_to_move(data: &AppState) -> bool {
+        data.computer.enabled
+            && data.lesson.is_none()
+            && data.variation.is_none()
+            && data.game_state.current_turn == data.computer.color
+            && !data.game_state.is_over()
+    }
+
+    /// Looks for the computer's move on another thread, which sends it back as `COMPUTER_MOVE`
+    fn start_computer_move(&mut self, ctx: &mut druid::EventCtx, data: &AppState) {
+        // On a ponder hit the answer is already found, or being searched. On a miss it is dropped.
+        if let Some(ponder) = self.ponder.take() {
+            if ponder.key == Some(polyglot_key(&data.game_state, &data.current_board())) {
+                self.search = Some((ponder.search, ponder.stop));
+                if let Some(found) = ponder.found {
+                    ctx.submit_command(COMPUTER_MOVE.with((ponder.search, found)));
+                }
+                return;
+            }
+            ponder.stop.store(true, Ordering::Relaxed);
+        }
+        self.searches += 1;
+        let search = self.searches;
+        let stop = Arc::new(AtomicBool::new(false));
+        self.search = Some((search, stop.clone()));
+        let sink = ctx.get_external_handle();
+        let (options, state, board, book) = (data.computer.clone(), data.game_state.clone(), data.current_board(), self.book.clone());
+        thread::spawn(move || {
+            let found = computer_move(&options, &state, &board, book.as_deref(), &stop);
+            let _ = sink.submit_command(COMPUTER_MOVE, (search, found), Target::Auto);
+        });
+    }
+
+    /// Guesses the user's reply on another thread and searches the computer's answer to it while
+    /// the user thinks, sending the guess as `PONDERING` and the answer as `COMPUTER_MOVE`
+    fn start_ponder(&mut self, sink: ExtEventSink, data: &AppState) {
+        self.searches += 1;
+        let search = self.searches;
+        let stop = Arc::new(AtomicBool::new(false));
+        self.ponder = Some(Ponder { search, stop: stop.clone(), key: None, found: None });
+        let (options, mut state, mut board, book) = (data.computer.clone(), data.game_state.clone(), data.current_board(), self.book.clone());
+        thread::spawn(move || {
+            let Some((from, to, promotion)) = predicted_reply(&state, &board) else { return };
+            state.make_move_with_promotion(from, to, promotion, &mut board);
+            let _ = sink.submit_command(PONDERING, (search, polyglot_key(&state, &board)), Target::Auto);
+            let found = computer_move(&options, &state, &board, book.as_deref(), &stop);
+            let _ = sink.submit_command(COMPUTER_MOVE, (search, found), Target::Auto);
+        });
+    }
+
+    /// Searches the human's best move on another thread, which sends it back as `HINT`
+    fn start_hint(&self, ctx: &mut druid::EventCtx, data: &AppState) {
+        let sink = ctx.get_external_handle();
+        let (state, board) = (data.game_state.clone(), data.current_board());
+        thread::spawn(move || {
+            let found = best_move(&state, &board, HINT_DEPTH, 16, 1, evaluate);
+            let _ = sink.submit_command(HINT, (polyglot_key(&state, &board), found), Target::Auto);
+        });
+    }
+
+    /// Analyses the position shown on another thread until the position changes, reporting each
+    /// depth as `ANALYSIS`. What was found about the position before is reported first.
+    fn start_analysis(&mut self, sink: ExtEventSink, data: &AppState) {
+        self.stop_analysis();
+        self.analyses += 1;
+        let analysis = self.analyses;
+        let stop = Arc::new(AtomicBool::new(false));
+        self.analysis = Some(stop.clone());
+        let (state, board) = data.shown_position();
+        let (lines, hash_mb) = (data.analysis_lines, data.computer.hash_mb as usize);
+        let external = data.computer.external_analysis.then(|| data.computer.external_engine(data.computer.analysis_engine).cloned());
+        let cached = self.analysis_cache.get(polyglot_key(&state, &board), lines);
+        thread::spawn(move || {
+            let send = |report| {
+                let _ = sink.submit_command(ANALYSIS, (analysis, report), Target::Auto);
+            };
+            if let Some(cached) = cached {
+                send(cached);
+            }
+            match external {
+                Some(config) => {
+                    let result = config
+                        .and_then(|config| config.start())
+                        .and_then(|mut engine| engine.analyse(&state, &board, lines, &stop, send));
+                    if let Err(message) = result {
+                        let _ = sink.submit_command(ANALYSIS_FAILED, (analysis, message), Target::Auto);
+                    }
+                }
+                None => analyse(&state, &board, lines, hash_mb, &stop, send),
+            }
+        });
+    }
+
+    /// Reviews the game's moves on another thread, which sends its findings as `REVIEW`
+    fn start_review(&self, sink: ExtEventSink, data: &mut AppState) {
+        data.board_view.reviewing = true;
+        let state = data.game_state.clone();
+        thread::spawn(move || {
+            let review = PgnGame::from_game(&state).replay_positions().map(|positions| review_game(&positions));
+            let _ = sink.submit_command(REVIEW, (state.san_moves, review), Target::Auto);
+        });
+    }
+
+    /// What the review found about the move at `ply`, for the dialog shown when its mark is clicked
+    fn review_message(&self, data: &AppState, ply: usize) -> String {
+        let san = data.game_state.san_moves.get(ply).cloned().unwrap_or_default();
+        let glyph = data.game_state.move_qualities.get(ply).copied().flatten().map_or("", |quality| quality.glyph());
+        let reviewed = self.review.as_ref()
+            .filter(|(moves, _)| *moves == data.game_state.san_moves)
+            .and_then(|(_, review)| review.moves.get(ply));
+        let message = match reviewed {
+            Some(ReviewedMove { loss, better: Some(better), .. }) => format!("{}{} lost {:.2} pawns. Better was {}.", san, glyph, *loss as f64 / 100.0, better),
+            Some(_) => format!("{}{}: the engine found nothing better.", san, glyph),
+            None => format!("{}{}: review the game with Cmd+Shift+E to see the better move.", san, glyph),
+        };
+        match data.game_state.move_evals.get(ply).copied().flatten() {
+            Some(eval) => format!("{} Evaluation after it: {}.", message, eval.text()),
+            None => message,
+        }
+    }
+
+    /// Plays the first move of an analysis line clicked; a line of a position browsed to is
+    /// tried from there
+    fn play_analysis_move(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, ((from_row, from_col), (to_row, to_col), promotion): ParsedMove) {
+        if let Some(ply) = data.viewed_ply.filter(|_| data.lesson.is_none()) {
+            data.try_variation(ply, data.shown_position());
+            self.forget_position(data);
+        }
+        if !Self::computer_to_move(data) && self.make_move(from_row * 8 + from_col, to_row * 8 + to_col, promotion, data) {
+            data.selected_square = None;
+        }
+        ctx.request_paint();
+    }
+
+    fn stop_analysis(&mut self) {
+        if let Some(stop) = self.analysis.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Plays the computer's move on the board
+    fn play_computer_move(&mut self, data: &mut AppState, (from, to, promotion): ParsedMove) {
+        let mut board = data.current_board();
+        if data.game_state.make_move_with_promotion(from, to, promotion, &mut board) {
+            data.board = board.into();
+            data.selected_square = None;
+        }
+    }
+
+    /// Runs a toolbar button. Against the computer a draw offer is answered right away.
+    fn game_action(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, action: GameAction) {
+        match action {
+            GameAction::Resign => data.game_state.resign(),
+            GameAction::OfferDraw => {
+                data.game_state.offer_draw();
+                if data.computer.enabled && data.game_state.draw_offer.is_some() {
+                    if accepts_draw(&data.game_state, &data.current_board()) {
+                        data.game_state.accept_draw();
+                    } else {
+                        data.game_state.draw_offer = None;
+                        ctx.new_window(message_dialog("Draw offer", "The computer declines the draw."));
+                    }
+                }
+            }
+            // Remembered with this game, so opening another one starts from its own orientation
+            GameAction::FlipBoard => data.game_state.flipped = Some(!data.flipped()),
+            GameAction::BackToGame => {
+                data.discard_variation();
+                self.forget_position(data);
+            }
+            GameAction::KeepLine => {
+                data.keep_variation();
+                self.forget_position(data);
+            }
+        }
+        ctx.request_paint();
+    }
+
+    /// Takes the time since the last tick off the clock of the side to move, adds the increment
+    /// for each move played since or rewinds the clocks for moves taken back, and ends the game
+    /// when the time runs out. The clock starts with the first move.
+    fn run_clock(&mut self, data: &mut AppState) {
+        let plies = data.game_state.san_moves.len();
+        let now = Instant::now();
+        let (Some(clock), Some((last, last_plies))) = (data.clock.as_mut(), self.clock_tick.replace((now, plies))) else {
+            return;
+        };
+        if plies < last_plies {
+            clock.take_back(last_plies - plies);
+        }
+        if data.game_state.is_over() || plies == 0 {
+            return;
+        }
+        let turn = data.game_state.current_turn;
+        let mut mover = turn;
+        for _ in last_plies..plies {
+            mover = if mover == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+            clock.moved(mover);
+        }
+        if clock.run(turn, (now - last).as_millis() as u64) {
+            let board: Vec<Option<Piece>> = data.board.iter().copied().collect();
+            data.game_state.flag_fall(&board);
+        }
+    }
+
+    /// Takes back the last move, or redoes the last one taken back. Against the computer both
+    /// the computer's move and the user's go, so that the user is to move again.
+    fn take_back(&mut self, data: &mut AppState, redo: bool) {
+        let moves = if data.computer.enabled && (redo || !Self::computer_to_move(data)) { 2 } else { 1 };
+        let mut board = data.current_board();
+        for _ in 0..moves {
+            let done = if redo { data.game_state.redo_move(&mut board) } else { data.game_state.undo_move(&mut board) };
+            if !done {
+                break;
+            }
+        }
+        data.board = board.into();
+        // A search started for the position taken back no longer applies
+        self.stop_computer();
+        data.viewed_ply = None;
+        data.viewed_position = None;
+        data.selected_square = None;
+        data.show_marks();
+    }
+
+    /// Replaces the game shown with an imported or loaded one, recording it in the timeline
+    fn set_position(&mut self, ctx: &mut druid::EventCtx, data: &mut AppState, game_state: GameState, board: Vec<Option<Piece>>, label: &str) {
+        self.timeline.record(label, &game_state, &board);
+        self.forget_position(data);
+        data.board = board.into();
+        data.game_state = game_state;
+        data.selected_square = None;
+        data.viewed_ply = None;
+        data.viewed_position = None;
+        data.lesson = None;
+        data.clock = None;
+        data.show_marks();
+        ctx.request_paint();
+    }
+
+    /// Gives up the computer's search and its pondering, whose answers no longer apply
+    fn stop_computer(&mut self) {
+        let ponder = self.ponder.take().map(|ponder| ponder.stop);
+        for stop in self.search.take().map(|(_, stop)| stop).into_iter().chain(ponder) {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Drops what the board kept about the game shown, once another game takes its place: the
+    /// computer's search and a pending promotion. The clock of a game in another tab stands
+    /// still until the game is shown again.
+    fn forget_position(&mut self, data: &mut AppState) {
+        self.stop_computer();
+        self.hint = None;
+        self.clock_tick = None;
+        self.animation = None;
+        data.board_view.promotion = None;
+    }
+
+    /// The moves of the piece on `selected` to `targets` whose outcome the tablebase knows, as
+    /// target squares with the outcome for the mover
+    fn tablebase_moves(tablebase: &Tablebase, data: &AppState, selected: usize, targets: &[usize]) -> Vec<(usize, TablebaseResult)> {
+        targets.iter()
+            .filter_map(|&to| {
+                let (mut state, mut board) = (data.game_state.clone(), data.current_board());
+                state.make_move((selected / 8, selected % 8), (to / 8, to % 8), &mut board);
+                Some((to, tablebase.probe(&state, &board)?.reversed()))
+            })
+            .collect()
+    }
+
+    /// The heaviest book move for the position, if a book is loaded and knows the position
+    fn book_hint(&self, data: &AppState) -> Option<BookMove> {
+        let book = self.book.as_ref()?;
+        book.moves(&data.game_state, &data.current_board()).into_iter().next()
+    }
+
+    /// Opens an online analysis board, reporting failures in a dialog
+    fn open_analysis(ctx: &mut druid::EventCtx, url: String) {
+        if let Err(message) = open_in_browser(&url) {
+            ctx.new_window(error_dialog("Open in browser failed", &message));
+        }
+        ctx.set_handled();
+    }
+
+    /// Copies the position to the clipboard as FEN, or as an EPD record without operations
+    fn copy_position(&self, data: &AppState, as_epd: bool) {
+        let board = data.current_board();
+        let text = if as_epd {
+            EpdRecord::new(data.game_state.clone(), board).to_epd()
+        } else {
+            to_fen(&data.game_state, &board)
+        };
+        Application::global().clipboard().put_string(text);
+    }
 }
 
 impl Widget<AppState> for ChessBoard {
-    fn event(&mut self, ctx: &mut druid::EventCtx, event: &druid::Event, data: &mut AppState, _env: &druid::Env) {
-        if let druid::Event::MouseDown(mouse_event) = event {
-            let window_size = ctx.window().get_size();
-            let width = window_size.width;
-            let square_size = width.min(window_size.height) / 8.0;
-            let board_width = 8.0 * square_size;
-            let x_offset = (width - board_width) / 2.0;
-            let y_offset = 30.0; // Add vertical offset for status text
+    fn event(&mut self, ctx: &mut druid::EventCtx, event: &druid::Event, data: &mut AppState, env: &druid::Env) {
+        // The popups over the board come first, as they cover the squares under them
+        self.promotion_picker.event(ctx, event, data, env);
+        self.exchange_popup.event(ctx, event, data, env);
+        if ctx.is_handled() {
+            return;
+        }
 
-            // Calculate which square was clicked
-            let board_x = mouse_event.pos.x - x_offset;
-            let board_y = mouse_event.pos.y - y_offset;
-
-            if board_x >= 0.0 && board_x < board_width && board_y >= 0.0 && board_y < board_width {
-                let col = (board_x / square_size) as usize;
-                let row = (board_y / square_size) as usize;
-                let square_idx = row * 8 + col;
-
-                if let Some(selected) = data.selected_square {
-                    if selected == square_idx {
-                        // Clicking the same square deselects it
-                        data.selected_square = None;
-                    } else {
-                        // Try to make a move
-                        if self.make_move(selected, square_idx, data) {
-                            data.selected_square = None;
+        if let druid::Event::WindowConnected = event {
+            ctx.request_focus();
+            match load_engines() {
+                Ok(engines) => data.computer.engines = engines,
+                Err(message) => ctx.new_window(error_dialog("Engines not loaded", &message)),
+            }
+            if let Err(message) = switch_piece_set(&mut self.piece_set, &data.piece_set) {
+                data.piece_set = String::new();
+                ctx.new_window(error_dialog("Piece set not loaded", &message));
+            }
+        }
+
+        if let (Some((_, _, started)), druid::Event::AnimFrame(_)) = (self.animation, event) {
+            if data.animation.duration().is_some_and(|duration| started.elapsed() < duration) {
+                ctx.request_anim_frame();
+            } else {
+                self.animation = None;
+            }
+            ctx.request_paint();
+        }
+
+        if let (Some(hud), druid::Event::MouseDown(_) | druid::Event::KeyDown(_)) = (&mut self.hud, event) {
+            hud.input();
+        }
+
+        if let druid::Event::Timer(token) = event {
+            if *token == self.computer_timer {
+                self.computer_timer = TimerToken::INVALID;
+                if Self::computer_to_move(data) {
+                    self.start_computer_move(ctx, data);
+                }
+            } else if *token == self.clock_timer {
+                self.run_clock(data);
+                self.clock_timer = if data.clock.is_some() && !data.game_state.is_over() { ctx.request_timer(CLOCK_TICK) } else { TimerToken::INVALID };
+            } else if *token == self.rotate_timer {
+                self.rotate_timer = TimerToken::INVALID;
+                if data.auto_rotate && !data.computer.enabled {
+                    data.game_state.flipped = Some(data.game_state.current_turn == PieceColor::Black);
+                }
+            }
+        }
+
+        if let druid::Event::Command(cmd) = event {
+            if let Some((search, found)) = cmd.get(COMPUTER_MOVE) {
+                if let Some(ponder) = self.ponder.as_mut().filter(|ponder| ponder.search == *search) {
+                    ponder.found = Some(found.clone());
+                } else if self.search.as_ref().is_some_and(|(running, _)| running == search) {
+                    self.search = None;
+                    if let Err(message) = found {
+                        // Otherwise the computer would try again right away
+                        data.computer.enabled = false;
+                        ctx.new_window(error_dialog("Engine failed", message));
+                    } else if let (Ok(Some(found)), true) = (found, Self::computer_to_move(data)) {
+                        self.play_computer_move(data, *found);
+                        if data.computer.ponder {
+                            self.start_ponder(ctx.get_external_handle(), data);
+                        }
+                        ctx.request_paint();
+                        // Players of slow games may be in another window meanwhile
+                        if !ctx.window().is_foreground_window() {
+                            if data.turn_notification {
+                                let played = data.game_state.san_moves.last().cloned().unwrap_or_default();
+                                notify("Your move", &format!("The computer played {}", played));
+                            }
+                            if data.turn_sound {
+                                chime();
+                            }
                         }
                     }
-                } else if let Some(piece) = self.squares[square_idx].piece {
-                    // Select a piece of the current player's color
-                    if piece.color == data.game_state.current_turn {
-                        data.selected_square = Some(square_idx);
+                }
+                ctx.set_handled();
+            } else if let Some(message) = cmd.get(PIECE_SET_FAILED) {
+                data.piece_set = String::new();
+                ctx.new_window(error_dialog("Piece set not loaded", message));
+                ctx.set_handled();
+            } else if let Some((moves, review)) = cmd.get(REVIEW) {
+                data.board_view.reviewing = false;
+                match review {
+                    Ok(review) if *moves == data.game_state.san_moves => {
+                        for (ply, reviewed) in review.moves.iter().enumerate() {
+                            if let Some(quality) = reviewed.quality {
+                                data.game_state.move_qualities.set(ply, Some(quality));
+                            }
+                            if let (Some(eval), Some(slot)) = (reviewed.evaluation(), data.game_state.move_evals.get_mut(ply)) {
+                                *slot = Some(eval);
+                            }
+                        }
+                        data.game_state.review = Some((review.white, review.black));
+                        self.review = Some((moves.clone(), review.clone()));
+                        let summary = format!("{}: {}\n\n{}: {}",
+                            data.player_label(PieceColor::White), review.white.text(),
+                            data.player_label(PieceColor::Black), review.black.text());
+                        ctx.new_window(message_dialog("Game review", &summary));
                     }
+                    // The game has moved on meanwhile
+                    Ok(_) => {}
+                    Err(message) => ctx.new_window(error_dialog("Review failed", message)),
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if let Some(&(search, key)) = cmd.get(PONDERING) {
+                if let Some(ponder) = self.ponder.as_mut().filter(|ponder| ponder.search == search) {
+                    ponder.key = Some(key);
+                }
+                ctx.set_handled();
+            } else if let Some((analysis, message)) = cmd.get(ANALYSIS_FAILED) {
+                if self.analysis.is_some() && *analysis == self.analyses {
+                    self.stop_analysis();
+                    data.board_view.analysing = false;
+                    ctx.new_window(error_dialog("Analysis failed", message));
+                    ctx.request_paint();
+                }
+                ctx.set_handled();
+            } else if let Some(&(key, found)) = cmd.get(HINT) {
+                self.hint = found.map(|found| (key, found));
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if let Some((analysis, report)) = cmd.get(ANALYSIS) {
+                let (state, board) = data.shown_position();
+                let key = polyglot_key(&state, &board);
+                // An analysis known from before is shown until the engine gets deeper
+                let shown = data.board_view.engine_report.as_ref().filter(|shown| shown.key == key);
+                let deeper = shown.is_none_or(|shown| report.depth > shown.analysis.depth || report.lines.len() != shown.analysis.lines.len());
+                if self.analysis.is_some() && *analysis == self.analyses && deeper {
+                    // The tablebase's outcome and line are worked out once for each position
+                    let (tablebase, tablebase_line) = match (shown, &self.tablebase) {
+                        (Some(shown), _) => (shown.tablebase, shown.tablebase_line.clone()),
+                        (None, Some(tablebase)) => match tablebase.probe(&state, &board) {
+                            Some(result) => (Some(result), tablebase.line(&state, &board, ANALYSIS_LINE_MOVES)),
+                            None => (None, None),
+                        },
+                        (None, None) => (None, None),
+                    };
+                    data.board_view.engine_report = Some(Arc::new(EngineReport { key, analysis: report.clone(), tablebase, tablebase_line }));
+                    // A cache that cannot be written only means analysing the position again
+                    let _ = self.analysis_cache.insert(key, report);
+                }
+                ctx.set_handled();
+            } else if let Some(&action) = cmd.get(GAME_ACTION) {
+                self.game_action(ctx, data, action);
+                ctx.set_handled();
+            } else if let Some(&piece_type) = cmd.get(PICK_PROMOTION) {
+                self.promote(ctx, data, piece_type);
+                ctx.set_handled();
+            } else if let Some(&ply) = cmd.get(SHOW_MOVE_REVIEW) {
+                ctx.new_window(message_dialog("Move review", &self.review_message(data, ply)));
+                ctx.set_handled();
+            } else if let Some(&first) = cmd.get(PLAY_ANALYSIS_MOVE) {
+                self.play_analysis_move(ctx, data, first);
+                ctx.set_handled();
+            } else if cmd.is(START_REVIEW) || cmd.is(REVIEW_GAME) {
+                if !data.board_view.reviewing && !data.game_state.san_moves.is_empty() {
+                    self.start_review(ctx.get_external_handle(), data);
+                }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(EXPORT_DIAGRAM) {
+                // The position on the board, which is an earlier one while stepping through the game
+                let (state, board) = data.shown_position();
+                if let Err(message) = export_diagram(file.path(), &board, state.last_move, &data.diagram) {
+                    ctx.new_window(error_dialog("Export failed", &message));
+                }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(EXPORT_GIF) {
+                let result = PgnGame::from_game(&data.game_state)
+                    .replay_positions()
+                    .and_then(|positions| export_gif(file.path(), &positions, &data.diagram));
+                if let Err(message) = result {
+                    ctx.new_window(error_dialog("Export failed", &message));
+                }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(SAVE_GAME) {
+                // As PGN when picked in the panel or named so, otherwise everything in the app's format
+                let as_pgn = file.format == Some(PGN_FILE) || file.path().extension().is_some_and(|e| e.eq_ignore_ascii_case("pgn"));
+                let saved = if as_pgn {
+                    std::fs::write(file.path(), data.pgn().to_pgn())
+                        .map_err(|e| format!("cannot write {}: {}", file.path().display(), e))
+                } else {
+                    save_game(file.path(), &data.game_state, &data.current_board(), data.clock.as_ref())
+                };
+                if let Err(message) = saved {
+                    ctx.new_window(error_dialog("Save failed", &message));
                 }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(OPEN_GAME) {
+                // PGN and FEN files are read like pasted text, which reports what fails to parse
+                let is_text = file.path().extension().is_some_and(|e| e.eq_ignore_ascii_case("pgn") || e.eq_ignore_ascii_case("fen"));
+                let label = format!("Opened {}", file.path().file_name().unwrap_or_default().to_string_lossy());
+                if is_text {
+                    match std::fs::read_to_string(file.path()) {
+                        Ok(text) => self.open_text(ctx, data, &text, &label, "Open failed"),
+                        Err(e) => ctx.new_window(error_dialog("Open failed", &format!("cannot read {}: {}", file.path().display(), e))),
+                    }
+                } else {
+                    match load_game(file.path()) {
+                        Ok(((game_state, board), clock)) => {
+                            self.set_position(ctx, data, game_state, board, &label);
+                            data.clock = clock;
+                        }
+                        Err(message) => ctx.new_window(error_dialog("Open failed", &message)),
+                    }
+                }
+                ctx.set_handled();
+            } else if let Some(&index) = cmd.get(OPEN_LISTED_GAME) {
+                if let Some(game) = self.listed_games.get(index) {
+                    let label = format!("Opened {} - {}", game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"));
+                    match game.replay() {
+                        Ok((game_state, board)) => self.set_position(ctx, data, game_state, board, &label),
+                        Err(message) => ctx.new_window(error_dialog("Open failed", &format!("Invalid PGN: {}", message))),
+                    }
+                }
+                ctx.set_handled();
+            } else if let Some(&index) = cmd.get(RESTORE_SNAPSHOT) {
+                if let Some(snapshot) = self.timeline.snapshots().get(index) {
+                    let label = format!("Restored \"{}\"", snapshot.label);
+                    let (game_state, board) = snapshot.position.clone();
+                    self.set_position(ctx, data, game_state, board, &label);
+                }
+                ctx.set_handled();
+            } else if cmd.is(COPY_FEN) || cmd.is(COPY_EPD) {
+                self.copy_position(data, cmd.is(COPY_EPD));
+                ctx.set_handled();
+            } else if cmd.is(COPY_PGN) {
+                Application::global().clipboard().put_string(data.pgn().to_pgn());
+                ctx.set_handled();
+            } else if cmd.is(PASTE_POSITION) {
+                self.paste_position(ctx, data);
+                ctx.set_handled();
+            } else if cmd.is(OPEN_LICHESS) || cmd.is(OPEN_CHESS_COM) {
+                // The game up to the position shown, which is an earlier one while stepping through it
+                let (state, board) = data.shown_position();
+                let url = if cmd.is(OPEN_LICHESS) { lichess_analysis_url(&state, &board) } else { chess_com_analysis_url(&state, &board) };
+                Self::open_analysis(ctx, url);
+            } else if cmd.is(SHOW_EXPORT_DIAGRAM) {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("PNG image", &["png"]), FileSpec::new("SVG image", &["svg"])])
+                    .default_name("diagram.png")
+                    .title("Export diagram")
+                    .accept_command(EXPORT_DIAGRAM);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                ctx.set_handled();
+            } else if cmd.is(SHOW_EXPORT_GIF) {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("GIF animation", &["gif"])])
+                    .default_name("game.gif")
+                    .title("Export game as GIF")
+                    .accept_command(EXPORT_GIF);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                ctx.set_handled();
+            } else if cmd.is(SHOW_PRINT_SCORESHEET) {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("PDF document", &["pdf"])])
+                    .default_name("scoresheet.pdf")
+                    .title("Print scoresheet")
+                    .accept_command(EXPORT_SCORESHEET);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                ctx.set_handled();
+            } else if cmd.is(SHOW_HISTORY) {
+                let entries: Vec<(String, String)> = self.timeline.snapshots().iter()
+                    .map(|snapshot| (age(snapshot.taken), snapshot.label.clone()))
+                    .collect();
+                ctx.new_window(history_dialog(&entries));
+                ctx.set_handled();
+            } else if cmd.is(FLIP_BOARD) {
+                // Remembered with this game, so opening another one starts from its own orientation
+                data.game_state.flipped = Some(!data.flipped());
+                ctx.set_handled();
+            } else if cmd.is(SHOW_HINT) {
+                if !Self::computer_to_move(data) {
+                    self.start_hint(ctx, data);
+                }
+                ctx.set_handled();
+            } else if cmd.is(TOGGLE_BOOK_HINT) {
+                if self.book.is_some() {
+                    self.show_book_hint = !self.show_book_hint;
+                } else {
+                    ctx.new_window(error_dialog("No opening book", &format!("Place a Polyglot book at {} to get book move hints.", BOOK_PATH)));
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(TOGGLE_ANALYSIS) {
+                if self.analysis.is_some() {
+                    self.stop_analysis();
+                } else {
+                    self.start_analysis(ctx.get_external_handle(), data);
+                }
+                data.board_view.analysing = self.analysis.is_some();
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(START_LESSONS) {
+                self.start_lesson(ctx, data, 0);
+                ctx.set_handled();
+            } else if cmd.is(TOGGLE_HUD) {
+                self.hud = if self.hud.is_some() { None } else { Some(FrameStats::new()) };
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(NEW_TAB) && !self.scratch {
+                data.open_tab();
+                self.forget_position(data);
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(CLOSE_TAB) && !self.scratch {
+                data.close_tab();
+                self.forget_position(data);
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if let Some(index) = cmd.get(SWITCH_TAB) {
+                if *index != data.active_tab {
+                    data.switch_tab(*index);
+                    self.forget_position(data);
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if let Some(&(line, count)) = cmd.get(SHOW_LINE_MOVE) {
+                if let Err(message) = self.show_line_move(data, line, count) {
+                    ctx.new_window(error_dialog("Cannot show the move", &message));
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(UNDO) || cmd.is(REDO) {
+                self.take_back(data, cmd.is(REDO));
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if cmd.is(NEW_GAME) {
+                let fen = match data.start_position {
+                    StartPosition::Standard => STARTING_FEN.to_string(),
+                    StartPosition::Fen => data.start_fen.trim().to_string(),
+                    StartPosition::Chess960 => chess960_fen(rand::thread_rng().gen_range(0..960)),
+                };
+                match parse_fen(&fen) {
+                    Ok((game_state, board)) => {
+                        self.set_position(ctx, data, game_state, board, "New game");
+                        data.clock = data.time_control.clock();
+                    }
+                    Err(message) => ctx.new_window(error_dialog("New game", &format!("Invalid FEN: {}", message))),
+                }
+                ctx.set_handled();
+            } else if cmd.is(PRINT_BULLETIN) {
+                if data.bulletin_games.contains(&true) {
+                    let options = FileDialogOptions::new()
+                        .allowed_types(vec![FileSpec::new("HTML page", &["html", "htm"])])
+                        .default_name("bulletin.html")
+                        .title("Print bulletin")
+                        .accept_command(EXPORT_BULLETIN);
+                    ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                } else {
+                    ctx.new_window(error_dialog("Print bulletin", "Tick the games to print first."));
+                }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(EXPORT_BULLETIN) {
+                let games: Vec<&PgnGame> = self.listed_games.iter()
+                    .zip(data.bulletin_games.iter())
+                    .filter(|(_, &picked)| picked)
+                    .map(|(game, _)| game)
+                    .collect();
+                if let Err(message) = export_bulletin(file.path(), &games) {
+                    ctx.new_window(error_dialog("Export failed", &message));
+                }
+                ctx.set_handled();
+            } else if let Some(file) = cmd.get(EXPORT_SCORESHEET) {
+                if let Err(message) = export_scoresheet(file.path(), &data.game_state) {
+                    ctx.new_window(error_dialog("Export failed", &message));
+                }
+                ctx.set_handled();
+            }
+        }
+
+        // Holding the peek key shows the pieces hidden in blindfold mode
+        if let druid::Event::KeyUp(key_event) = event {
+            if data.board_view.peeking && matches!(&key_event.key, KbKey::Character(key) if key == PEEK_KEY) {
+                data.board_view.peeking = false;
+                ctx.request_paint();
+            }
+        }
+
+        if let druid::Event::KeyDown(key_event) = event {
+            // The arrow keys browse the game until Up or Down brings up the square cursor
+            let step = match &key_event.key {
+                KbKey::ArrowUp => Some((-1, 0)),
+                KbKey::ArrowDown => Some((1, 0)),
+                KbKey::ArrowLeft if self.cursor.is_some() => Some((0, -1)),
+                KbKey::ArrowRight if self.cursor.is_some() => Some((0, 1)),
+                _ => None,
+            };
+            let promotion_choice = match &key_event.key {
+                KbKey::Character(key) => PROMOTION_KEYS.iter().find(|(letter, _)| key.eq_ignore_ascii_case(letter)).map(|&(_, piece_type)| piece_type),
+                _ => None,
+            };
+            if let (Some(_), Some(piece_type)) = (data.board_view.promotion, promotion_choice) {
+                // A pending promotion takes the letter of the piece picked
+                self.promote(ctx, data, piece_type);
+                ctx.set_handled();
+            } else if let Some((rows, cols)) = step.filter(|_| key_event.mods.is_empty()) {
+                self.move_cursor(data, rows, cols);
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if HotKey::new(SysMods::None, KbKey::Enter).matches(key_event) && self.cursor.is_some() && data.board_view.typed.is_empty() {
+                if let Some(square) = self.cursor {
+                    self.press_square(ctx, data, square);
+                }
+                ctx.set_handled();
+            } else if HotKey::new(SysMods::None, KbKey::Escape).matches(key_event) && (data.board_view.promotion.is_some() || self.cursor.is_some()) && data.board_view.typed.is_empty() {
+                // Escape takes back a pending promotion or the selection first, then the cursor
+                if data.board_view.promotion.is_some() {
+                    data.board_view.promotion = None;
+                } else if data.selected_square.is_some() {
+                    data.selected_square = None;
+                } else {
+                    self.cursor = None;
+                }
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if self.type_move(ctx, data, key_event) {
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if data.blindfold && key_event.mods.is_empty() && matches!(&key_event.key, KbKey::Character(key) if key == PEEK_KEY) {
+                data.board_view.peeking = true;
+                ctx.request_paint();
+                ctx.set_handled();
+            } else if HotKey::new(SysMods::None, KbKey::ArrowLeft).matches(key_event) {
+                Navigation::Previous.go(ctx, data);
+            } else if HotKey::new(SysMods::None, KbKey::ArrowRight).matches(key_event) {
+                Navigation::Next.go(ctx, data);
+            } else if HotKey::new(SysMods::None, KbKey::Home).matches(key_event) {
+                Navigation::First.go(ctx, data);
+            } else if HotKey::new(SysMods::None, KbKey::End).matches(key_event) || (HotKey::new(SysMods::None, KbKey::Escape).matches(key_event) && data.viewed_ply.is_some()) {
+                Navigation::Last.go(ctx, data);
+            } else if HotKey::new(SysMods::None, KbKey::Escape).matches(key_event) && data.variation.is_some() {
+                self.game_action(ctx, data, GameAction::BackToGame);
+                ctx.set_handled();
+            }
+        }
+
+        // The squares move under the mouse when the window is resized or its scale changes
+        if let druid::Event::WindowSize(_) | druid::Event::WindowScale(_) = event {
+            data.board_view.hovered = None;
+            ctx.request_paint();
+        }
+
+        if let druid::Event::MouseMove(mouse_event) = event {
+            let hovered = Geometry::new(ctx.size(), ctx.scale()).square_at(mouse_event.pos, data.flipped());
+            if hovered != data.board_view.hovered {
+                data.board_view.hovered = hovered;
+            }
+        }
+
+        // The right mouse button draws an arrow between the squares it is pressed and released on,
+        // or circles the square when both are the same; drawing the same mark again removes it
+        if let druid::Event::MouseUp(mouse_event) = event {
+            if mouse_event.button.is_right() {
+                let released = Geometry::new(ctx.size(), ctx.scale()).square_at(mouse_event.pos, data.flipped());
+                if let (Some(from), Some(to)) = (self.mark_start.take(), released) {
+                    let color = data.mark_color;
+                    if from == to {
+                        match data.circles.iter().position(|&(square, _)| square == from) {
+                            Some(i) if data.circles[i].1 == color => { data.circles.remove(i); }
+                            Some(i) => data.circles[i].1 = color,
+                            None => data.circles.push_back((from, color)),
+                        }
+                    } else {
+                        match data.arrows.iter().position(|&(start, end, _)| start == from && end == to) {
+                            Some(i) if data.arrows[i].2 == color => { data.arrows.remove(i); }
+                            Some(i) => data.arrows[i].2 = color,
+                            None => data.arrows.push_back((from, to, color)),
+                        }
+                    }
+                    data.record_marks();
+                }
+            }
+        }
+
+        if let druid::Event::MouseDown(mouse_event) = event {
+            ctx.request_focus();
+
+            if mouse_event.button.is_right() {
+                self.mark_start = Geometry::new(ctx.size(), ctx.scale()).square_at(mouse_event.pos, data.flipped());
+                return;
+            }
+            // Any left click clears the arrows and circles from the board, but not those kept with the move
+            if mouse_event.button.is_left() {
+                data.arrows.clear();
+                data.circles.clear();
+            }
+
+            // A click beside the piece picker of a pending promotion takes it back
+            if data.board_view.promotion.take().is_some() {
                 ctx.request_paint();
+                return;
+            }
+
+            // Calculate which square was clicked
+            if let Some(square_idx) = Geometry::new(ctx.size(), ctx.scale()).square_at(mouse_event.pos, data.flipped()) {
+                self.press_square(ctx, data, square_idx);
             }
         }
     }
 
-    fn lifecycle(&mut self, _ctx: &mut druid::LifeCycleCtx, _event: &druid::LifeCycle, _data: &AppState, _env: &druid::Env) {}
-    fn update(&mut self, _ctx: &mut druid::UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &druid::Env) {}
+    fn lifecycle(&mut self, ctx: &mut druid::LifeCycleCtx, event: &druid::LifeCycle, data: &AppState, env: &druid::Env) {
+        if let druid::LifeCycle::WidgetAdded = event {
+            ctx.register_for_focus();
+        }
+        self.promotion_picker.lifecycle(ctx, event, data, env);
+        self.exchange_popup.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut druid::UpdateCtx, old_data: &AppState, data: &AppState, env: &druid::Env) {
+        self.promotion_picker.update(ctx, data, env);
+        self.exchange_popup.update(ctx, data, env);
+        // Settings can change from other windows, and the position along with the game; the
+        // popups are laid out over the squares they belong to
+        if !old_data.same(data) {
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+        if old_data.piece_set != data.piece_set {
+            if let Err(message) = switch_piece_set(&mut self.piece_set, &data.piece_set) {
+                ctx.submit_command(PIECE_SET_FAILED.with(message).to(ctx.widget_id()));
+            }
+        }
+        if !old_data.game_state.same(&data.game_state) {
+            self.timeline.autosnapshot(&data.game_state, &data.current_board());
+        }
+        // The analysis follows the position shown, and not other changes to the game such as
+        // the marks drawn on it
+        let shown_key = |data: &AppState| {
+            let (state, board) = data.shown_position();
+            polyglot_key(&state, &board)
+        };
+        let shown_changed = old_data.viewed_ply != data.viewed_ply || shown_key(old_data) != shown_key(data);
+        if self.analysis.is_some() && (shown_changed || old_data.analysis_lines != data.analysis_lines) {
+            self.start_analysis(ctx.get_external_handle(), data);
+        }
+        // The moves of the selected piece are looked up once rather than on every frame, as
+        // probing the tablebase for each of them reads from disk
+        let live_key = |data: &AppState| polyglot_key(&data.game_state, &data.current_board());
+        if old_data.selected_square != data.selected_square || old_data.tablebase_moves != data.tablebase_moves
+            || old_data.game_state.status != data.game_state.status || live_key(old_data) != live_key(data) {
+            self.targets = data.selected_square.map(|selected| self.get_possible_moves(selected, data)).unwrap_or_default();
+            self.tablebase_targets = match (&self.tablebase, data.selected_square) {
+                (Some(tablebase), Some(selected)) if data.tablebase_moves => Self::tablebase_moves(tablebase, data, selected, &self.targets),
+                _ => Vec::new(),
+            };
+        }
+
+        // Between two players on one device the board turns to whoever is to move
+        let moved = old_data.game_state.san_moves.len() != data.game_state.san_moves.len();
+        if moved {
+            // The piece of a new move slides to its square, but not on undo
+            let played = data.game_state.san_moves.len() == old_data.game_state.san_moves.len() + 1;
+            // A Chess960 king castles onto its rook's square but lands on the g- or c-file
+            let castled = data.game_state.castling_files.is_some() && data.game_state.san_moves.last().is_some_and(|san| san.starts_with("O-O"));
+            self.animation = data.game_state.last_move
+                .filter(|_| played && data.animation.duration().is_some())
+                .map(|(from, to)| if castled { (from, (to.0, data.game_state.castling_columns(from, to).0)) } else { (from, to) })
+                .map(|(from, to)| (from.0 * 8 + from.1, to.0 * 8 + to.1, Instant::now()));
+            if self.animation.is_some() {
+                ctx.request_anim_frame();
+            }
+        }
+        // A move played or the game ending sounds, but not another game or line taking the board
+        let same_game = old_data.active_tab == data.active_tab && old_data.variation.is_some() == data.variation.is_some();
+        let played = data.game_state.san_moves.len() == old_data.game_state.san_moves.len() + 1;
+        let ended = same_game && data.game_state.is_over() && !old_data.game_state.is_over() && (played || !moved);
+        if data.sound_effects && same_game {
+            if ended {
+                play_effect(SoundEffect::GameEnd, data.sound_volume);
+            } else if let Some(san) = data.game_state.san_moves.last().filter(|_| played) {
+                play_effect(SoundEffect::of_move(san), data.sound_volume);
+            }
+        }
+        if data.auto_rotate && !data.computer.enabled && (moved || !old_data.auto_rotate) {
+            self.rotate_timer = ctx.request_timer(ROTATE_DELAY);
+        }
+
+        // A game finished on the board is reviewed right away, but not a line tried or a finished
+        // game loaded
+        if ended && !data.board_view.reviewing && data.variation.is_none() {
+            ctx.submit_command(START_REVIEW.to(ctx.widget_id()));
+        }
 
-    fn layout(&mut self, _ctx: &mut druid::LayoutCtx, bc: &druid::BoxConstraints, _data: &AppState, _env: &druid::Env) -> druid::Size {
-        let max_size = bc.max();
-        let square_size = max_size.width.min(max_size.height);
-        druid::Size::new(square_size, square_size + 60.0) // Add space for status text and coordinates
+        if data.clock.is_some() && !data.game_state.is_over() && self.clock_timer == TimerToken::INVALID {
+            self.clock_timer = ctx.request_timer(CLOCK_TICK);
+        }
+
+        // Give the computer a moment before it replies, like a person would take
+        if Self::computer_to_move(data) && self.computer_timer == TimerToken::INVALID && self.search.is_none() {
+            self.computer_timer = ctx.request_timer(Duration::from_millis(600));
+        }
     }
 
-    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &AppState, _env: &druid::Env) {
-        let window_size = ctx.window().get_size();
-        let width = window_size.width;
-        let square_size = width.min(window_size.height) / 8.0;
-        let board_width = 8.0 * square_size;
-        let x_offset = (width - board_width) / 2.0;
-        let y_offset = 30.0; // Add vertical offset for status text
-
-        // Draw status text at the top
-        let status_text = format!("{} to move - Game Status: {:?}",
-            if data.game_state.current_turn == PieceColor::White { "White" } else { "Black" },
-            data.game_state.status
-        );
-        let text_layout = ctx.text().new_text_layout(status_text)
-            .font(druid::FontFamily::SYSTEM_UI, 20.0)
-            .text_color(Color::BLACK)
-            .build()
-            .unwrap();
-        ctx.draw_text(&text_layout, (x_offset, 5.0));
-
-        // Draw move history on the right side
-        let history_x = x_offset + board_width + 20.0;
-        let mut history_y = y_offset;
-        let history_text = ctx.text().new_text_layout("Move History:")
-            .font(druid::FontFamily::SYSTEM_UI, 16.0)
-            .text_color(Color::BLACK)
-            .build()
-            .unwrap();
-        ctx.draw_text(&history_text, (history_x, history_y));
-        history_y += 25.0;
+    fn layout(&mut self, ctx: &mut druid::LayoutCtx, bc: &druid::BoxConstraints, data: &AppState, env: &druid::Env) -> druid::Size {
+        // As wide as the space given and as tall as the board fitted into it, so that the player
+        // bars and the toolbar stay against it
+        let scale = ctx.scale();
+        let width = if bc.is_width_bounded() { bc.max().width } else { 8.0 * PREFERRED_SQUARE_SIZE + 2.0 * RANK_LABELS_WIDTH };
+        let height = if bc.is_height_bounded() { bc.max().height } else { Geometry::height(PREFERRED_SQUARE_SIZE) };
+        let square_size = Geometry::square_pixels(druid::Size::new(width, height), scale, f64::floor) / scale.x();
+        let size = bc.constrain((width, Geometry::height(square_size)));
+        let Geometry { square_size, x_offset, y_offset, .. } = Geometry::new(size, scale);
+        let flipped = data.flipped();
+
+        // The piece picker covers the promotion square and the three toward the middle
+        let picker = data.board_view.promotion.map(|(_, to)| oriented(to / 8, to % 8, flipped));
+        let picker_bc = match picker {
+            Some(_) => BoxConstraints::tight(druid::Size::new(square_size, 4.0 * square_size)),
+            None => BoxConstraints::tight(druid::Size::ZERO),
+        };
+        self.promotion_picker.layout(ctx, &picker_bc, data, env);
+        let (row, col) = picker.unwrap_or_default();
+        let top = if row == 0 { 0 } else { row - 3 };
+        self.promotion_picker.set_origin(ctx, druid::Point::new(x_offset + col as f64 * square_size, y_offset + top as f64 * square_size));
+
+        // The exchange calculator goes beside the square under the mouse, on its left when there
+        // is no room on its right
+        let popup = self.exchange_popup.layout(ctx, &BoxConstraints::new(druid::Size::ZERO, size), data, env);
+        let (row, col) = data.board_view.hovered.map(|square| oriented(square / 8, square % 8, flipped)).unwrap_or_default();
+        let mut x = x_offset + (col + 1) as f64 * square_size + 4.0;
+        if x + popup.width > size.width {
+            x = x_offset + col as f64 * square_size - popup.width - 4.0;
+        }
+        let y = (y_offset + row as f64 * square_size).min(size.height - popup.height).max(0.0);
+        self.exchange_popup.set_origin(ctx, druid::Point::new(x.max(0.0), y));
+        size
+    }
 
-        for move_text in &data.game_state.move_history {
-            let move_layout = ctx.text().new_text_layout(move_text.clone())
-                .font(druid::FontFamily::MONOSPACE, 14.0)
-                .text_color(Color::BLACK)
-                .build()
-                .unwrap();
-            ctx.draw_text(&move_layout, (history_x, history_y));
-            history_y += 20.0;
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &AppState, env: &druid::Env) {
+        if let Some(hud) = &mut self.hud {
+            hud.paint_start();
         }
+        let Geometry { square_size, board_width, x_offset, y_offset } = Geometry::new(ctx.size(), ctx.scale());
+        let (state, board) = data.shown_position();
+        let text_color = env.get(druid::theme::TEXT_COLOR);
+        let shown_ply = data.viewed_ply.unwrap_or(data.game_state.san_moves.len());
+
+        let book_hint = if self.show_book_hint && data.viewed_ply.is_none() { self.book_hint(data) } else { None };
+        // A hint only holds for the position it was searched in
+        let engine_hint = self.hint
+            .filter(|&(key, _)| key == polyglot_key(&state, &board))
+            .map(|(_, (from, to, _))| (from, to));
+        let in_check = matches!(state.status, GameStatus::Check | GameStatus::Checkmate);
+        let threatened = if data.show_threats { threatened_pieces(&state, &board) } else { Vec::new() };
+
+        // How far the piece of the last move has slid, from 0 to 1
+        let hide_pieces = data.blindfold && !data.board_view.peeking;
+        let sliding = self.animation.filter(|_| data.viewed_ply.is_none()).and_then(|(from, to, started)| {
+            let duration = data.animation.duration()?;
+            Some((from, to, (started.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0)))
+        });
+        let piece_size = square_size * if data.low_vision { 0.85 } else { 0.6 };
 
         // Draw the board
-        for (i, square) in self.squares.iter().enumerate() {
-            let row = i / 8;
-            let col = i % 8;
+        let flipped = data.flipped();
+        let (light_color, dark_color) = data.board_theme.squares(&data.custom_colors, data.dark_mode());
+        for (i, &occupant) in board.iter().enumerate() {
+            let (row, col) = oriented(i / 8, i % 8, flipped);
             let x = x_offset + col as f64 * square_size;
-            let y = y_offset + row as f64 * square_size;  // Add offset for status text
+            let y = y_offset + row as f64 * square_size;
 
             let rect = druid::Rect::from_origin_size(
                 (x, y),
                 (square_size, square_size),
             );
 
-            // Highlight selected square and possible moves
+            let base_color = match ((i / 8 + i % 8) % 2 == 0, data.low_vision) {
+                (true, false) => light_color,
+                (false, false) => dark_color,
+                // High-contrast squares that both piece colors stand out against
+                (true, true) => Color::rgb8(240, 200, 90),
+                (false, true) => Color::rgb8(30, 70, 150),
+            };
+
+            // Highlight selected square, possible moves, the book move and a king in check
+            let palette = data.highlight_palette;
+            let legal_color = self.targets.contains(&i).then(|| match self.tablebase_targets.iter().find(|&&(to, _)| to == i) {
+                Some(&(_, result)) => tablebase_color(result),
+                None => palette.legal_move(),
+            });
             let fill_color = if Some(i) == data.selected_square {
-                Color::rgb8(255, 255, 0)
-            } else if let Some(selected) = data.selected_square {
-                if self.get_possible_moves(selected, data).contains(&i) {
-                    Color::rgb8(144, 238, 144) // Light green for possible moves
-                } else if square.is_light {
-                    Color::rgb8(200, 200, 200)
-                } else {
-                    Color::rgb8(100, 100, 100)
-                }
-            } else if square.is_light {
-                Color::rgb8(200, 200, 200)
+                data.custom_colors.selection_color(palette)
+            } else if book_hint.is_some_and(|hint| i == hint.from.0 * 8 + hint.from.1 || i == hint.to.0 * 8 + hint.to.1)
+                || engine_hint.is_some_and(|(from, to)| i == from.0 * 8 + from.1 || i == to.0 * 8 + to.1) {
+                palette.book_move()
+            } else if let (Some(color), MoveIndicator::Tint) = (&legal_color, data.move_indicator) {
+                *color
+            } else if in_check && matches!(occupant, Some(p) if p.piece_type == PieceType::King && p.color == state.current_turn) {
+                palette.check()
             } else {
-                Color::rgb8(100, 100, 100)
+                base_color
             };
 
             ctx.fill(rect, &fill_color);
 
-            // Draw piece if present
-            if let Some(piece) = square.piece {
-                let piece_color = match piece.color {
-                    PieceColor::White => Color::WHITE,
-                    PieceColor::Black => Color::BLACK,
-                };
+            // Outline the squares of the last move
+            if state.last_move.is_some_and(|(from, to)| i == from.0 * 8 + from.1 || i == to.0 * 8 + to.1) {
+                let border = if data.low_vision { square_size * 0.1 } else { 3.0 };
+                ctx.stroke(rect.inset(-border / 2.0), &data.custom_colors.last_move_color(data.highlight_palette), border);
+            }
 
-                let center_x = x + square_size / 2.0;
-                let center_y = y + square_size / 2.0;
-                let piece_size = square_size * 0.6;
-
-                match piece.piece_type {
-                    PieceType::King => {
-                        // Cross base
-                        let rect = druid::Rect::from_center_size(
-                            (center_x, center_y),
-                            (piece_size * 0.2, piece_size),
-                        );
-                        ctx.fill(rect, &piece_color);
-                        let rect = druid::Rect::from_center_size(
-                            (center_x, center_y - piece_size * 0.3),
-                            (piece_size * 0.6, piece_size * 0.2),
-                        );
-                        ctx.fill(rect, &piece_color);
-                        // Crown circle
-                        let circle = druid::kurbo::Circle::new(
-                            (center_x, center_y - piece_size * 0.35),
-                            piece_size * 0.15,
-                        );
-                        ctx.fill(circle, &piece_color);
-                    },
-                    PieceType::Queen => {
-                        // Base
-                        let mut path = druid::kurbo::BezPath::new();
-                        path.move_to((center_x - piece_size * 0.3, center_y + piece_size * 0.3));
-                        path.line_to((center_x + piece_size * 0.3, center_y + piece_size * 0.3));
-                        path.line_to((center_x, center_y - piece_size * 0.4));
-                        path.close_path();
-                        ctx.fill(path, &piece_color);
-                        // Crown
-                        for i in -2..=2 {
-                            let circle = druid::kurbo::Circle::new(
-                                (center_x + (i as f64) * piece_size * 0.15, center_y - piece_size * 0.25),
-                                piece_size * 0.08,
-                            );
-                            ctx.fill(circle, &piece_color);
-                        }
-                    },
-                    PieceType::Rook => {
-                        // Base
-                        let rect = druid::Rect::from_center_size(
-                            (center_x, center_y + piece_size * 0.1),
-                            (piece_size * 0.4, piece_size * 0.6),
-                        );
-                        ctx.fill(rect, &piece_color);
-                        // Battlements
-                        for i in -1..=1 {
-                            let rect = druid::Rect::from_center_size(
-                                (center_x + (i as f64) * piece_size * 0.15, center_y - piece_size * 0.25),
-                                (piece_size * 0.1, piece_size * 0.2),
-                            );
-                            ctx.fill(rect, &piece_color);
-                        }
-                    },
-                    PieceType::Bishop => {
-                        // Base triangle
-                        let mut path = druid::kurbo::BezPath::new();
-                        path.move_to((center_x - piece_size * 0.3, center_y + piece_size * 0.3));
-                        path.line_to((center_x + piece_size * 0.3, center_y + piece_size * 0.3));
-                        path.line_to((center_x, center_y - piece_size * 0.3));
-                        path.close_path();
-                        ctx.fill(path, &piece_color);
-                        // Top circle
-                        let circle = druid::kurbo::Circle::new(
-                            (center_x, center_y - piece_size * 0.35),
-                            piece_size * 0.1,
-                        );
-                        ctx.fill(circle, &piece_color);
-                    },
-                    PieceType::Knight => {
-                        // Horse head shape
-                        let mut path = druid::kurbo::BezPath::new();
-                        path.move_to((center_x - piece_size * 0.2, center_y + piece_size * 0.3));
-                        path.line_to((center_x + piece_size * 0.2, center_y + piece_size * 0.3));
-                        path.line_to((center_x + piece_size * 0.2, center_y));
-                        path.line_to((center_x + piece_size * 0.1, center_y - piece_size * 0.3));
-                        path.line_to((center_x - piece_size * 0.2, center_y));
-                        path.close_path();
-                        ctx.fill(path, &piece_color);
-                        // Eye
-                        let eye = druid::kurbo::Circle::new(
-                            (center_x + piece_size * 0.05, center_y - piece_size * 0.1),
-                            piece_size * 0.05,
-                        );
-                        ctx.fill(eye, &Color::rgb8(50, 50, 50));
-                    },
-                    PieceType::Pawn => {
-                        // Base
-                        let circle = druid::kurbo::Circle::new(
-                            (center_x, center_y + piece_size * 0.1),
-                            piece_size * 0.2,
-                        );
-                        ctx.fill(circle, &piece_color);
-                        // Head
-                        let circle = druid::kurbo::Circle::new(
-                            (center_x, center_y - piece_size * 0.2),
-                            piece_size * 0.15,
-                        );
-                        ctx.fill(circle, &piece_color);
-                    },
+            // Draw piece if present, unless it is still on its way here or the pieces are hidden
+            if let Some(piece) = occupant.filter(|_| !hide_pieces && sliding.is_none_or(|(_, to, _)| to != i)) {
+                paint_piece(ctx, self.piece_set.as_mut(), data.glyph_pieces, piece, rect, piece_size);
+            }
+
+            // A dot on the empty squares the selected piece can go to, a ring around its captures
+            if let (Some(color), MoveIndicator::Dots) = (&legal_color, data.move_indicator) {
+                if occupant.is_some() {
+                    let width = square_size * 0.08;
+                    ctx.stroke(druid::kurbo::Circle::new(rect.center(), (square_size - width) / 2.0), color, width);
+                } else {
+                    ctx.fill(druid::kurbo::Circle::new(rect.center(), square_size * 0.15), color);
                 }
             }
+
+            // Warning triangle in the bottom-left corner of a hanging piece
+            if threatened.contains(&i) && !hide_pieces {
+                let size = square_size * 0.3;
+                let (left, bottom) = (x + square_size * 0.05, y + square_size * 0.95);
+                let mut triangle = druid::kurbo::BezPath::new();
+                triangle.move_to((left, bottom));
+                triangle.line_to((left + size, bottom));
+                triangle.line_to((left + size / 2.0, bottom - size * 0.9));
+                triangle.close_path();
+                ctx.fill(&triangle, &data.highlight_palette.check());
+                ctx.stroke(&triangle, &Color::WHITE, 1.5);
+                let mark = ctx.text().new_text_layout("!")
+                    .font(druid::FontFamily::SYSTEM_UI, size * 0.6)
+                    .default_attribute(druid::FontWeight::BOLD)
+                    .text_color(Color::WHITE)
+                    .build()
+                    .unwrap();
+                let mark_size = mark.size();
+                ctx.draw_text(&mark, (left + (size - mark_size.width) / 2.0, bottom - size * 0.1 - mark_size.height));
+            }
+        }
+
+        // The keyboard cursor, in two tones so that it shows on squares of either color
+        if let Some(cursor) = self.cursor.filter(|_| ctx.has_focus()) {
+            let (row, col) = oriented(cursor / 8, cursor % 8, flipped);
+            let rect = druid::Rect::from_origin_size((x_offset + col as f64 * square_size, y_offset + row as f64 * square_size), (square_size, square_size));
+            ctx.stroke(rect.inset(-2.0), &Color::BLACK, 4.0);
+            ctx.stroke(rect.inset(-5.0), &Color::WHITE, 2.0);
+        }
+
+        // A line tried is framed, so that it is not taken for the game
+        if data.variation.is_some() {
+            let frame = druid::Rect::from_origin_size((x_offset, y_offset), (board_width, board_width));
+            ctx.stroke(frame.inset(-2.0), &data.highlight_palette.selection(), 4.0);
+        }
+
+        // Circles and arrows drawn with the right mouse button, above the pieces
+        let square_center = |i: usize| {
+            let (row, col) = oriented(i / 8, i % 8, flipped);
+            druid::Point::new(x_offset + (col as f64 + 0.5) * square_size, y_offset + (row as f64 + 0.5) * square_size)
+        };
+        // The sliding piece goes over the squares it crosses, slowing down as it lands
+        if let Some((from, to, progress)) = sliding.filter(|_| !hide_pieces) {
+            let eased = 1.0 - (1.0 - progress).powi(2);
+            let (start, end) = (square_center(from), square_center(to));
+            let center = start + (end - start) * eased;
+            if let Some(piece) = board[to] {
+                paint_piece(ctx, self.piece_set.as_mut(), data.glyph_pieces, piece, druid::Rect::from_center_size(center, (square_size, square_size)), piece_size);
+            }
+        }
+
+        for &(square, color) in &data.circles {
+            let width = square_size * 0.08;
+            ctx.stroke(druid::kurbo::Circle::new(square_center(square), (square_size - width) / 2.0), &color.color(), width);
+        }
+        for &(from, to, color) in &data.arrows {
+            paint_arrow(ctx, square_center(from), square_center(to), square_size, &color.color());
+        }
+
+        // Badge the destination of the last move with its review annotation
+        let last_quality = shown_ply.checked_sub(1).and_then(|ply| data.game_state.move_qualities.get(ply).copied().flatten());
+        if let (Some((_, to)), Some(quality)) = (state.last_move, last_quality) {
+            let radius = square_size * 0.18;
+            let (row, col) = oriented(to.0, to.1, flipped);
+            let center = druid::Point::new(
+                x_offset + (col + 1) as f64 * square_size - radius * 0.8,
+                y_offset + row as f64 * square_size + radius * 0.8,
+            );
+            paint_quality_badge(ctx, center, radius, quality);
         }
 
         // Draw coordinates
-        let coord_size = 14.0;
-        for i in 0..8 {
-            // Draw rank numbers (1-8)
-            let rank_text = ctx.text().new_text_layout((8-i).to_string())
-                .font(druid::FontFamily::SYSTEM_UI, coord_size)
-                .text_color(Color::BLACK)
-                .build()
-                .unwrap();
-            ctx.draw_text(&rank_text, (x_offset - 20.0, y_offset + i as f64 * square_size + square_size/2.0 - coord_size/2.0));
-
-            // Draw file letters (a-h)
-            let file_text = ctx.text().new_text_layout(((b'a' + i as u8) as char).to_string())
-                .font(druid::FontFamily::SYSTEM_UI, coord_size)
-                .text_color(Color::BLACK)
-                .build()
-                .unwrap();
-            ctx.draw_text(&file_text, (x_offset + i as f64 * square_size + square_size/2.0 - coord_size/2.0, y_offset + board_width + 5.0));
-        }
-    }
-}
\ No newline at end of file
+        if data.show_coordinates {
+            let coord_size = 14.0;
+            for i in 0..8 {
+                // Draw rank numbers (1-8), and file letters (a-h) below, as the board is oriented
+                let (rank, file) = oriented(i, i, flipped);
+                let rank_text = ctx.text().new_text_layout((8 - rank).to_string())
+                    .font(druid::FontFamily::SYSTEM_UI, coord_size)
+                    .text_color(text_color)
+                    .build()
+                    .unwrap();
+                let rank_x = if data.mirror_coordinates { x_offset + board_width + 8.0 } else { x_offset - RANK_LABELS_WIDTH };
+                ctx.draw_text(&rank_text, (rank_x, y_offset + i as f64 * square_size + square_size/2.0 - coord_size/2.0));
+
+                let file_text = ctx.text().new_text_layout(((b'a' + file as u8) as char).to_string())
+                    .font(druid::FontFamily::SYSTEM_UI, coord_size)
+                    .text_color(text_color)
+                    .build()
+                    .unwrap();
+                ctx.draw_text(&file_text, (x_offset + i as f64 * square_size + square_size/2.0 - coord_size/2.0, y_offset + board_width + 5.0));
+            }
+        }
+
+        // The piece picker and the exchange calculator go over the board
+        self.promotion_picker.paint(ctx, data, env);
+        self.exchange_popup.paint(ctx, data, env);
+
+        // Timings cover this frame up to here, not drawing the HUD itself
+        if let Some(hud) = &mut self.hud {
+            hud.paint_end();
+            let lines = hud.lines();
+            let mut hud_y = y_offset + 5.0;
+            ctx.fill(druid::Rect::from_origin_size((5.0, hud_y), (250.0, 18.0 * lines.len() as f64 + 8.0)), &Color::rgba8(0, 0, 0, 180));
+            for line in lines {
+                let layout = ctx.text().new_text_layout(line)
+                    .font(druid::FontFamily::MONOSPACE, 12.0)
+                    .text_color(Color::WHITE)
+                    .build()
+                    .unwrap();
+                ctx.draw_text(&layout, (10.0, hud_y + 4.0));
+                hud_y += 18.0;
+            }
+        }
+    }
+}
+
+/// Draws a piece in `rect`: from the piece set when there is one, otherwise as a Unicode glyph or
+/// with the built-in shapes `shape_size` wide
+pub(super) fn paint_piece(ctx: &mut druid::PaintCtx, piece_set: Option<&mut PieceSet>, glyphs: bool, piece: Piece, rect: druid::Rect, shape_size: f64) {
+    match piece_set {
+        Some(piece_set) => piece_set.paint(ctx, piece, rect),
+        None if glyphs => paint_glyph(ctx, piece, rect),
+        None => for (shape, color) in piece_shapes(piece, rect.center().x, rect.center().y, shape_size) {
+            ctx.fill(shape, &color);
+        },
+    }
+}
+
+/// Draws a piece with the chess symbols of the system font. White pieces are the solid symbol
+/// filled in white under the outlined one, so that they stand out on light squares too.
+fn paint_glyph(ctx: &mut druid::PaintCtx, piece: Piece, rect: druid::Rect) {
+    let (solid, outline) = match piece.piece_type {
+        PieceType::King => ("\u{265A}", "\u{2654}"),
+        PieceType::Queen => ("\u{265B}", "\u{2655}"),
+        PieceType::Rook => ("\u{265C}", "\u{2656}"),
+        PieceType::Bishop => ("\u{265D}", "\u{2657}"),
+        PieceType::Knight => ("\u{265E}", "\u{2658}"),
+        PieceType::Pawn => ("\u{265F}", "\u{2659}"),
+    };
+    let layers = match piece.color {
+        PieceColor::White => vec![(solid, Color::WHITE), (outline, Color::BLACK)],
+        PieceColor::Black => vec![(solid, Color::BLACK)],
+    };
+    for (glyph, color) in layers {
+        let layout = ctx.text().new_text_layout(glyph)
+            .font(druid::FontFamily::SYSTEM_UI, rect.height() * 0.8)
+            .text_color(color)
+            .build()
+            .unwrap();
+        let size = layout.size();
+        ctx.draw_text(&layout, (rect.center().x - size.width / 2.0, rect.center().y - size.height / 2.0));
+    }
+}
+
+/// A round badge with the glyph of a review annotation, e.g. "??" on red for a blunder
+pub(super) fn paint_quality_badge(ctx: &mut druid::PaintCtx, center: druid::Point, radius: f64, quality: MoveQuality) {
+    ctx.fill(druid::kurbo::Circle::new(center, radius), &move_quality_color(quality));
+    ctx.stroke(druid::kurbo::Circle::new(center, radius), &Color::WHITE, 1.5);
+    let glyph = ctx.text().new_text_layout(quality.glyph())
+        .font(druid::FontFamily::SYSTEM_UI, radius * 1.1)
+        .default_attribute(druid::FontWeight::BOLD)
+        .text_color(Color::WHITE)
+        .build()
+        .unwrap();
+    let size = glyph.size();
+    ctx.draw_text(&glyph, (center.x - size.width / 2.0, center.y - size.height / 2.0));
+}
+
+/// Row and column on screen of a board square, with Black at the bottom when flipped; flipping
+/// twice gives the square back, so this also maps a screen row and column to the board
+pub(super) fn oriented(row: usize, col: usize, flipped: bool) -> (usize, usize) {
+    if flipped { (7 - row, 7 - col) } else { (row, col) }
+}
+
+/// An arrow between two square centers, its head ending short of the target center
+fn paint_arrow(ctx: &mut druid::PaintCtx, from: druid::Point, to: druid::Point, square_size: f64, color: &Color) {
+    let direction = (to - from).normalize();
+    let normal = druid::Vec2::new(-direction.y, direction.x);
+    let (shaft, head_width, head_length) = (square_size * 0.18, square_size * 0.45, square_size * 0.4);
+    let tip = to - direction * square_size * 0.2;
+    let base = tip - direction * head_length;
+
+    let mut arrow = druid::kurbo::BezPath::new();
+    arrow.move_to(from + normal * shaft / 2.0);
+    arrow.line_to(base + normal * shaft / 2.0);
+    arrow.line_to(base + normal * head_width / 2.0);
+    arrow.line_to(tip);
+    arrow.line_to(base - normal * head_width / 2.0);
+    arrow.line_to(base - normal * shaft / 2.0);
+    arrow.line_to(from - normal * shaft / 2.0);
+    arrow.close_path();
+    ctx.fill(&arrow, color);
+}
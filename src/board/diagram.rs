@@ -0,0 +1,176 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::Path;
+use druid::kurbo::{BezPath, Point, Rect, Shape};
+use druid::piet::{BitmapTarget, Device, ImageFormat, Text, TextLayout, TextLayoutBuilder};
+use druid::{Color, Data, Lens, RenderContext};
+use serde::{Deserialize, Serialize};
+use crate::game::pgn::Position;
+use crate::pieces::Piece;
+use super::palette::HighlightPalette;
+use super::piece_shapes::piece_shapes;
+
+/// Settings for exported board diagrams
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiagramOptions {
+    pub size: u32, // Board width in pixels, not counting the coordinate margin
+    pub coordinates: bool,
+    pub last_move: bool,
+    pub frame_delay: u32, // Milliseconds each move is shown in GIF exports
+}
+
+impl DiagramOptions {
+    pub fn new() -> Self {
+        Self { size: 800, coordinates: true, last_move: true, frame_delay: 1000 }
+    }
+}
+
+impl Default for DiagramOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a diagram is made of, so that the PNG and SVG writers draw the same picture
+enum Mark {
+    Fill(BezPath, Color),
+    Stroke(BezPath, Color, f64),
+    Label(String, Point, f64), // Centered on the point, with the given font size
+}
+
+/// Writes the position to `path` as a PNG or SVG image, depending on the file extension
+pub fn export_diagram(path: &Path, board: &[Option<Piece>], last_move: Option<((usize, usize), (usize, usize))>, options: &DiagramOptions) -> Result<(), String> {
+    let (marks, width, height) = diagram_marks(board, last_move, options);
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("png") => write_png(path, &marks, width, height),
+        Some("svg") => std::fs::write(path, svg_document(&marks, width, height))
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e)),
+        _ => Err("Diagrams can only be saved as .png or .svg files.".to_string()),
+    }
+}
+
+fn diagram_marks(board: &[Option<Piece>], last_move: Option<((usize, usize), (usize, usize))>, options: &DiagramOptions) -> (Vec<Mark>, f64, f64) {
+    let board_size = options.size as f64;
+    let square_size = board_size / 8.0;
+    let margin = coordinate_margin(options);
+    let mut marks = Vec::new();
+
+    for (i, square) in board.iter().enumerate() {
+        let (row, col) = (i / 8, i % 8);
+        let rect = Rect::from_origin_size((margin + col as f64 * square_size, row as f64 * square_size), (square_size, square_size));
+        let color = if (row + col) % 2 == 0 { Color::rgb8(200, 200, 200) } else { Color::rgb8(100, 100, 100) };
+        marks.push(Mark::Fill(rect.to_path(0.1), color));
+
+        if options.last_move && last_move.is_some_and(|(from, to)| i == from.0 * 8 + from.1 || i == to.0 * 8 + to.1) {
+            let border = square_size * 0.06;
+            marks.push(Mark::Stroke(rect.inset(-border / 2.0).to_path(0.1), HighlightPalette::Standard.last_move(), border));
+        }
+
+        if let Some(piece) = square {
+            let center = rect.center();
+            for (shape, color) in piece_shapes(*piece, center.x, center.y, square_size * 0.6) {
+                marks.push(Mark::Fill(shape, color));
+            }
+        }
+    }
+
+    if options.coordinates {
+        let font_size = square_size * 0.25;
+        for i in 0..8 {
+            let offset = (i as f64 + 0.5) * square_size;
+            marks.push(Mark::Label((8 - i).to_string(), Point::new(margin / 2.0, offset), font_size));
+            marks.push(Mark::Label(((b'a' + i as u8) as char).to_string(), Point::new(margin + offset, board_size + margin / 2.0), font_size));
+        }
+    }
+
+    (marks, board_size + margin, board_size + margin)
+}
+
+/// Ranks go in a margin on the left and files in a margin below
+fn coordinate_margin(options: &DiagramOptions) -> f64 {
+    if options.coordinates { options.size as f64 / 8.0 * 0.4 } else { 0.0 }
+}
+
+fn write_png(path: &Path, marks: &[Mark], width: f64, height: f64) -> Result<(), String> {
+    let mut device = Device::new().map_err(|e| e.to_string())?;
+    let mut target = device.bitmap_target(width.ceil() as usize, height.ceil() as usize, 1.0)
+        .map_err(|e| e.to_string())?;
+    render(&mut target, marks, width, height)?;
+    target.save_to_file(path).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// Writes each position of a game as a frame of an animated GIF
+pub fn export_gif(path: &Path, positions: &[Position], options: &DiagramOptions) -> Result<(), String> {
+    let size = (options.size as f64 + coordinate_margin(options)).ceil() as u16;
+    let mut device = Device::new().map_err(|e| e.to_string())?;
+    let file = File::create(path).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    let mut encoder = gif::Encoder::new(file, size, size, &[]).map_err(|e| e.to_string())?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+    for (state, board) in positions {
+        let (marks, width, height) = diagram_marks(board, state.last_move, options);
+        let mut target = device.bitmap_target(size as usize, size as usize, 1.0)
+            .map_err(|e| e.to_string())?;
+        render(&mut target, &marks, width, height)?;
+        // The background is opaque, so premultiplied pixels equal straight RGBA
+        let image = target.to_image_buf(ImageFormat::RgbaPremul).map_err(|e| e.to_string())?;
+        let mut pixels = image.raw_pixels().to_vec();
+
+        let mut frame = gif::Frame::from_rgba_speed(size, size, &mut pixels, 10);
+        frame.delay = (options.frame_delay / 10) as u16; // GIF delays are in hundredths of a second
+        encoder.write_frame(&frame).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn render(target: &mut BitmapTarget, marks: &[Mark], width: f64, height: f64) -> Result<(), String> {
+    let mut rc = target.render_context();
+    rc.fill(Rect::from_origin_size((0.0, 0.0), (width, height)), &Color::WHITE);
+    for mark in marks {
+        match mark {
+            Mark::Fill(shape, color) => rc.fill(shape, color),
+            Mark::Stroke(shape, color, width) => rc.stroke(shape, color, *width),
+            Mark::Label(text, center, font_size) => {
+                let layout = rc.text().new_text_layout(text.clone())
+                    .font(druid::FontFamily::SYSTEM_UI, *font_size)
+                    .text_color(Color::BLACK)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let size = layout.size();
+                rc.draw_text(&layout, (center.x - size.width / 2.0, center.y - size.height / 2.0));
+            }
+        }
+    }
+    rc.finish().map_err(|e| e.to_string())
+}
+
+fn svg_document(marks: &[Mark], width: f64, height: f64) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width, h = height
+    );
+    let _ = writeln!(svg, "<rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>", width, height);
+    for mark in marks {
+        let _ = match mark {
+            Mark::Fill(shape, color) => writeln!(svg, "<path d=\"{}\" fill=\"{}\"/>", shape.to_svg(), svg_color(color)),
+            Mark::Stroke(shape, color, width) => writeln!(
+                svg, "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+                shape.to_svg(), svg_color(color), width
+            ),
+            Mark::Label(text, center, font_size) => writeln!(
+                svg, "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                center.x, center.y, font_size, text
+            ),
+        };
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn svg_color(color: &Color) -> String {
+    let (r, g, b, _) = color.as_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
@@ -0,0 +1,111 @@
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::{theme, BoxConstraints, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Selector, Size, UpdateCtx, Widget};
+use crate::app::AppState;
+use crate::book::polyglot::polyglot_key;
+use crate::engine::search::Analysis;
+use crate::engine::tablebase::TablebaseResult;
+use crate::game::notation::ParsedMove;
+use super::move_list::{move_list_width, text_sizes};
+
+/// Plays the first move of an analysis line clicked in the engine panel
+pub(super) const PLAY_ANALYSIS_MOVE: Selector<ParsedMove> = Selector::new("chess_rust.play-analysis-move");
+
+/// Moves of an analysis line shown
+pub(super) const ANALYSIS_LINE_MOVES: usize = 6;
+
+/// What the analysis found at its last depth about a position
+pub struct EngineReport {
+    pub key: u64, // Polyglot key of the position analysed
+    pub analysis: Analysis,
+    pub tablebase: Option<TablebaseResult>, // Outcome of the position, when the tablebase knows it
+    pub tablebase_line: Option<(ParsedMove, Vec<String>)>, // The tablebase's best move and the line following it in SAN
+}
+
+/// The engine's output in analysis mode: how far it got, the tablebase outcome with its line and
+/// the best lines found, each of which plays its first move when clicked
+pub(super) struct EnginePanel {
+    lines: Vec<(Rect, ParsedMove)>, // Where the analysis lines were painted, with their first move
+}
+
+impl EnginePanel {
+    pub(super) fn new() -> Self {
+        Self { lines: Vec::new() }
+    }
+}
+
+/// The rows under the panel's title, with the first move of each analysis line. A report about
+/// another position is left from before the position shown changed, until the next one comes.
+fn rows(data: &AppState) -> Vec<(String, Option<ParsedMove>)> {
+    let (state, board) = data.shown_position();
+    let report = data.board_view.engine_report.as_ref().filter(|report| report.key == polyglot_key(&state, &board));
+    let stats = match report {
+        Some(report) => format!("Depth {}, {} nodes, {} nodes/s", report.analysis.depth, report.analysis.nodes, report.analysis.nodes_per_second()),
+        None => "Searching...".to_string(),
+    };
+    let mut rows = vec![(stats, None)];
+    if let Some(report) = report {
+        match (report.tablebase, &report.tablebase_line) {
+            (Some(result), Some((first, moves))) => {
+                let moves = moves.iter().take(ANALYSIS_LINE_MOVES).cloned().collect::<Vec<_>>().join(" ");
+                rows.push((format!("Tablebase: {} {}", result.text(), moves), Some(*first)));
+            }
+            (Some(result), None) => rows.push((format!("Tablebase: {}", result.text()), None)),
+            _ => {}
+        }
+    }
+    for (rank, line) in report.iter().flat_map(|report| &report.analysis.lines).enumerate() {
+        let moves = line.moves.iter().take(ANALYSIS_LINE_MOVES).cloned().collect::<Vec<_>>().join(" ");
+        rows.push((format!("{}. {} {}", rank + 1, line.score_text(), moves), Some(line.first)));
+    }
+    rows
+}
+
+impl Widget<AppState> for EnginePanel {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AppState, _env: &Env) {
+        let Event::MouseDown(mouse_event) = event else { return };
+        if let Some(&(_, first)) = self.lines.iter().find(|(rect, _)| rect.contains(mouse_event.pos)) {
+            ctx.submit_command(PLAY_ANALYSIS_MOVE.with(first));
+            ctx.set_handled();
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppState, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &Env) {
+        ctx.request_layout();
+        ctx.request_paint();
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppState, _env: &Env) -> Size {
+        let (header_size, move_size, line_height) = text_sizes(data.low_vision);
+        let height = header_size + 9.0 + rows(data).len() as f64 * line_height + line_height / 2.0;
+        bc.constrain((move_list_width(move_size), height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
+        let text_color = env.get(theme::TEXT_COLOR);
+        let (header_size, move_size, line_height) = text_sizes(data.low_vision);
+        let title = ctx.text().new_text_layout("Engine:")
+            .font(FontFamily::SYSTEM_UI, header_size)
+            .text_color(text_color)
+            .build()
+            .unwrap();
+        ctx.draw_text(&title, (0.0, 0.0));
+
+        self.lines.clear();
+        let mut y = header_size + 9.0;
+        for (text, first) in rows(data) {
+            let layout = ctx.text().new_text_layout(text)
+                .font(FontFamily::SYSTEM_UI, move_size)
+                .text_color(text_color)
+                .build()
+                .unwrap();
+            let origin = Point::new(0.0, y);
+            ctx.draw_text(&layout, origin);
+            if let Some(first) = first {
+                self.lines.push((Rect::from_origin_size(origin, layout.size()), first));
+            }
+            y += line_height;
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use druid::widget::{CrossAxisAlignment, Either, Flex, Label, MainAxisAlignment, SizedBox};
+use druid::{theme, Env, FontDescriptor, FontFamily, Widget, WidgetExt};
+use crate::app::AppState;
+use super::chess_board::ChessBoard;
+use super::engine_panel::EnginePanel;
+use super::move_list::{navigation_bar, text_sizes, MoveList};
+use super::player_bar::PlayerBar;
+use super::status_bar::status_bar;
+use super::toolbar::toolbar;
+
+/// Room on either side of the panel beside the board
+const SIDE_PANEL_GAP: f64 = 30.0;
+
+/// A game as a window shows it: the status line across the top, and under it `board` between
+/// the player bars and above the toolbar, with the engine output and the move list on the side
+/// picked for them
+pub fn game_view(board: ChessBoard) -> impl Widget<AppState> {
+    let board_column = Flex::column()
+        .main_axis_alignment(MainAxisAlignment::Center)
+        .must_fill_main_axis(true)
+        .with_child(PlayerBar::new(true))
+        .with_flex_child(board, 1.0)
+        .with_child(PlayerBar::new(false))
+        .with_child(toolbar());
+    let row = Flex::row()
+        .with_child(Either::new(|data: &AppState, _env| data.left_handed, side_panel(), SizedBox::empty()))
+        .with_flex_child(board_column.padding(5.0), 1.0)
+        .with_child(Either::new(|data: &AppState, _env| !data.left_handed, side_panel(), SizedBox::empty()));
+    Flex::column()
+        .with_child(status_bar())
+        .with_flex_child(row, 1.0)
+}
+
+/// The engine output while analysis mode is on, then the move list with the buttons that step
+/// through the game
+fn side_panel() -> impl Widget<AppState> {
+    let history_title = Label::new(|data: &AppState, _env: &Env| {
+        String::from(if data.board_view.reviewing { "Move History (reviewing...):" } else { "Move History:" })
+    })
+    .env_scope(|env, data: &AppState| {
+        let (header_size, _, _) = text_sizes(data.low_vision);
+        env.set(theme::UI_FONT, FontDescriptor::new(FontFamily::SYSTEM_UI).with_size(header_size));
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Either::new(|data: &AppState, _env| data.board_view.analysing, EnginePanel::new(), SizedBox::empty()))
+        .with_child(history_title)
+        .with_spacer(9.0)
+        .with_child(navigation_bar())
+        .with_spacer(6.0)
+        .with_flex_child(MoveList::new(), 1.0)
+        .padding((SIDE_PANEL_GAP, 5.0))
+}
@@ -0,0 +1,106 @@
+#[cfg(feature = "debug-hud")]
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "debug-hud")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "debug-hud")]
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting allocations so the debug HUD can show how many a frame makes.
+/// Only installed with the `debug-hud` feature, as the count slows down every allocation.
+#[cfg(feature = "debug-hud")]
+pub struct CountingAllocator;
+
+#[cfg(feature = "debug-hud")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Timings of the board widget for the debug HUD
+pub struct FrameStats {
+    paint_started: Option<(Instant, Option<usize>)>,
+    pending_input: Option<Instant>, // First input since the last paint
+    last_paint: Duration,
+    average_paint: Duration, // Exponential moving average
+    last_latency: Option<Duration>,
+    last_allocations: Option<usize>,
+}
+
+impl FrameStats {
+    /// Allocations made so far by every thread of the process, when they are counted
+    fn allocations() -> Option<usize> {
+        #[cfg(feature = "debug-hud")]
+        return Some(ALLOCATIONS.load(Ordering::Relaxed));
+        #[cfg(not(feature = "debug-hud"))]
+        None
+    }
+
+    pub fn new() -> Self {
+        Self {
+            paint_started: None,
+            pending_input: None,
+            last_paint: Duration::ZERO,
+            average_paint: Duration::ZERO,
+            last_latency: None,
+            last_allocations: None,
+        }
+    }
+
+    /// Notes an input event, so that the next paint can measure how long it took to show
+    pub fn input(&mut self) {
+        self.pending_input.get_or_insert_with(Instant::now);
+    }
+
+    pub fn paint_start(&mut self) {
+        let now = Instant::now();
+        if let Some(input) = self.pending_input.take() {
+            self.last_latency = Some(now - input);
+        }
+        self.paint_started = Some((now, Self::allocations()));
+    }
+
+    pub fn paint_end(&mut self) {
+        if let Some((started, allocations)) = self.paint_started.take() {
+            self.last_paint = started.elapsed();
+            self.average_paint = if self.average_paint.is_zero() {
+                self.last_paint
+            } else {
+                self.average_paint.mul_f64(0.9) + self.last_paint.mul_f64(0.1)
+            };
+            self.last_allocations = allocations.zip(Self::allocations()).map(|(before, after)| after - before);
+        }
+    }
+
+    /// HUD lines describing the last painted frame
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("paint {:.2} ms (avg {:.2} ms)", ms(self.last_paint), ms(self.average_paint)),
+            match self.last_latency {
+                Some(latency) => format!("input to paint {:.2} ms", ms(latency)),
+                None => "input to paint -".to_string(),
+            },
+            // Other threads, such as the engine's, allocate during the paint too
+            match self.last_allocations {
+                Some(allocations) => format!("allocations (whole process) {}", allocations),
+                None => "allocations not counted (debug-hud feature)".to_string(),
+            },
+        ]
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -1,2 +1,14 @@
 pub mod chess_board;
-mod chess_square;
\ No newline at end of file
+pub mod diagram;
+pub mod engine_panel;
+pub mod game_view;
+pub mod hud;
+mod move_list;
+pub mod palette;
+mod piece_shapes;
+pub mod piece_set;
+mod player_bar;
+mod popups;
+mod status_bar;
+mod toolbar;
+pub mod variation_tree;
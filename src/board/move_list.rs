@@ -0,0 +1,221 @@
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::widget::{Flex, Label, Painter};
+use druid::{theme, BoxConstraints, Color, Data, Env, Event, EventCtx, FontDescriptor, FontFamily, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Selector, Size, UpdateCtx, Widget, WidgetExt};
+use crate::app::AppState;
+use crate::dialog::error_dialog;
+use crate::game::notation::to_figurine;
+use crate::theme::{HIGHLIGHT_COLOR, MUTED_TEXT_COLOR, PANEL_COLOR};
+use super::chess_board::paint_quality_badge;
+
+/// Shows what the review found about the move at this ply
+pub(super) const SHOW_MOVE_REVIEW: Selector<usize> = Selector::new("chess_rust.show-move-review");
+
+/// Steps through the moves of the game, from the buttons above the move list or the arrow keys
+#[derive(Clone, Copy)]
+pub(super) enum Navigation {
+    First,
+    Previous,
+    Next,
+    Last, // Back to the live position
+}
+
+impl Navigation {
+    const ALL: [Navigation; 4] = [Navigation::First, Navigation::Previous, Navigation::Next, Navigation::Last];
+
+    fn label(self) -> &'static str {
+        match self {
+            Navigation::First => "|<",
+            Navigation::Previous => "<",
+            Navigation::Next => ">",
+            Navigation::Last => ">|",
+        }
+    }
+
+    /// The ply to show from `shown` in a game of `plies` moves
+    fn target(self, shown: usize, plies: usize) -> usize {
+        match self {
+            Navigation::First => 0,
+            Navigation::Previous => shown.saturating_sub(1),
+            Navigation::Next => (shown + 1).min(plies),
+            Navigation::Last => plies,
+        }
+    }
+
+    /// Moves through the game, showing an error when the position cannot be replayed
+    pub(super) fn go(self, ctx: &mut EventCtx, data: &mut AppState) {
+        let plies = data.game_state.san_moves.len();
+        let target = self.target(data.viewed_ply.unwrap_or(plies), plies);
+        if let Err(message) = data.view_ply(target) {
+            ctx.new_window(error_dialog("Cannot show the move", &message));
+        }
+        ctx.request_paint();
+        ctx.set_handled();
+    }
+}
+
+/// Font sizes of the headers and moves beside the board and the height of a line of moves,
+/// larger in low-vision mode
+pub(super) fn text_sizes(low_vision: bool) -> (f64, f64, f64) {
+    if low_vision { (24.0, 22.0, 30.0) } else { (16.0, 14.0, 20.0) }
+}
+
+/// Width of the move list: the move numbers, then two columns of moves with room for a review mark
+pub(super) fn move_list_width(move_size: f64) -> f64 {
+    move_size * 3.5 + 2.0 * (move_size * 6.0 + move_size * 1.3)
+}
+
+/// Buttons to step through the game; the last one stands out while an earlier position is
+/// shown, as it returns to the game
+pub(super) fn navigation_bar() -> impl Widget<AppState> {
+    let mut row = Flex::row();
+    for navigation in Navigation::ALL {
+        let background = Painter::new(move |ctx, data: &AppState, env| {
+            let live = matches!(navigation, Navigation::Last) && data.viewed_ply.is_some();
+            let bounds = ctx.size().to_rect();
+            ctx.fill(bounds, &env.get(if live { HIGHLIGHT_COLOR } else { PANEL_COLOR }));
+        });
+        let button = Label::new(navigation.label())
+            .padding((12.0, 2.0))
+            .background(background)
+            .border(Color::rgb8(150, 150, 150), 1.0)
+            .on_click(move |ctx, data: &mut AppState, _env| navigation.go(ctx, data));
+        row.add_child(button);
+        row.add_spacer(4.0);
+    }
+    row.env_scope(|env, data: &AppState| {
+        let (_, move_size, _) = text_sizes(data.low_vision);
+        env.set(theme::UI_FONT, FontDescriptor::new(FontFamily::MONOSPACE).with_size(move_size));
+    })
+}
+
+/// The moves of the game in two columns under their numbers, scrolled with the mouse wheel.
+/// Each move can be clicked to show the position after it, and moves with a review annotation
+/// get a mark after them, which can be clicked as well.
+pub(super) struct MoveList {
+    scroll: Option<f64>, // Scroll offset; None keeps the latest move in view
+    max_scroll: f64, // Scroll offset that brings the latest move into view
+    moves: Vec<(Rect, usize)>, // Where the moves were painted, with the ply after them
+    marks: Vec<(Rect, usize)>, // Where the annotation marks were painted, with their move
+}
+
+impl MoveList {
+    pub(super) fn new() -> Self {
+        Self { scroll: None, max_scroll: 0.0, moves: Vec::new(), marks: Vec::new() }
+    }
+}
+
+impl Widget<AppState> for MoveList {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
+        match event {
+            Event::Wheel(mouse_event) => {
+                // Scrolling down to the latest move follows the game again
+                let offset = self.scroll.unwrap_or(self.max_scroll) + mouse_event.wheel_delta.y;
+                self.scroll = (offset < self.max_scroll).then_some(offset.max(0.0));
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            Event::MouseDown(mouse_event) => {
+                if let Some(&(_, ply)) = self.marks.iter().find(|(rect, _)| rect.contains(mouse_event.pos)) {
+                    ctx.submit_command(SHOW_MOVE_REVIEW.with(ply));
+                    ctx.set_handled();
+                } else if let Some(&(_, ply)) = self.moves.iter().find(|(rect, _)| rect.contains(mouse_event.pos)) {
+                    if let Err(message) = data.view_ply(ply) {
+                        ctx.new_window(error_dialog("Cannot show the move", &message));
+                    }
+                    ctx.request_paint();
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppState, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        // A move played or taken back, or another game or line taking the board, brings the
+        // latest move back into view
+        let other_moves = !old_data.game_state.san_moves.same(&data.game_state.san_moves);
+        if other_moves || old_data.active_tab != data.active_tab || !old_data.variation.same(&data.variation) {
+            self.scroll = None;
+        }
+        if old_data.low_vision != data.low_vision {
+            ctx.request_layout();
+        }
+        ctx.request_paint();
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppState, _env: &Env) -> Size {
+        let (_, move_size, line_height) = text_sizes(data.low_vision);
+        let height = if bc.is_height_bounded() { bc.max().height } else { data.game_state.move_history.len() as f64 * line_height };
+        bc.constrain((move_list_width(move_size), height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
+        let (text_color, muted_color, highlight_color) = (env.get(theme::TEXT_COLOR), env.get(MUTED_TEXT_COLOR), env.get(HIGHLIGHT_COLOR));
+        let (_, move_size, line_height) = text_sizes(data.low_vision);
+        let mark_size = move_size * 1.3;
+        let (number_width, column_width) = (move_size * 3.5, move_size * 6.0 + mark_size);
+        let mut rows = Vec::new();
+        let mut ply = 0;
+        for move_text in &data.game_state.move_history {
+            // A line holds a move of each side, or one in "12... e5" and on the last line
+            let mut tokens = move_text.split_whitespace();
+            let number = tokens.next().unwrap_or_default().to_string();
+            let mut columns: [Option<(usize, String)>; 2] = [None, None];
+            let first_column = if number.contains("...") { 1 } else { 0 };
+            for (column, token) in columns.iter_mut().skip(first_column).zip(tokens) {
+                *column = Some((ply, token.to_string()));
+                ply += 1;
+            }
+            rows.push((number.trim_end_matches('.').to_string(), columns));
+        }
+
+        let area = ctx.size().to_rect();
+        self.max_scroll = (rows.len() as f64 * line_height - area.height()).max(0.0);
+        let scroll = self.scroll.unwrap_or(self.max_scroll).min(self.max_scroll);
+        let shown_ply = data.viewed_ply.unwrap_or(data.game_state.san_moves.len());
+        self.moves.clear();
+        self.marks.clear();
+        ctx.clip(area);
+        for (row, (number, columns)) in rows.iter().enumerate() {
+            let row_y = row as f64 * line_height - scroll;
+            if row_y + line_height < 0.0 || row_y > area.y1 {
+                continue;
+            }
+            let number = ctx.text().new_text_layout(format!("{}.", number))
+                .font(FontFamily::MONOSPACE, move_size)
+                .text_color(muted_color)
+                .build()
+                .unwrap();
+            ctx.draw_text(&number, (0.0, row_y));
+
+            for (column, cell) in columns.iter().enumerate() {
+                let Some((ply, san)) = cell else { continue };
+                let x = number_width + column as f64 * column_width;
+                let text = if data.figurine_notation { to_figurine(san) } else { san.clone() };
+                // The moves of a line tried are set in italics after the game's own
+                let tried = data.variation_start().is_some_and(|start| *ply >= start);
+                let layout = ctx.text().new_text_layout(text)
+                    .font(FontFamily::MONOSPACE, move_size)
+                    .default_attribute(if tried { druid::FontStyle::Italic } else { druid::FontStyle::Regular })
+                    .text_color(text_color)
+                    .build()
+                    .unwrap();
+                let rect = Rect::from_origin_size((x - 2.0, row_y), (column_width - 4.0, line_height));
+                // The move that led to the position shown
+                if ply + 1 == shown_ply {
+                    ctx.fill(rect, &highlight_color);
+                }
+                ctx.draw_text(&layout, (x, row_y));
+                self.moves.push((rect.intersect(area), ply + 1));
+
+                if let Some(quality) = data.game_state.move_qualities.get(*ply).copied().flatten() {
+                    let center = Point::new(x + layout.size().width + 4.0 + mark_size / 2.0, row_y + layout.size().height / 2.0);
+                    paint_quality_badge(ctx, center, mark_size / 2.0, quality);
+                    self.marks.push((Rect::from_center_size(center, (mark_size, mark_size)), *ply));
+                }
+            }
+        }
+    }
+}
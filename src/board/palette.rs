@@ -0,0 +1,233 @@
+use std::time::Duration;
+use druid::{Color, Data, Lens};
+use serde::{Deserialize, Serialize};
+use crate::engine::tablebase::TablebaseResult;
+use crate::game::annotation::{MarkColor, MoveQuality};
+
+/// Colors used for the board overlays. The alternatives avoid the color pairs that are hard
+/// to tell apart with the matching color vision deficiency, mostly using the Okabe-Ito palette.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum HighlightPalette {
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl HighlightPalette {
+    pub const ALL: [HighlightPalette; 4] = [
+        HighlightPalette::Standard,
+        HighlightPalette::Deuteranopia,
+        HighlightPalette::Protanopia,
+        HighlightPalette::Tritanopia,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HighlightPalette::Standard => "Standard",
+            HighlightPalette::Deuteranopia => "Deuteranopia safe",
+            HighlightPalette::Protanopia => "Protanopia safe",
+            HighlightPalette::Tritanopia => "Tritanopia safe",
+        }
+    }
+
+    pub fn selection(self) -> Color {
+        match self {
+            HighlightPalette::Standard => Color::rgb8(255, 255, 0),
+            HighlightPalette::Deuteranopia | HighlightPalette::Protanopia => Color::rgb8(240, 228, 66),
+            HighlightPalette::Tritanopia => Color::rgb8(255, 130, 170),
+        }
+    }
+
+    pub fn legal_move(self) -> Color {
+        match self {
+            HighlightPalette::Standard => Color::rgb8(144, 238, 144),
+            HighlightPalette::Deuteranopia | HighlightPalette::Protanopia => Color::rgb8(86, 180, 233),
+            HighlightPalette::Tritanopia => Color::rgb8(0, 158, 115),
+        }
+    }
+
+    pub fn last_move(self) -> Color {
+        match self {
+            HighlightPalette::Standard => Color::rgb8(255, 140, 0),
+            HighlightPalette::Deuteranopia | HighlightPalette::Protanopia => Color::rgb8(230, 159, 0),
+            HighlightPalette::Tritanopia => Color::rgb8(213, 94, 0),
+        }
+    }
+
+    pub fn check(self) -> Color {
+        match self {
+            HighlightPalette::Standard => Color::rgb8(230, 60, 60),
+            HighlightPalette::Deuteranopia => Color::rgb8(213, 94, 0),
+            // Reds look dark and muddy without L cones, so use a strong blue instead
+            HighlightPalette::Protanopia => Color::rgb8(0, 114, 178),
+            HighlightPalette::Tritanopia => Color::rgb8(150, 0, 0),
+        }
+    }
+
+    pub fn book_move(self) -> Color {
+        match self {
+            HighlightPalette::Standard => Color::rgb8(135, 206, 250),
+            HighlightPalette::Deuteranopia | HighlightPalette::Protanopia => Color::rgb8(204, 121, 167),
+            HighlightPalette::Tritanopia => Color::rgb8(120, 120, 120),
+        }
+    }
+}
+
+/// Colors of the light and dark squares
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum BoardTheme {
+    Grey,
+    Wood,
+    Green,
+    Blue,
+    Custom, // The colors typed in the settings
+}
+
+impl BoardTheme {
+    pub const ALL: [BoardTheme; 5] = [BoardTheme::Grey, BoardTheme::Wood, BoardTheme::Green, BoardTheme::Blue, BoardTheme::Custom];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BoardTheme::Grey => "Grey",
+            BoardTheme::Wood => "Wood",
+            BoardTheme::Green => "Green",
+            BoardTheme::Blue => "Blue",
+            BoardTheme::Custom => "Custom",
+        }
+    }
+
+    /// The light and dark square colors, the grey ones a little darker in dark mode. A custom
+    /// color that does not parse falls back to the grey theme's.
+    pub fn squares(self, custom: &CustomColors, dark_mode: bool) -> (Color, Color) {
+        match self {
+            BoardTheme::Grey if dark_mode => (Color::rgb8(160, 160, 160), Color::rgb8(85, 85, 85)),
+            BoardTheme::Grey => (Color::rgb8(200, 200, 200), Color::rgb8(100, 100, 100)),
+            BoardTheme::Wood => (Color::rgb8(240, 217, 181), Color::rgb8(181, 136, 99)),
+            BoardTheme::Green => (Color::rgb8(238, 238, 210), Color::rgb8(118, 150, 86)),
+            BoardTheme::Blue => (Color::rgb8(222, 227, 230), Color::rgb8(140, 162, 173)),
+            BoardTheme::Custom => {
+                let (light, dark) = BoardTheme::Grey.squares(custom, dark_mode);
+                (parse_color(&custom.light).unwrap_or(light), parse_color(&custom.dark).unwrap_or(dark))
+            }
+        }
+    }
+}
+
+/// Colors typed in the settings as `#RRGGBB`
+#[derive(Clone, PartialEq, Eq, Debug, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomColors {
+    pub light: String, // Squares of BoardTheme::Custom
+    pub dark: String,
+    pub selection: String, // Replace the highlight palette's colors when set
+    pub last_move: String,
+}
+
+impl CustomColors {
+    pub fn new() -> Self {
+        Self { light: "#C8C8C8".to_string(), dark: "#646464".to_string(), selection: String::new(), last_move: String::new() }
+    }
+
+    pub fn selection_color(&self, palette: HighlightPalette) -> Color {
+        parse_color(&self.selection).unwrap_or(palette.selection())
+    }
+
+    pub fn last_move_color(&self, palette: HighlightPalette) -> Color {
+        parse_color(&self.last_move).unwrap_or(palette.last_move())
+    }
+}
+
+impl Default for CustomColors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A color typed as `#RRGGBB` or `RRGGBB`, `None` when it is empty or not a color
+fn parse_color(text: &str) -> Option<Color> {
+    Color::from_hex_str(text.trim()).ok()
+}
+
+/// How the squares the selected piece can move to are marked
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum MoveIndicator {
+    Dots, // A dot on empty squares and a ring around the pieces it can take
+    Tint, // The whole square filled
+}
+
+impl MoveIndicator {
+    pub const ALL: [MoveIndicator; 2] = [MoveIndicator::Dots, MoveIndicator::Tint];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveIndicator::Dots => "Dots and rings",
+            MoveIndicator::Tint => "Tinted squares",
+        }
+    }
+}
+
+/// How fast a moved piece slides to its square
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum AnimationSpeed {
+    Off,
+    Fast,
+    Normal,
+    Slow,
+}
+
+impl AnimationSpeed {
+    pub const ALL: [AnimationSpeed; 4] = [AnimationSpeed::Off, AnimationSpeed::Fast, AnimationSpeed::Normal, AnimationSpeed::Slow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationSpeed::Off => "Off",
+            AnimationSpeed::Fast => "Fast",
+            AnimationSpeed::Normal => "Normal",
+            AnimationSpeed::Slow => "Slow",
+        }
+    }
+
+    /// How long the slide takes, `None` when pieces jump straight to their square
+    pub fn duration(self) -> Option<Duration> {
+        match self {
+            AnimationSpeed::Off => None,
+            AnimationSpeed::Fast => Some(Duration::from_millis(100)),
+            AnimationSpeed::Normal => Some(Duration::from_millis(200)),
+            AnimationSpeed::Slow => Some(Duration::from_millis(400)),
+        }
+    }
+}
+
+impl MarkColor {
+    /// Translucent, so that the pieces under a mark stay visible
+    pub fn color(self) -> Color {
+        match self {
+            MarkColor::Green => Color::rgba8(21, 120, 27, 200),
+            MarkColor::Red => Color::rgba8(200, 30, 30, 200),
+            MarkColor::Blue => Color::rgba8(0, 48, 136, 200),
+            MarkColor::Yellow => Color::rgba8(230, 160, 0, 200),
+        }
+    }
+}
+
+/// Badge colors for move quality annotations, as used by the common online review tools
+pub fn move_quality_color(quality: MoveQuality) -> Color {
+    match quality {
+        MoveQuality::Brilliant => Color::rgb8(27, 172, 166),
+        MoveQuality::Good => Color::rgb8(92, 139, 176),
+        MoveQuality::Interesting => Color::rgb8(150, 110, 190),
+        MoveQuality::Dubious => Color::rgb8(247, 198, 49),
+        MoveQuality::Mistake => Color::rgb8(229, 143, 42),
+        MoveQuality::Blunder => Color::rgb8(202, 52, 49),
+    }
+}
+
+/// Legal move highlights by the outcome the move keeps for the mover
+pub fn tablebase_color(result: TablebaseResult) -> Color {
+    match result {
+        TablebaseResult::Win(_) => Color::rgb8(92, 170, 90),
+        TablebaseResult::Draw => Color::rgb8(160, 160, 160),
+        TablebaseResult::Loss(_) => Color::rgb8(202, 52, 49),
+    }
+}
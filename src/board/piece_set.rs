@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use druid::piet::PietImage;
+use druid::{ImageFormat, InterpolationMode, PaintCtx, Rect, RenderContext};
+use resvg::tiny_skia::{FilterQuality, Pixmap, PixmapPaint, Transform};
+use resvg::usvg;
+use crate::pieces::{Piece, PieceColor, PieceType};
+
+/// Folder of the piece sets that come with the app, relative to the working directory
+const BUNDLED_SETS_PATH: &str = "pieces";
+
+/// Rasterized images kept before the cache starts over, enough for a few sizes of every piece
+const MAX_CACHED_IMAGES: usize = 48;
+
+/// Where piece sets are looked for: those bundled with the app, then the user's own in
+/// `chess_rust/pieces` of the platform's configuration folder
+fn set_folders() -> Vec<PathBuf> {
+    let mut folders = vec![PathBuf::from(BUNDLED_SETS_PATH)];
+    folders.extend(dirs::config_dir().map(|folder| folder.join("chess_rust").join("pieces")));
+    folders
+}
+
+/// Names of the piece sets found, sorted. Each set is a folder with an image for every piece,
+/// named as in most published sets: `wK.svg` for the white king, `bN.png` for a black knight.
+pub fn available_sets() -> Vec<String> {
+    let mut names: Vec<String> = set_folders().iter()
+        .filter_map(|folder| std::fs::read_dir(folder).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            entry.file_type().ok()?.is_dir().then(|| entry.file_name().to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Switches `piece_set` to the set called `name`, or to the built-in shapes when it is empty.
+/// The set already loaded is kept when it is the one asked for.
+pub fn switch_piece_set(piece_set: &mut Option<PieceSet>, name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        *piece_set = None;
+    } else if piece_set.as_ref().is_none_or(|set| set.name() != name) {
+        *piece_set = Some(PieceSet::load(name)?);
+    }
+    Ok(())
+}
+
+/// Image of one piece as read from its file
+enum Source {
+    Svg(Box<usvg::Tree>),
+    Png(Pixmap),
+}
+
+/// A piece set loaded from its images, rasterized once for each size it is drawn at
+pub struct PieceSet {
+    name: String,
+    sources: Vec<Source>, // By `index`
+    images: HashMap<(usize, u32), PietImage>, // By piece index and size in device pixels
+}
+
+impl PieceSet {
+    /// Loads the set called `name`, failing when one of its twelve images is missing or unreadable
+    pub fn load(name: &str) -> Result<Self, String> {
+        let folder = set_folders().into_iter()
+            .map(|folder| folder.join(name))
+            .find(|folder| folder.is_dir())
+            .ok_or_else(|| format!("no piece set called {}", name))?;
+        let mut sources = Vec::with_capacity(12);
+        for color in [PieceColor::White, PieceColor::Black] {
+            for piece_type in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King] {
+                sources.push(load_source(&folder, &file_stem(Piece { piece_type, color }))?);
+            }
+        }
+        Ok(Self { name: name.to_string(), sources, images: HashMap::new() })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Draws `piece` filling `rect`, which is expected to be square. The image is rasterized at
+    /// the display's pixel size rather than the window's, so that it stays sharp on high-DPI
+    /// displays.
+    pub fn paint(&mut self, ctx: &mut PaintCtx, piece: Piece, rect: Rect) {
+        let size = (rect.width() * ctx.scale().x()).round().max(1.0) as u32;
+        let key = (index(piece), size);
+        if !self.images.contains_key(&key) {
+            let Some(pixels) = rasterize(&self.sources[key.0], size) else { return };
+            let Ok(image) = ctx.make_image(size as usize, size as usize, &pixels, ImageFormat::RgbaPremul) else { return };
+            if self.images.len() >= MAX_CACHED_IMAGES {
+                self.images.clear();
+            }
+            self.images.insert(key, image);
+        }
+        ctx.draw_image(&self.images[&key], rect, InterpolationMode::Bilinear);
+    }
+}
+
+/// Position of a piece in `PieceSet::sources`: the white pieces from pawn to king, then the black
+fn index(piece: Piece) -> usize {
+    let kind = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    match piece.color {
+        PieceColor::White => kind,
+        PieceColor::Black => 6 + kind,
+    }
+}
+
+/// File name of a piece's image without the extension, such as `wK`
+fn file_stem(piece: Piece) -> String {
+    let color = match piece.color {
+        PieceColor::White => 'w',
+        PieceColor::Black => 'b',
+    };
+    let kind = match piece.piece_type {
+        PieceType::Pawn => 'P',
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        PieceType::King => 'K',
+    };
+    format!("{}{}", color, kind)
+}
+
+/// Reads the SVG image of a piece, or its PNG image when there is no SVG
+fn load_source(folder: &Path, stem: &str) -> Result<Source, String> {
+    let svg = folder.join(format!("{}.svg", stem));
+    if svg.is_file() {
+        let data = std::fs::read(&svg).map_err(|e| format!("cannot read {}: {}", svg.display(), e))?;
+        let tree = usvg::Tree::from_data(&data, &usvg::Options::default()).map_err(|e| format!("invalid SVG {}: {}", svg.display(), e))?;
+        return Ok(Source::Svg(Box::new(tree)));
+    }
+    let png = folder.join(format!("{}.png", stem));
+    let data = std::fs::read(&png).map_err(|e| format!("cannot read {}.svg or {}.png in {}: {}", stem, stem, folder.display(), e))?;
+    Pixmap::decode_png(&data).map(Source::Png).map_err(|e| format!("invalid PNG {}: {}", png.display(), e))
+}
+
+/// The image scaled to fit a square of `size` pixels, centered, as premultiplied RGBA
+fn rasterize(source: &Source, size: u32) -> Option<Vec<u8>> {
+    let mut pixmap = Pixmap::new(size, size)?;
+    let (width, height) = match source {
+        Source::Svg(tree) => (tree.size().width(), tree.size().height()),
+        Source::Png(image) => (image.width() as f32, image.height() as f32),
+    };
+    let scale = size as f32 / width.max(height);
+    let transform = Transform::from_translate((size as f32 - width * scale) / 2.0, (size as f32 - height * scale) / 2.0).pre_scale(scale, scale);
+    match source {
+        Source::Svg(tree) => resvg::render(tree, transform, &mut pixmap.as_mut()),
+        Source::Png(image) => {
+            let paint = PixmapPaint { quality: FilterQuality::Bicubic, ..PixmapPaint::default() };
+            pixmap.draw_pixmap(0, 0, image.as_ref(), &paint, transform, None);
+        }
+    }
+    Some(pixmap.take())
+}
@@ -0,0 +1,118 @@
+use druid::kurbo::{BezPath, Circle, Rect, Shape};
+use druid::Color;
+use crate::pieces::*;
+
+const TOLERANCE: f64 = 0.1;
+
+/// The filled shapes that draw a piece of width `piece_size` centered on the given point,
+/// shared by the board and exported diagrams
+pub fn piece_shapes(piece: Piece, center_x: f64, center_y: f64, piece_size: f64) -> Vec<(BezPath, Color)> {
+    let color = match piece.color {
+        PieceColor::White => Color::WHITE,
+        PieceColor::Black => Color::BLACK,
+    };
+    let mut shapes = Vec::new();
+
+    match piece.piece_type {
+        PieceType::King => {
+            // Cross base
+            let rect = Rect::from_center_size(
+                (center_x, center_y),
+                (piece_size * 0.2, piece_size),
+            );
+            shapes.push((rect.to_path(TOLERANCE), color));
+            let rect = Rect::from_center_size(
+                (center_x, center_y - piece_size * 0.3),
+                (piece_size * 0.6, piece_size * 0.2),
+            );
+            shapes.push((rect.to_path(TOLERANCE), color));
+            // Crown circle
+            let circle = Circle::new(
+                (center_x, center_y - piece_size * 0.35),
+                piece_size * 0.15,
+            );
+            shapes.push((circle.to_path(TOLERANCE), color));
+        },
+        PieceType::Queen => {
+            // Base
+            let mut path = BezPath::new();
+            path.move_to((center_x - piece_size * 0.3, center_y + piece_size * 0.3));
+            path.line_to((center_x + piece_size * 0.3, center_y + piece_size * 0.3));
+            path.line_to((center_x, center_y - piece_size * 0.4));
+            path.close_path();
+            shapes.push((path, color));
+            // Crown
+            for i in -2..=2 {
+                let circle = Circle::new(
+                    (center_x + (i as f64) * piece_size * 0.15, center_y - piece_size * 0.25),
+                    piece_size * 0.08,
+                );
+                shapes.push((circle.to_path(TOLERANCE), color));
+            }
+        },
+        PieceType::Rook => {
+            // Base
+            let rect = Rect::from_center_size(
+                (center_x, center_y + piece_size * 0.1),
+                (piece_size * 0.4, piece_size * 0.6),
+            );
+            shapes.push((rect.to_path(TOLERANCE), color));
+            // Battlements
+            for i in -1..=1 {
+                let rect = Rect::from_center_size(
+                    (center_x + (i as f64) * piece_size * 0.15, center_y - piece_size * 0.25),
+                    (piece_size * 0.1, piece_size * 0.2),
+                );
+                shapes.push((rect.to_path(TOLERANCE), color));
+            }
+        },
+        PieceType::Bishop => {
+            // Base triangle
+            let mut path = BezPath::new();
+            path.move_to((center_x - piece_size * 0.3, center_y + piece_size * 0.3));
+            path.line_to((center_x + piece_size * 0.3, center_y + piece_size * 0.3));
+            path.line_to((center_x, center_y - piece_size * 0.3));
+            path.close_path();
+            shapes.push((path, color));
+            // Top circle
+            let circle = Circle::new(
+                (center_x, center_y - piece_size * 0.35),
+                piece_size * 0.1,
+            );
+            shapes.push((circle.to_path(TOLERANCE), color));
+        },
+        PieceType::Knight => {
+            // Horse head shape
+            let mut path = BezPath::new();
+            path.move_to((center_x - piece_size * 0.2, center_y + piece_size * 0.3));
+            path.line_to((center_x + piece_size * 0.2, center_y + piece_size * 0.3));
+            path.line_to((center_x + piece_size * 0.2, center_y));
+            path.line_to((center_x + piece_size * 0.1, center_y - piece_size * 0.3));
+            path.line_to((center_x - piece_size * 0.2, center_y));
+            path.close_path();
+            shapes.push((path, color));
+            // Eye
+            let eye = Circle::new(
+                (center_x + piece_size * 0.05, center_y - piece_size * 0.1),
+                piece_size * 0.05,
+            );
+            shapes.push((eye.to_path(TOLERANCE), Color::rgb8(50, 50, 50)));
+        },
+        PieceType::Pawn => {
+            // Base
+            let circle = Circle::new(
+                (center_x, center_y + piece_size * 0.1),
+                piece_size * 0.2,
+            );
+            shapes.push((circle.to_path(TOLERANCE), color));
+            // Head
+            let circle = Circle::new(
+                (center_x, center_y - piece_size * 0.2),
+                piece_size * 0.15,
+            );
+            shapes.push((circle.to_path(TOLERANCE), color));
+        },
+    }
+
+    shapes
+}
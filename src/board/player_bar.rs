@@ -0,0 +1,117 @@
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::{BoxConstraints, Color, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Rect, RenderContext, Size, UpdateCtx, Widget};
+use crate::app::AppState;
+use crate::game::clock::time_text;
+use crate::game::material::{captured_in_game, captured_pieces, material_balance};
+use crate::pieces::{Piece, PieceColor};
+use crate::theme::{MUTED_TEXT_COLOR, PANEL_COLOR};
+use super::chess_board::paint_piece;
+use super::piece_set::{switch_piece_set, PieceSet};
+use super::piece_shapes::piece_shapes;
+
+/// Height of the player bars above and below the board
+const PLAYER_BAR_HEIGHT: f64 = 26.0;
+
+/// A player's name and clock, with the pieces they have captured and their material lead: the
+/// opponent's above the board, and the bottom side's below it
+pub struct PlayerBar {
+    top: bool, // Whether this is the bar above the board
+    piece_set: Option<PieceSet>, // Images the captured pieces are drawn with, as on the board
+}
+
+impl PlayerBar {
+    pub fn new(top: bool) -> Self {
+        Self { top, piece_set: None }
+    }
+}
+
+impl Widget<AppState> for PlayerBar {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut AppState, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppState, _env: &Env) {
+        // The board reports a set that cannot be loaded
+        if let LifeCycle::WidgetAdded = event {
+            let _ = switch_piece_set(&mut self.piece_set, &data.piece_set);
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        if old_data.piece_set != data.piece_set {
+            let _ = switch_piece_set(&mut self.piece_set, &data.piece_set);
+        }
+        ctx.request_paint();
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &AppState, _env: &Env) -> Size {
+        bc.constrain((bc.max().width, PLAYER_BAR_HEIGHT))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
+        let (state, board) = data.shown_position();
+        let (text_color, muted_color) = (env.get(druid::theme::TEXT_COLOR), env.get(MUTED_TEXT_COLOR));
+        let width = ctx.size().width;
+        let color = if self.top == data.flipped() { PieceColor::White } else { PieceColor::Black };
+        let opponent = if color == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+        ctx.fill(Rect::new(0.0, 2.0, width, PLAYER_BAR_HEIGHT - 2.0), &env.get(PANEL_COLOR));
+        if state.current_turn == color {
+            ctx.fill(Rect::new(0.0, 2.0, 4.0, PLAYER_BAR_HEIGHT - 2.0), &Color::rgb8(60, 140, 60));
+        }
+
+        let name = ctx.text().new_text_layout(data.player_label(color))
+            .font(druid::FontFamily::SYSTEM_UI, 14.0)
+            .default_attribute(druid::FontWeight::BOLD)
+            .text_color(text_color)
+            .build()
+            .unwrap();
+        ctx.draw_text(&name, (10.0, (PLAYER_BAR_HEIGHT - name.size().height) / 2.0));
+
+        // The live clock, or the time recorded after the last move of the position browsed to
+        let live = data.clock.as_ref().filter(|_| data.viewed_ply.is_none()).map(|clock| clock.remaining(color));
+        if let Some(ms) = live.or_else(|| state.clock_after(color)) {
+            let low = ms < 10_000;
+            let time = ctx.text().new_text_layout(time_text(ms))
+                .font(druid::FontFamily::MONOSPACE, 14.0)
+                .default_attribute(druid::FontWeight::BOLD)
+                .text_color(if low { data.highlight_palette.check() } else { text_color })
+                .build()
+                .unwrap();
+            ctx.draw_text(&time, (22.0 + name.size().width, (PLAYER_BAR_HEIGHT - time.size().height) / 2.0));
+        }
+
+        // Small icons of the pieces the player took, then the lead. Games saved before captures
+        // were logged fall back to the pieces missing from the board.
+        let captured = if state.captures.len() == state.san_moves.len() {
+            captured_in_game(&state.captures, opponent)
+        } else {
+            captured_pieces(&board, opponent)
+        };
+        let lead = material_balance(&board, color);
+        let mut right = width - 8.0;
+        if lead > 0 {
+            let lead = ctx.text().new_text_layout(format!("+{}", lead))
+                .font(druid::FontFamily::SYSTEM_UI, 14.0)
+                .text_color(muted_color)
+                .build()
+                .unwrap();
+            right -= lead.size().width;
+            ctx.draw_text(&lead, (right, (PLAYER_BAR_HEIGHT - lead.size().height) / 2.0));
+            right -= 4.0;
+        }
+        let icon_size = PLAYER_BAR_HEIGHT - 10.0;
+        let spacing = icon_size * 0.55; // Icons overlap a little, like a stack of taken pieces
+        for (i, &piece_type) in captured.iter().rev().enumerate() {
+            let center_x = right - icon_size / 2.0 - i as f64 * spacing;
+            let piece = Piece { piece_type, color: opponent };
+            if self.piece_set.is_some() || data.glyph_pieces {
+                // Images and glyphs carry their own outline and margin
+                let rect = Rect::from_center_size((center_x, PLAYER_BAR_HEIGHT / 2.0), (PLAYER_BAR_HEIGHT, PLAYER_BAR_HEIGHT));
+                paint_piece(ctx, self.piece_set.as_mut(), data.glyph_pieces, piece, rect, icon_size);
+            } else {
+                for (shape, fill) in piece_shapes(piece, center_x, PLAYER_BAR_HEIGHT / 2.0, icon_size) {
+                    ctx.fill(&shape, &fill);
+                    ctx.stroke(&shape, &Color::rgb8(60, 60, 60), 0.75);
+                }
+            }
+        }
+    }
+}
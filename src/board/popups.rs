@@ -0,0 +1,166 @@
+use druid::piet::{Text, TextLayoutBuilder};
+use druid::{BoxConstraints, Color, Env, Event, EventCtx, FontFamily, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Rect, RenderContext, Selector, Size, UpdateCtx, Widget};
+use crate::app::AppState;
+use crate::game::fen::square_name;
+use crate::game::notation::piece_letter;
+use crate::game::threats::{capture_sequence, exchange_gain};
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::chess_board::{oriented, paint_piece};
+use super::piece_set::{switch_piece_set, PieceSet};
+
+/// Plays the pending promotion with the piece picked
+pub(super) const PICK_PROMOTION: Selector<PieceType> = Selector::new("chess_rust.pick-promotion");
+
+/// The pieces a pawn can promote to, in the order the picker offers them from the promotion square
+const PROMOTION_PIECES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+/// Width of the exchange calculator
+const EXCHANGE_WIDTH: f64 = 230.0;
+
+/// Height of a line of the exchange calculator
+const EXCHANGE_LINE_HEIGHT: f64 = 18.0;
+
+/// The pieces a pending promotion can pick from, a square each. The board lays it over the
+/// promotion square and the three toward the middle of the board.
+pub(super) struct PromotionPicker {
+    piece_set: Option<PieceSet>, // Images the pieces are drawn with, as on the board
+}
+
+impl PromotionPicker {
+    pub(super) fn new() -> Self {
+        Self { piece_set: None }
+    }
+
+    /// Whether the queen is at the top, as it is for a promotion on the top row
+    fn downward(data: &AppState) -> bool {
+        data.board_view.promotion.is_some_and(|(_, to)| oriented(to / 8, to % 8, data.flipped()).0 == 0)
+    }
+}
+
+impl Widget<AppState> for PromotionPicker {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
+        let Event::MouseDown(mouse_event) = event else { return };
+        let square_size = ctx.size().width;
+        if data.board_view.promotion.is_none() || square_size <= 0.0 {
+            return;
+        }
+        let slot = ((mouse_event.pos.y / square_size) as usize).min(3);
+        let index = if Self::downward(data) { slot } else { 3 - slot };
+        ctx.submit_command(PICK_PROMOTION.with(PROMOTION_PIECES[index]));
+        ctx.set_handled();
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppState, _env: &Env) {
+        // The board reports a set that cannot be loaded
+        if let LifeCycle::WidgetAdded = event {
+            let _ = switch_piece_set(&mut self.piece_set, &data.piece_set);
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        if old_data.piece_set != data.piece_set {
+            let _ = switch_piece_set(&mut self.piece_set, &data.piece_set);
+        }
+        ctx.request_paint();
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &AppState, _env: &Env) -> Size {
+        bc.min()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, _env: &Env) {
+        if data.board_view.promotion.is_none() {
+            return;
+        }
+        let square_size = ctx.size().width;
+        let piece_size = square_size * if data.low_vision { 0.85 } else { 0.6 };
+        let color = data.game_state.current_turn;
+        let downward = Self::downward(data);
+        for (i, piece_type) in PROMOTION_PIECES.into_iter().enumerate() {
+            let slot = if downward { i } else { 3 - i };
+            let rect = Rect::from_origin_size((0.0, slot as f64 * square_size), (square_size, square_size));
+            ctx.fill(rect, &Color::WHITE);
+            ctx.stroke(rect, &Color::rgb8(100, 100, 100), 1.0);
+            paint_piece(ctx, self.piece_set.as_mut(), data.glyph_pieces, Piece { piece_type, color }, rect, piece_size);
+        }
+    }
+}
+
+/// The exchange calculator beside a capture under the mouse, telling who takes part in the
+/// capture sequence and how it ends in material. The board lays it beside the square.
+pub(super) struct ExchangePopup {
+    lines: Vec<String>,
+}
+
+impl ExchangePopup {
+    pub(super) fn new() -> Self {
+        Self { lines: Vec::new() }
+    }
+}
+
+impl Widget<AppState> for ExchangePopup {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut AppState, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppState, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &Env) {
+        ctx.request_layout();
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppState, _env: &Env) -> Size {
+        self.lines = data.board_view.hovered
+            .filter(|_| data.show_exchanges && data.viewed_ply.is_none())
+            .and_then(|square| exchange_lines(data, square))
+            .unwrap_or_default();
+        if self.lines.is_empty() {
+            return bc.min();
+        }
+        bc.constrain((EXCHANGE_WIDTH, EXCHANGE_LINE_HEIGHT * self.lines.len() as f64 + 8.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &AppState, _env: &Env) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgba8(0, 0, 0, 200));
+        let mut y = 4.0;
+        for line in &self.lines {
+            let layout = ctx.text().new_text_layout(line.clone())
+                .font(FontFamily::SYSTEM_UI, 13.0)
+                .text_color(Color::WHITE)
+                .build()
+                .unwrap();
+            ctx.draw_text(&layout, (6.0, y));
+            y += EXCHANGE_LINE_HEIGHT;
+        }
+    }
+}
+
+/// Lines of the exchange calculator for a piece of the opponent that the side to move can
+/// capture on `square`: who takes part in the capture sequence and the material outcome
+fn exchange_lines(data: &AppState, square: usize) -> Option<Vec<String>> {
+    let side = data.game_state.current_turn;
+    let target = data.board[square].filter(|piece| piece.color != side)?;
+    let board = data.current_board();
+    let pos = (square / 8, square % 8);
+    let gain = exchange_gain(&board, pos, side)?;
+
+    let capturers = capture_sequence(&board, pos, side);
+    let letters = |parity: usize| capturers.iter().skip(parity).step_by(2)
+        .map(|piece| piece_letter(piece.piece_type).to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (mover, opponent) = if side == PieceColor::White { ("White", "Black") } else { ("Black", "White") };
+    let defenders = letters(1);
+    Some(vec![
+        format!("Capture on {} ({})", square_name(pos), piece_letter(target.piece_type)),
+        format!("{} takes with: {}", mover, letters(0)),
+        format!("{} retakes with: {}", opponent, if defenders.is_empty() { "-" } else { &defenders }),
+        match gain {
+            gain if gain > 0 => format!("{} wins {} pawn{}", mover, gain, if gain == 1 { "" } else { "s" }),
+            0 => "Even trade".to_string(),
+            gain => format!("{} loses {} pawn{}: don't take", mover, -gain, if gain == -1 { "" } else { "s" }),
+        },
+    ])
+}
@@ -0,0 +1,65 @@
+use druid::widget::{Label, Painter};
+use druid::{Env, RenderContext, Widget, WidgetExt};
+use crate::app::AppState;
+use crate::engine::eval::evaluate;
+use crate::game::game_state::GameStatus;
+use crate::lessons::LESSONS;
+use crate::theme::{HIGHLIGHT_COLOR, PANEL_COLOR};
+
+/// Height of the status line above everything else
+const STATUS_HEIGHT: f64 = 30.0;
+
+/// The line above the board: whose move it is, or how the game ended, on a background that
+/// warns of check and marks a finished game
+pub fn status_bar() -> impl Widget<AppState> {
+    let background = Painter::new(|ctx, data: &AppState, env| {
+        let (state, _) = data.shown_position();
+        let color = if state.is_over() {
+            env.get(HIGHLIGHT_COLOR)
+        } else if state.status == GameStatus::Check {
+            data.highlight_palette.check().with_alpha(0.35)
+        } else {
+            env.get(PANEL_COLOR)
+        };
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &color);
+    });
+    Label::new(|data: &AppState, _env: &Env| status_text(data))
+        .with_text_size(20.0)
+        .padding((10.0, 0.0))
+        .align_left()
+        .fix_height(STATUS_HEIGHT)
+        .expand_width()
+        .background(background)
+}
+
+/// The status of the position shown, after what the board is up to: the lesson, the move
+/// browsed to, the line tried or the move typed, and then the opening and the evaluation
+fn status_text(data: &AppState) -> String {
+    let (state, board) = data.shown_position();
+    let view = &data.board_view;
+    let mut status_text = match data.lesson {
+        Some(index) => format!("Lesson {}/{} - {}: {}", index + 1, LESSONS.len(), LESSONS[index].title, LESSONS[index].instruction),
+        None => state.status_text(),
+    };
+    if let Some(ply) = data.viewed_ply {
+        status_text = format!("Move {}/{} (End returns to the game) - {}", ply, data.game_state.san_moves.len(), status_text);
+    }
+    if let Some(ply) = data.variation_start() {
+        status_text = format!("Trying a line from move {} (Esc returns to the game) - {}", ply, status_text);
+    }
+    if view.typed_rejected {
+        status_text = format!("{} is not a legal move - {}", view.typed, status_text);
+    } else if !view.typed.is_empty() {
+        status_text = format!("Move: {} (Enter plays it) - {}", view.typed, status_text);
+    } else if data.blindfold && !view.peeking {
+        status_text = format!("Blindfold (hold Space to peek, type moves in SAN) - {}", status_text);
+    }
+    if let Some(opening) = &state.opening {
+        status_text.push_str(&format!(" - {} {}", opening.eco, opening.name));
+    }
+    if data.show_eval {
+        status_text.push_str(&format!(" - Eval {:+.2}", evaluate(&board) as f64 / 100.0));
+    }
+    status_text
+}
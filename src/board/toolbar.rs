@@ -0,0 +1,67 @@
+use druid::widget::{Either, Flex, Label, LabelText};
+use druid::{Color, Env, Selector, Widget, WidgetExt};
+use crate::app::AppState;
+use crate::theme::PANEL_COLOR;
+use super::chess_board::ChessBoard;
+
+/// Runs a button of the toolbar under the board
+pub(super) const GAME_ACTION: Selector<GameAction> = Selector::new("chess_rust.game-action");
+
+/// Height of the row of game buttons at the bottom
+const TOOLBAR_HEIGHT: f64 = 34.0;
+
+/// Buttons of the toolbar under the board
+#[derive(Clone, Copy)]
+pub(super) enum GameAction {
+    Resign,
+    OfferDraw,
+    FlipBoard,
+    BackToGame, // Drops the line tried
+    KeepLine, // Keeps the line tried as a side line of the game
+}
+
+/// The buttons under the board. Resigning and offering a draw are up to the side to move while
+/// the game is going, and not to the computer; an offer from the opponent can be taken instead.
+/// While a line is tried they give way to the buttons that end it.
+pub(super) fn toolbar() -> impl Widget<AppState> {
+    let game = Flex::row()
+        .with_flex_child(button("Resign", GameAction::Resign).disabled_if(|data, _env| !playing(data)), 1.0)
+        .with_spacer(4.0)
+        .with_flex_child(
+            button(|data: &AppState, _env: &Env| draw_label(data).to_string(), GameAction::OfferDraw)
+                .disabled_if(|data, _env| !playing(data) || data.game_state.draw_offer == Some(data.game_state.current_turn)),
+            1.0,
+        )
+        .with_spacer(4.0)
+        .with_flex_child(button("Flip Board", GameAction::FlipBoard), 1.0);
+    let line = Flex::row()
+        .with_flex_child(button("Back to Game", GameAction::BackToGame), 1.0)
+        .with_spacer(4.0)
+        .with_flex_child(button("Keep Line", GameAction::KeepLine), 1.0)
+        .with_spacer(4.0)
+        .with_flex_child(button("Flip Board", GameAction::FlipBoard), 1.0);
+    Either::new(|data: &AppState, _env| data.variation.is_some(), line, game)
+        .padding((0.0, 4.0))
+        .fix_height(TOOLBAR_HEIGHT)
+}
+
+/// A flat button with its label centered, which runs `action` on the board
+fn button(label: impl Into<LabelText<AppState>>, action: GameAction) -> impl Widget<AppState> {
+    Label::new(label)
+        .with_text_size(14.0)
+        .center()
+        .background(PANEL_COLOR)
+        .border(Color::rgb8(150, 150, 150), 1.0)
+        .on_click(move |ctx, _data: &mut AppState, _env| ctx.submit_command(GAME_ACTION.with(action)))
+}
+
+/// Whether the side to move can resign or offer a draw: in a game going on, on its own turn and
+/// in the live position
+fn playing(data: &AppState) -> bool {
+    !data.game_state.is_over() && !ChessBoard::computer_to_move(data) && data.viewed_ply.is_none() && data.lesson.is_none()
+}
+
+/// A draw offered by the opponent is accepted with the button that offers one
+fn draw_label(data: &AppState) -> &'static str {
+    if data.game_state.draw_offer.is_some_and(|color| color != data.game_state.current_turn) { "Accept Draw" } else { "Offer Draw" }
+}
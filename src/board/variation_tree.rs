@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::{BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Selector, Size, UpdateCtx, Widget};
+use crate::app::AppState;
+use crate::game::fen::parse_fen;
+use crate::game::game_state::GameState;
+use crate::pieces::PieceColor;
+use crate::theme::{HIGHLIGHT_COLOR, MUTED_TEXT_COLOR};
+
+/// Shows the position after a number of moves of a line of the game: the mainline for None,
+/// otherwise the side line with that index
+pub const SHOW_LINE_MOVE: Selector<(Option<usize>, usize)> = Selector::new("chess_rust.show-line-move");
+
+const FONT_SIZE: f64 = 13.0;
+
+/// Height of a row of moves
+const ROW_HEIGHT: f64 = 20.0;
+
+/// How much further in a side line starts than the line it branches from
+const INDENT: f64 = 16.0;
+
+/// Space around the tree, and between the moves of a row
+const PADDING: f64 = 8.0;
+
+/// Something clickable in the tree
+enum Entry {
+    Move { line: Option<usize>, count: usize, text: String }, // Shows the position after `count` moves of `line`
+    Toggle { line: usize, text: String }, // Collapses or expands a side line
+}
+
+/// The moves of the game with its side lines below the moves they replace, indented by how
+/// deeply they are nested. A side line can be collapsed to a single row, and clicking a move
+/// shows the position after it.
+pub struct VariationTree {
+    collapsed: HashSet<usize>, // Indices of the side lines collapsed
+    entries: Vec<(Rect, Entry)>,
+}
+
+impl VariationTree {
+    pub fn new() -> Self {
+        Self { collapsed: HashSet::new(), entries: Vec::new() }
+    }
+}
+
+/// Lays the entries out in rows, wrapping those too long for the width
+struct Placer<'a> {
+    ctx: &'a mut LayoutCtx,
+    collapsed: &'a HashSet<usize>,
+    game: &'a GameState,
+    first_move: (u32, bool), // Number of the game's first move, and whether Black played it
+    width: f64,
+    entries: Vec<(Rect, Entry)>,
+    position: Point,
+}
+
+impl Placer<'_> {
+    /// Places the moves of `line` from where the last entry ended, each of its own side lines
+    /// on indented rows after the move it replaces. The first move of the line is the game's
+    /// move `first_ply`.
+    fn place_line(&mut self, line: Option<usize>, indent: usize, first_ply: usize) {
+        let game = self.game;
+        let moves = match line {
+            None => &game.san_moves,
+            Some(index) => &game.variations[index].moves,
+        };
+        let mut numbered = true;
+        for (i, san) in moves.iter().enumerate() {
+            let text = self.move_text(first_ply + i, san, numbered);
+            self.place(indent, Entry::Move { line, count: i + 1, text });
+            numbered = false;
+            for (child, side_line) in game.variations.iter().enumerate() {
+                if side_line.parent != line || side_line.ply != i {
+                    continue;
+                }
+                self.new_row(indent + 1);
+                if self.collapsed.contains(&child) {
+                    let text = format!("▸ {} moves", side_line.moves.len());
+                    self.place(indent + 1, Entry::Toggle { line: child, text });
+                } else {
+                    self.place(indent + 1, Entry::Toggle { line: child, text: "▾".to_string() });
+                    self.place_line(Some(child), indent + 1, first_ply + i);
+                }
+                self.new_row(indent);
+                numbered = true;
+            }
+        }
+    }
+
+    /// A move as written in the tree: with its number before White's moves, and before
+    /// Black's when it starts a row of its own
+    fn move_text(&self, ply: usize, san: &str, numbered: bool) -> String {
+        let (first_number, black_first) = self.first_move;
+        let half_moves = ply + black_first as usize;
+        let number = first_number as usize + half_moves / 2;
+        match half_moves % 2 {
+            0 => format!("{}. {}", number, san),
+            _ if numbered => format!("{}... {}", number, san),
+            _ => san.to_string(),
+        }
+    }
+
+    fn place(&mut self, indent: usize, entry: Entry) {
+        let text = match &entry {
+            Entry::Move { text, .. } | Entry::Toggle { text, .. } => text,
+        };
+        let layout = self.ctx.text().new_text_layout(text.clone())
+            .font(druid::FontFamily::SYSTEM_UI, FONT_SIZE)
+            .build()
+            .unwrap();
+        let width = layout.size().width + PADDING / 2.0;
+        let row_start = PADDING + indent as f64 * INDENT;
+        if self.position.x > row_start && self.position.x + width > self.width - PADDING {
+            self.new_row(indent);
+        }
+        let rect = Rect::from_origin_size(self.position, (width, ROW_HEIGHT));
+        self.position.x += width + PADDING / 2.0;
+        self.entries.push((rect, entry));
+    }
+
+    fn new_row(&mut self, indent: usize) {
+        self.position = Point::new(PADDING + indent as f64 * INDENT, self.position.y + ROW_HEIGHT);
+    }
+}
+
+impl Widget<AppState> for VariationTree {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AppState, _env: &Env) {
+        let Event::MouseDown(mouse_event) = event else { return };
+        let Some((_, entry)) = self.entries.iter().find(|(rect, _)| rect.contains(mouse_event.pos)) else { return };
+        match entry {
+            Entry::Move { line, count, .. } => ctx.submit_command(SHOW_LINE_MOVE.with((*line, *count))),
+            Entry::Toggle { line, .. } => {
+                if !self.collapsed.remove(line) {
+                    self.collapsed.insert(*line);
+                }
+                ctx.request_layout();
+            }
+        }
+        ctx.request_paint();
+        ctx.set_handled();
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppState, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        let (old_lines, lines) = (&old_data.root_game().variations, &data.root_game().variations);
+        // The indices collapsed only stay meaningful while side lines are added after the others
+        if lines.len() < old_lines.len() || old_lines.iter().zip(lines.iter()).any(|(old, new)| old != new) {
+            self.collapsed.clear();
+        }
+        ctx.request_layout();
+        ctx.request_paint();
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppState, _env: &Env) -> Size {
+        let game = data.root_game();
+        let first_move = game.start_fen.as_deref()
+            .and_then(|fen| parse_fen(fen).ok())
+            .map_or((1, false), |(state, _)| (state.fullmove_number, state.current_turn == PieceColor::Black));
+        let width = bc.max().width;
+        let mut placer = Placer {
+            ctx,
+            collapsed: &self.collapsed,
+            game,
+            first_move,
+            width,
+            entries: Vec::new(),
+            position: Point::new(PADDING, PADDING),
+        };
+        placer.place_line(None, 0, 0);
+        self.entries = placer.entries;
+        let height = self.entries.iter().map(|(rect, _)| rect.y1).fold(ROW_HEIGHT, f64::max) + PADDING;
+        bc.constrain((width, height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
+        let (text_color, muted_color) = (env.get(druid::theme::TEXT_COLOR), env.get(MUTED_TEXT_COLOR));
+
+        if self.entries.is_empty() {
+            let layout = ctx.text().new_text_layout("No moves yet")
+                .font(druid::FontFamily::SYSTEM_UI, FONT_SIZE)
+                .text_color(muted_color)
+                .build()
+                .unwrap();
+            ctx.draw_text(&layout, (PADDING, PADDING));
+            return;
+        }
+
+        // The move shown on the board, unless a line is being tried there
+        let shown = data.variation.is_none().then(|| data.viewed_ply.unwrap_or(data.game_state.san_moves.len()));
+        for (rect, entry) in &self.entries {
+            let (text, color) = match entry {
+                Entry::Move { line, count, text } => {
+                    if line.is_none() && Some(*count) == shown {
+                        ctx.fill(*rect, &env.get(HIGHLIGHT_COLOR));
+                    }
+                    (text, &text_color)
+                }
+                Entry::Toggle { text, .. } => (text, &muted_color),
+            };
+            let layout = ctx.text().new_text_layout(text.clone())
+                .font(druid::FontFamily::SYSTEM_UI, FONT_SIZE)
+                .text_color(*color)
+                .build()
+                .unwrap();
+            ctx.draw_text(&layout, (rect.x0 + PADDING / 4.0, rect.y0 + (ROW_HEIGHT - layout.size().height) / 2.0));
+        }
+    }
+}
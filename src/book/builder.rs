@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::game::fen::{parse_fen, STARTING_FEN};
+use crate::game::notation::parse_san;
+use crate::game::pgn::{parse_pgn_games, PgnGame};
+use crate::pieces::{PieceColor, PieceType};
+use super::polyglot::{encode_move, polyglot_key, ENTRY_SIZE};
+
+/// What went into a book built by `build_book`
+pub struct BookSummary {
+    pub games: usize,
+    pub skipped: Vec<String>, // Files and games that could not be read, with the reason
+    pub entries: usize,
+}
+
+/// Builds a Polyglot book from the games of the `.pgn` files in `folder`, taking the first
+/// `plies` moves of each. A move earns 2 points for each game its side won and 1 for each draw
+/// (or game without a result), the usual weighting, so that frequent and successful moves
+/// weigh most; moves that only ever lost are left out.
+pub fn build_book(folder: &Path, output: &Path, plies: usize) -> Result<BookSummary, String> {
+    let mut files: Vec<_> = std::fs::read_dir(folder)
+        .map_err(|e| format!("cannot read {}: {}", folder.display(), e))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pgn")))
+        .collect();
+    files.sort();
+
+    let mut points: HashMap<(u64, u16), u32> = HashMap::new();
+    let mut summary = BookSummary { games: 0, skipped: Vec::new(), entries: 0 };
+    for file in &files {
+        let games = std::fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_pgn_games(&text));
+        let games = match games {
+            Ok(games) => games,
+            Err(message) => {
+                summary.skipped.push(format!("{}: {}", file.display(), message));
+                continue;
+            }
+        };
+        for (i, game) in games.iter().enumerate() {
+            match game_moves(game, plies) {
+                Ok(moves) => {
+                    for (key, raw, earned) in moves {
+                        *points.entry((key, raw)).or_insert(0) += earned;
+                    }
+                    summary.games += 1;
+                }
+                Err(message) => summary.skipped.push(format!("{} game {}: {}", file.display(), i + 1, message)),
+            }
+        }
+    }
+
+    // Books are sorted by key so that they can be searched, heaviest move first within a key
+    let mut entries: Vec<(u64, u16, u32)> = points.into_iter()
+        .filter(|&(_, points)| points > 0)
+        .map(|((key, raw), points)| (key, raw, points))
+        .collect();
+    entries.sort_by_key(|&(key, raw, points)| (key, std::cmp::Reverse(points), raw));
+    // Weights are 16-bit, so a large corpus is scaled down, keeping every move at least 1
+    let heaviest = entries.iter().map(|&(_, _, points)| points).max().unwrap_or(0);
+    let scale = (heaviest as f64 / u16::MAX as f64).max(1.0);
+
+    let mut data = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for &(key, raw, points) in &entries {
+        let weight = ((points as f64 / scale).round() as u16).max(1);
+        data.extend_from_slice(&key.to_be_bytes());
+        data.extend_from_slice(&raw.to_be_bytes());
+        data.extend_from_slice(&weight.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes()); // Learning data, unused
+    }
+    std::fs::write(output, data).map_err(|e| format!("cannot write {}: {}", output.display(), e))?;
+    summary.entries = entries.len();
+    Ok(summary)
+}
+
+/// The first `plies` moves of `game` as the key of their position, the move as books store it
+/// and the points it earned
+fn game_moves(game: &PgnGame, plies: usize) -> Result<Vec<(u64, u16, u32)>, String> {
+    let winner = match game.result.as_deref().or(game.tag("Result")) {
+        Some("1-0") => Some(PieceColor::White),
+        Some("0-1") => Some(PieceColor::Black),
+        _ => None,
+    };
+    let start = game.tag("FEN").unwrap_or(STARTING_FEN);
+    let (mut state, mut board) = parse_fen(start).map_err(|e| format!("invalid FEN tag: {}", e))?;
+    let mut moves = Vec::new();
+
+    for san in game.moves.iter().take(plies) {
+        let (from, to, promotion) = parse_san(&state, &board, san).ok_or_else(|| format!("illegal move '{}'", san))?;
+        let is_promotion = matches!(board[from.0 * 8 + from.1], Some(p) if p.piece_type == PieceType::Pawn) && (to.0 == 0 || to.0 == 7);
+        let raw = encode_move(from, to, is_promotion.then_some(promotion), &board);
+        let earned = match winner {
+            Some(color) if color == state.current_turn => 2,
+            Some(_) => 0,
+            None => 1,
+        };
+        moves.push((polyglot_key(&state, &board), raw, earned));
+        if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+            return Err(format!("could not play '{}'", san));
+        }
+    }
+    Ok(moves)
+}
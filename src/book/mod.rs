@@ -0,0 +1,3 @@
+pub mod builder;
+pub mod polyglot;
+mod polyglot_random;
\ No newline at end of file
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::path::Path;
+use memmap2::Mmap;
+use crate::game::game_state::GameState;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::polyglot_random::POLYGLOT_RANDOM;
+
+pub const ENTRY_SIZE: usize = 16;
+const CASTLE_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+/// A move found in the book, already translated to board coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<PieceType>,
+    pub weight: u16,
+}
+
+/// A Polyglot `.bin` opening book, memory-mapped so large books are not read up front
+pub struct PolyglotBook {
+    data: Mmap,
+}
+
+impl PolyglotBook {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+        // SAFETY: the book is only read, and a file truncated underneath us is a user error
+        let data = unsafe { Mmap::map(&file) }.map_err(|e| format!("cannot map {}: {}", path.display(), e))?;
+        if data.len() % ENTRY_SIZE != 0 {
+            return Err(format!("{} is not a Polyglot book", path.display()));
+        }
+        Ok(Self { data })
+    }
+
+    fn entry_count(&self) -> usize {
+        self.data.len() / ENTRY_SIZE
+    }
+
+    fn entry_key(&self, index: usize) -> u64 {
+        let offset = index * ENTRY_SIZE;
+        u64::from_be_bytes(self.data[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// The legal book moves for the position, heaviest first
+    pub fn moves(&self, state: &GameState, board: &Vec<Option<Piece>>) -> Vec<BookMove> {
+        let key = polyglot_key(state, board);
+
+        // Entries are sorted by key, so find the first one with a matching key
+        let (mut low, mut high) = (0, self.entry_count());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.entry_key(mid) < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let mut moves = Vec::new();
+        for index in low..self.entry_count() {
+            if self.entry_key(index) != key {
+                break;
+            }
+            let offset = index * ENTRY_SIZE;
+            let raw = u16::from_be_bytes([self.data[offset + 8], self.data[offset + 9]]);
+            let weight = u16::from_be_bytes([self.data[offset + 10], self.data[offset + 11]]);
+            let book_move = decode_move(raw, weight, board);
+            // A hash collision or a broken book can still produce nonsense, so check legality
+            if state.is_valid_move(book_move.from, book_move.to, board) {
+                moves.push(book_move);
+            }
+        }
+        moves.sort_by_key(|m| std::cmp::Reverse(m.weight));
+        moves
+    }
+}
+
+/// The Polyglot hash of a position, as used to look it up in `.bin` books
+pub fn polyglot_key(state: &GameState, board: &[Option<Piece>]) -> u64 {
+    let mut key = 0;
+
+    for (i, square) in board.iter().enumerate() {
+        if let Some(piece) = square {
+            let kind = match piece.piece_type {
+                PieceType::Pawn => 0,
+                PieceType::Knight => 2,
+                PieceType::Bishop => 4,
+                PieceType::Rook => 6,
+                PieceType::Queen => 8,
+                PieceType::King => 10,
+            } + if piece.color == PieceColor::White { 1 } else { 0 };
+            // Polyglot counts ranks from the first, our rows start at the eighth
+            let (row, col) = (i / 8, i % 8);
+            key ^= POLYGLOT_RANDOM[64 * kind + 8 * (7 - row) + col];
+        }
+    }
+
+    let castling = [
+        state.white_can_castle_kingside,
+        state.white_can_castle_queenside,
+        state.black_can_castle_kingside,
+        state.black_can_castle_queenside,
+    ];
+    for (i, &allowed) in castling.iter().enumerate() {
+        if allowed {
+            key ^= POLYGLOT_RANDOM[CASTLE_OFFSET + i];
+        }
+    }
+
+    // The en passant file only counts when a pawn of the side to move can actually capture
+    if let Some((row, col)) = state.en_passant {
+        // Next to the pawn that moved two squares, which stands just past the square
+        let pawn_row = if row == 2 { 3 } else { 4 };
+        let can_capture = [col.wrapping_sub(1), col + 1].iter().any(|&c| {
+            c < 8 && matches!(board[pawn_row * 8 + c],
+                Some(p) if p.piece_type == PieceType::Pawn && p.color == state.current_turn)
+        });
+        if can_capture {
+            key ^= POLYGLOT_RANDOM[EN_PASSANT_OFFSET + col];
+        }
+    }
+
+    if state.current_turn == PieceColor::White {
+        key ^= POLYGLOT_RANDOM[TURN_OFFSET];
+    }
+    key
+}
+
+/// Packs a move the way books store it, the inverse of `decode_move`
+pub fn encode_move(from: (usize, usize), to: (usize, usize), promotion: Option<PieceType>, board: &[Option<Piece>]) -> u16 {
+    let promotion = match promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    // Castling is stored as the king capturing its own rook
+    let is_king = matches!(board[from.0 * 8 + from.1], Some(p) if p.piece_type == PieceType::King);
+    let to_col = match to.1 {
+        6 if is_king && from.1 == 4 => 7,
+        2 if is_king && from.1 == 4 => 0,
+        col => col,
+    };
+    (promotion << 12 | (7 - from.0) << 9 | from.1 << 6 | (7 - to.0) << 3 | to_col) as u16
+}
+
+fn decode_move(raw: u16, weight: u16, board: &[Option<Piece>]) -> BookMove {
+    let field = |shift: u16| (raw >> shift & 7) as usize;
+    let from = (7 - field(9), field(6));
+    let mut to = (7 - field(3), field(0));
+    let promotion = match field(12) {
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => None,
+    };
+
+    // Castling is stored as the king capturing its own rook
+    let is_king = matches!(board[from.0 * 8 + from.1], Some(p) if p.piece_type == PieceType::King);
+    if is_king && from.1 == 4 && from.0 == to.0 {
+        match to.1 {
+            7 => to.1 = 6,
+            0 => to.1 = 2,
+            _ => {}
+        }
+    }
+
+    BookMove { from, to, promotion, weight }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fen::{parse_fen, STARTING_FEN};
+    use crate::game::notation::parse_uci_move;
+
+    /// The key after playing `moves`, given in UCI notation, from the standard position
+    fn key_after(moves: &str) -> u64 {
+        let (mut state, mut board) = parse_fen(STARTING_FEN).unwrap();
+        for uci in moves.split_whitespace() {
+            let (from, to, promotion) = parse_uci_move(&state, &board, uci).unwrap();
+            assert!(state.make_move_with_promotion(from, to, promotion, &mut board), "{} is illegal", uci);
+        }
+        polyglot_key(&state, &board)
+    }
+
+    /// The reference keys published with the Polyglot book format
+    #[test]
+    fn reference_keys() {
+        assert_eq!(key_after(""), 0x463b96181691fc9c);
+        assert_eq!(key_after("e2e4"), 0x823c9b50fd114196);
+        assert_eq!(key_after("e2e4 e7e5"), 0x0844931a6ef4b9a0);
+        assert_eq!(key_after("e2e4 d7d5"), 0x0756b94461c50fb0);
+        assert_eq!(key_after("e2e4 d7d5 e4e5"), 0x662fafb965db29d4);
+        // The en passant file counts once a pawn can take on it
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5"), 0x22a48b5a8e47ff78);
+        // Castling rights are lost, one side after the other
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5 e1e2"), 0x652a607ca3f242c1);
+        assert_eq!(key_after("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7"), 0x00fdd303c946bdd9);
+        assert_eq!(key_after("a2a4 b7b5 h2h4 b5b4 c2c4"), 0x3c8123ea7b067637);
+        assert_eq!(key_after("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3"), 0x5c3f9b829b279560);
+    }
+}
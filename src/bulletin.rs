@@ -0,0 +1,146 @@
+use std::path::Path;
+use crate::game::pgn::PgnGame;
+use crate::pieces::{Piece, PieceColor};
+
+const STYLE: &str = "<style>
+body { font-family: Georgia, serif; max-width: 800px; margin: 2em auto; }
+table.results { border-collapse: collapse; margin-bottom: 2em; }
+table.results th, table.results td { border: 1px solid #999; padding: 4px 10px; text-align: left; }
+.game { page-break-before: always; }
+.details { color: #555; }
+.moves { line-height: 1.6; }
+.comment { font-style: italic; color: #444; }
+figure { display: inline-block; margin: 0.5em 1em; text-align: center; }
+table.board { border-collapse: collapse; border: 2px solid #333; }
+table.board td { width: 32px; height: 32px; padding: 0; text-align: center; font-size: 26px; }
+td.light { background: #eee; }
+td.dark { background: #aaa; }
+@media print { body { margin: 0; } }
+</style>
+";
+
+/// Writes the games to `path` as one HTML bulletin, ready to print from a browser: a table of
+/// the results, then every game with its header, moves and diagrams
+pub fn export_bulletin(path: &Path, games: &[&PgnGame]) -> Result<(), String> {
+    let html = bulletin_html(games)?;
+    std::fs::write(path, html).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// Diagrams are drawn after each move with an annotation glyph, the moments the annotator
+/// singled out, and after the last move
+fn bulletin_html(games: &[&PgnGame]) -> Result<String, String> {
+    let title = games.first().and_then(|game| known_tag(game, "Event")).unwrap_or("Games bulletin");
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n{}</head>\n<body>\n", escape(title), STYLE));
+    html.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+
+    html.push_str("<table class=\"results\">\n<tr><th>#</th><th>White</th><th>Black</th><th>Result</th><th>Round</th></tr>\n");
+    for (i, game) in games.iter().enumerate() {
+        html.push_str(&format!(
+            "<tr><td><a href=\"#game-{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            i + 1,
+            i + 1,
+            escape(known_tag(game, "White").unwrap_or("?")),
+            escape(known_tag(game, "Black").unwrap_or("?")),
+            escape(result(game)),
+            escape(known_tag(game, "Round").unwrap_or("")),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    for (i, game) in games.iter().enumerate() {
+        let positions = game.replay_positions().map_err(|e| format!("game {}: {}", i + 1, e))?;
+        html.push_str(&format!("<section class=\"game\" id=\"game-{}\">\n", i + 1));
+        html.push_str(&format!(
+            "<h2>{}. {} - {}, {}</h2>\n",
+            i + 1,
+            escape(known_tag(game, "White").unwrap_or("?")),
+            escape(known_tag(game, "Black").unwrap_or("?")),
+            escape(result(game)),
+        ));
+        let opening = match (known_tag(game, "ECO"), known_tag(game, "Opening")) {
+            (Some(eco), Some(name)) => Some(format!("{} {}", eco, name)),
+            (eco, name) => eco.or(name).map(str::to_string),
+        };
+        let details: Vec<String> = [known_tag(game, "Site"), known_tag(game, "Date"), known_tag(game, "Round")]
+            .into_iter()
+            .flatten()
+            .map(str::to_string)
+            .chain(opening)
+            .collect();
+        if !details.is_empty() {
+            html.push_str(&format!("<p class=\"details\">{}</p>\n", escape(&details.join(" · "))));
+        }
+
+        html.push_str("<p class=\"moves\">\n");
+        let mut resumed = true; // At the start of the moves or after a diagram
+        for (j, san) in game.moves.iter().enumerate() {
+            // Number White's moves, and Black's when the moves resume with one
+            let (state, _) = &positions[j];
+            let number = if state.current_turn == PieceColor::White {
+                format!("{}.", state.fullmove_number)
+            } else {
+                format!("{}...", state.fullmove_number)
+            };
+            if state.current_turn == PieceColor::White || resumed {
+                html.push_str(&format!("<b>{}</b> ", number));
+            }
+            resumed = false;
+            let quality = game.qualities.get(j).copied().flatten();
+            html.push_str(&escape(san));
+            if let Some(quality) = quality {
+                html.push_str(quality.glyph());
+            }
+            html.push('\n');
+            if let Some(comment) = game.comments.get(j).cloned().flatten() {
+                html.push_str(&format!("<span class=\"comment\">{}</span>\n", escape(&comment)));
+            }
+            if quality.is_some() && j + 1 < game.moves.len() {
+                html.push_str("</p>\n");
+                html.push_str(&diagram(&positions[j + 1].1, &format!("After {} {}", number, san)));
+                html.push_str("<p class=\"moves\">\n");
+                resumed = true;
+            }
+        }
+        html.push_str(&format!("<b>{}</b>\n</p>\n", escape(result(game))));
+
+        let (_, final_board) = positions.last().unwrap();
+        html.push_str(&diagram(final_board, "Final position"));
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+/// The board as a table of Unicode pieces, White at the bottom
+fn diagram(board: &[Option<Piece>], caption: &str) -> String {
+    let mut html = String::from("<figure>\n<table class=\"board\">\n");
+    for row in 0..8 {
+        html.push_str("<tr>");
+        for col in 0..8 {
+            let class = if (row + col) % 2 == 0 { "light" } else { "dark" };
+            let piece = board[row * 8 + col].map(|piece| piece.figurine()).unwrap_or(' ');
+            html.push_str(&format!("<td class=\"{}\">{}</td>", class, piece));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str(&format!("</table>\n<figcaption>{}</figcaption>\n</figure>\n", escape(caption)));
+    html
+}
+
+/// A tag with a value other than the PGN placeholders for unknown
+fn known_tag<'a>(game: &'a PgnGame, name: &str) -> Option<&'a str> {
+    game.tag(name).filter(|value| !matches!(*value, "" | "?" | "????.??.??"))
+}
+
+fn result(game: &PgnGame) -> &str {
+    match game.result.as_deref().or(game.tag("Result")).unwrap_or("*") {
+        "1/2-1/2" => "½-½",
+        result => result,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
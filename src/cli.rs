@@ -0,0 +1,241 @@
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::book::builder::build_book;
+use crate::engine::engine_match::{run_match, MatchPlayer, MatchSettings};
+use crate::engine::external::load_engines;
+use crate::engine::review::review_games;
+use crate::engine::search::count_nodes;
+use crate::engine::tablebase::Tablebase;
+use crate::game::epd::parse_epd_file;
+use crate::game::fen::{parse_fen, STARTING_FEN};
+use crate::game::perft::divide;
+use crate::game::pgn::parse_pgn_games;
+
+/// Positions searched by `bench`: openings, middlegames and endgames, with checks, captures,
+/// castling, en passant and promotions all on the board somewhere
+const BENCH_POSITIONS: [&str; 8] = [
+    STARTING_FEN,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1Q1PPP/R3KB1R w KQ - 0 9",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/4k3/8/2pP4/8/4K3/8 b - d3 0 1",
+];
+
+/// Search depth of `bench` unless given
+const BENCH_DEPTH: u32 = 4;
+
+/// Plies of each game that `book` takes unless told otherwise
+const BOOK_PLIES: usize = 20;
+
+/// Search depth of the built-in engine in `match` unless given as `builtin:<depth>`
+const MATCH_DEPTH: u32 = 4;
+
+/// Runs a command given on the command line instead of opening the window, e.g.
+/// `chess_rust perft 4 "<fen>"`. Returns false when there is no command to run.
+pub fn run(args: &[String]) -> bool {
+    let result = match args.first().map(String::as_str) {
+        Some("perft") => perft(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("book") => book(&args[1..]),
+        Some("match") => engine_match(&args[1..]),
+        Some("analyse") => analyse_games(&args[1..]),
+        _ => return false,
+    };
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+    true
+}
+
+/// `perft <depth> [fen]`: node counts of each move's subtree and their total, to check the move
+/// generator against published results, e.g. 8902 at depth 3 from the start position
+fn perft(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess_rust perft <depth> [fen]";
+    let depth: u32 = args.first().and_then(|depth| depth.parse().ok()).ok_or(usage)?;
+    // The FEN may be given as one argument or unquoted, as several
+    let fen = if args.len() > 1 { args[1..].join(" ") } else { STARTING_FEN.to_string() };
+    let (state, board) = parse_fen(&fen).map_err(|e| format!("Invalid FEN: {}", e))?;
+
+    let started = Instant::now();
+    let moves = divide(&state, &board, depth);
+    for (uci, nodes) in &moves {
+        println!("{}: {}", uci, nodes);
+    }
+    let nodes: u64 = if depth == 0 { 1 } else { moves.iter().map(|(_, nodes)| nodes).sum() };
+    let seconds = started.elapsed().as_secs_f64();
+    println!();
+    println!("Moves: {}", moves.len());
+    println!("Nodes: {}", nodes);
+    println!("Time: {:.2} s ({:.0} nodes/s)", seconds, nodes as f64 / seconds.max(1e-9));
+    Ok(())
+}
+
+/// `bench [depth]`: searches a fixed set of positions to a fixed depth on one thread. The node
+/// count only changes with the search or the move generator, and the speed shows regressions.
+fn bench(args: &[String]) -> Result<(), String> {
+    let depth = match args.first() {
+        Some(depth) => depth.parse().map_err(|_| "usage: chess_rust bench [depth]")?,
+        None => BENCH_DEPTH,
+    };
+
+    let started = Instant::now();
+    let mut total = 0;
+    for fen in BENCH_POSITIONS {
+        let (state, board) = parse_fen(fen).map_err(|e| format!("Invalid FEN {}: {}", fen, e))?;
+        let position_started = Instant::now();
+        let nodes = count_nodes(&state, &board, depth, 16);
+        println!("{:>10} nodes {:>8.2} s  {}", nodes, position_started.elapsed().as_secs_f64(), fen);
+        total += nodes;
+    }
+    let seconds = started.elapsed().as_secs_f64();
+    println!();
+    println!("Nodes: {}", total);
+    println!("Time: {:.2} s", seconds);
+    println!("Nodes/second: {:.0}", total as f64 / seconds.max(1e-9));
+    Ok(())
+}
+
+/// `book <pgn folder> <book.bin> [plies]`: builds a Polyglot opening book from the games of the
+/// PGN files in a folder, for the engines or the book move hint
+fn book(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess_rust book <pgn folder> <book.bin> [plies]";
+    let (folder, output) = match args {
+        [folder, output, ..] => (Path::new(folder), Path::new(output)),
+        _ => return Err(usage.to_string()),
+    };
+    let plies = match args.get(2) {
+        Some(plies) => plies.parse().map_err(|_| usage)?,
+        None => BOOK_PLIES,
+    };
+
+    let summary = build_book(folder, output, plies)?;
+    for skipped in &summary.skipped {
+        eprintln!("Skipped {}", skipped);
+    }
+    println!("{} games, {} book entries written to {}", summary.games, summary.entries, output.display());
+    Ok(())
+}
+
+/// `match <engine> <engine> [options]`: plays two engines against each other with alternating
+/// colors, printing each result and the score of the first engine. An engine is `builtin`,
+/// `builtin:<depth>` or the name of one registered in the engine manager. Options:
+/// `--games <n>`, `--movetime <ms>`, `--openings <epd file>`, `--pgn <file>` to save the games,
+/// `--resign <cp> <moves>` and `--draw <cp> <moves> <from move>` to adjudicate, 0 moves to never,
+/// `--syzygy <folder>` to end games by the tablebase once it covers the position, and `--ponder`
+/// to let the engines think on their opponent's time.
+fn engine_match(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess_rust match <engine> <engine> [--games <n>] [--movetime <ms>] [--openings <epd file>] [--pgn <file>] [--resign <cp> <moves>] [--draw <cp> <moves> <from move>] [--syzygy <folder>] [--ponder]";
+    let [first, second, options @ ..] = args else { return Err(usage.to_string()) };
+    let mut settings = MatchSettings::new();
+    let mut pgn_path = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(usage);
+        let number = |value: &String| value.parse::<u32>().map_err(|_| usage);
+        match option.as_str() {
+            "--games" => settings.games = number(value()?)?,
+            "--movetime" => settings.movetime = Duration::from_millis(number(value()?)? as u64),
+            "--openings" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+                let records = parse_epd_file(&text).map_err(|e| format!("{}: {}", path, e))?;
+                settings.openings = records.into_iter().map(|record| (record.state, record.board)).collect();
+            }
+            "--pgn" => pgn_path = Some(value()?.clone()),
+            "--resign" => {
+                settings.resign_score = number(value()?)? as i32;
+                settings.resign_moves = number(value()?)?;
+            }
+            "--draw" => {
+                settings.draw_score = number(value()?)? as i32;
+                settings.draw_moves = number(value()?)?;
+                settings.draw_after = number(value()?)?;
+            }
+            "--syzygy" => settings.tablebase = Some(Tablebase::open(value()?)?),
+            "--ponder" => settings.ponder = true,
+            _ => return Err(usage.to_string()),
+        }
+    }
+
+    let mut first = match_player(first)?;
+    let mut second = match_player(second)?;
+    let mut pgn_file = match &pgn_path {
+        Some(path) => Some(std::fs::File::create(path).map_err(|e| format!("cannot write {}: {}", path, e))?),
+        None => None,
+    };
+    let mut write_error = None;
+    println!("{} vs {}, {} games", first.name(), second.name(), settings.games);
+    let score = run_match(&mut first, &mut second, &settings, |round, game| {
+        println!("Game {}: {} - {} {} ({})", round, game.tag("White").unwrap_or("?"), game.tag("Black").unwrap_or("?"),
+            game.result.as_deref().unwrap_or("*"), game.tag("Termination").unwrap_or("?"));
+        // Written as the match goes, so that a long match that is stopped keeps its games
+        if let Some(file) = &mut pgn_file {
+            if let Err(e) = writeln!(file, "{}", game.to_pgn()) {
+                write_error.get_or_insert(e.to_string());
+            }
+        }
+    })?;
+    println!("Score of {} vs {}: +{} -{} ={}", first.name(), second.name(), score.wins, score.losses, score.draws);
+    match (write_error, pgn_path) {
+        (Some(e), Some(path)) => Err(format!("cannot write {}: {}", path, e)),
+        _ => Ok(()),
+    }
+}
+
+/// The built-in engine, or a registered engine started with its options
+fn match_player(name: &str) -> Result<MatchPlayer, String> {
+    if name == "builtin" {
+        return Ok(MatchPlayer::Builtin { depth: MATCH_DEPTH });
+    }
+    if let Some(depth) = name.strip_prefix("builtin:") {
+        let depth = depth.parse().map_err(|_| format!("invalid depth in {}", name))?;
+        return Ok(MatchPlayer::Builtin { depth });
+    }
+    let engines = load_engines()?;
+    let config = engines.iter()
+        .find(|config| config.name == name)
+        .ok_or_else(|| format!("no engine named {} is registered", name))?;
+    Ok(MatchPlayer::External(config.start()?))
+}
+
+/// `analyse <games.pgn> <output.pgn> [--threads <n>]`: reviews every game of a PGN file as the
+/// app reviews a finished game, several games at once on as many threads as there are cores
+/// unless told otherwise, and writes them with the quality of each move and the evaluation after it
+fn analyse_games(args: &[String]) -> Result<(), String> {
+    let usage = "usage: chess_rust analyse <games.pgn> <output.pgn> [--threads <n>]";
+    let (input, output, threads) = match args {
+        [input, output] => (input, output, thread::available_parallelism().map_or(1, |cores| cores.get())),
+        [input, output, option, threads] if option == "--threads" => (input, output, threads.parse().map_err(|_| usage)?),
+        _ => return Err(usage.to_string()),
+    };
+    let text = std::fs::read_to_string(input).map_err(|e| format!("cannot read {}: {}", input, e))?;
+    let games = parse_pgn_games(&text).map_err(|e| format!("{}: {}", input, e))?;
+
+    let started = Instant::now();
+    let reviewed = review_games(&games, threads, |done, moves| {
+        let seconds = started.elapsed().as_secs_f64();
+        print!("\rReviewed {}/{} games, {} moves ({:.1} moves/s)", done, games.len(), moves, moves as f64 / seconds.max(1e-9));
+        let _ = std::io::stdout().flush();
+    });
+    println!();
+
+    let mut pgn = String::new();
+    for (i, game) in reviewed.iter().enumerate() {
+        match game {
+            Ok(game) => {
+                pgn.push_str(&game.to_pgn());
+                pgn.push('\n');
+            }
+            Err(e) => eprintln!("Skipped game {}: {}", i + 1, e),
+        }
+    }
+    std::fs::write(output, pgn).map_err(|e| format!("cannot write {}: {}", output, e))?;
+    println!("{} games reviewed on {} threads in {:.1} s, written to {}", reviewed.iter().filter(|game| game.is_ok()).count(), threads, started.elapsed().as_secs_f64(), output);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+use druid::{commands, AppDelegate, Command, DelegateCtx, Env, Handled, Target, WindowId};
+use crate::analysis_board::{analysis_position, analysis_window};
+use crate::app::AppState;
+use crate::dialog::{error_dialog, new_game_dialog, settings_dialog};
+use crate::menu::{open_options, save_options, ANALYZE_POSITION, OPEN_FILE, OPEN_GAME, SAVE_FILE, SAVE_GAME, SHOW_NEW_GAME, SHOW_SETTINGS, TOGGLE_BLINDFOLD, TOGGLE_LOW_VISION, TOGGLE_THREATS, TOGGLE_VARIATION_TREE};
+use crate::settings::{save_settings, Settings};
+
+/// Handles the menu commands that do not need the board, showing the file panels for those
+/// that do; the others go on to the board
+pub struct Delegate {
+    last_directory: Option<PathBuf>, // Where a game was last opened or saved, for the next panel
+    analysis_window: Option<WindowId>, // The analysis board, while it is open
+}
+
+impl Delegate {
+    pub fn new() -> Self {
+        Self { last_directory: None, analysis_window: None }
+    }
+}
+
+impl AppDelegate<AppState> for Delegate {
+    fn command(&mut self, ctx: &mut DelegateCtx, target: Target, cmd: &Command, data: &mut AppState, _env: &Env) -> Handled {
+        if cmd.is(SHOW_NEW_GAME) {
+            ctx.new_window(new_game_dialog());
+            Handled::Yes
+        } else if cmd.is(SHOW_SETTINGS) {
+            ctx.new_window(settings_dialog());
+            Handled::Yes
+        } else if cmd.is(OPEN_FILE) {
+            // The panels belong to the window of the menu; the file picked goes to its board
+            ctx.submit_command(commands::SHOW_OPEN_PANEL.with(open_options(self.last_directory.as_deref())).to(target));
+            Handled::Yes
+        } else if cmd.is(SAVE_FILE) {
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(save_options(self.last_directory.as_deref())).to(target));
+            Handled::Yes
+        } else if cmd.is(ANALYZE_POSITION) {
+            // An analysis board already open starts over from the position shown
+            match analysis_position(data) {
+                Ok(game) => data.analysis_board = Some(game),
+                Err(message) => {
+                    ctx.new_window(error_dialog("Cannot analyze the position", &message));
+                    return Handled::Yes;
+                }
+            }
+            match self.analysis_window {
+                Some(id) => ctx.submit_command(commands::SHOW_WINDOW.to(id)),
+                None => {
+                    let window = analysis_window();
+                    self.analysis_window = Some(window.id);
+                    ctx.new_window(window);
+                }
+            }
+            Handled::Yes
+        } else if cmd.is(TOGGLE_VARIATION_TREE) {
+            data.show_variation_tree = !data.show_variation_tree;
+            Handled::Yes
+        } else if cmd.is(TOGGLE_BLINDFOLD) {
+            data.blindfold = !data.blindfold;
+            Handled::Yes
+        } else if cmd.is(TOGGLE_THREATS) {
+            data.show_threats = !data.show_threats;
+            Handled::Yes
+        } else if cmd.is(TOGGLE_LOW_VISION) {
+            data.low_vision = !data.low_vision;
+            Handled::Yes
+        } else {
+            // Note the folder of a file picked, then let the board open or save it
+            if let Some(file) = cmd.get(OPEN_GAME).or_else(|| cmd.get(SAVE_GAME)) {
+                self.last_directory = file.path().parent().map(|directory| directory.to_path_buf());
+            }
+            Handled::No
+        }
+    }
+
+    /// Saves the settings whenever a window closes: the settings and new game dialogs after
+    /// their changes, and the main window when the app quits
+    fn window_removed(&mut self, id: WindowId, data: &mut AppState, _env: &Env, ctx: &mut DelegateCtx) {
+        if self.analysis_window == Some(id) {
+            self.analysis_window = None;
+            data.analysis_board = None;
+        }
+        if let Err(message) = save_settings(&Settings::from_state(data)) {
+            ctx.new_window(error_dialog("Settings not saved", &message));
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use druid::widget::{Button, Checkbox, CrossAxisAlignment, Flex, Label, LineBreaking, Radio, RadioGroup, Scroll, Slider, TextBox};
+use druid::{commands, Lens, LensExt, Selector, Target, Widget, WidgetExt, WindowDesc};
+use crate::app::AppState;
+use crate::board::diagram::DiagramOptions;
+use crate::board::piece_set::available_sets;
+use crate::board::palette::{AnimationSpeed, BoardTheme, CustomColors, HighlightPalette, MoveIndicator};
+use crate::game::annotation::MarkColor;
+use crate::engine::external::{save_engines, EngineConfig};
+use crate::engine::{BookVariety, ComputerOptions, Level, Opponent};
+use crate::game::clock::TimeControl;
+use crate::game::start::StartPosition;
+use crate::game::pgn::PgnGame;
+use crate::pieces::PieceColor;
+use crate::theme::{themed, Appearance};
+
+/// Sent by the New game dialog to start the game set up in it
+pub const NEW_GAME: Selector = Selector::new("chess_rust.new-game");
+
+/// Sent by the game list with the index of the game to open
+pub const OPEN_LISTED_GAME: Selector<usize> = Selector::new("chess_rust.open-listed-game");
+
+/// Sent by the game list to print the games picked for the bulletin
+pub const PRINT_BULLETIN: Selector = Selector::new("chess_rust.print-bulletin");
+
+/// Sent by the History panel with the index of the snapshot to restore
+pub const RESTORE_SNAPSHOT: Selector<usize> = Selector::new("chess_rust.restore-snapshot");
+
+/// Choices of the opponent radio buttons
+const OPPONENTS: [(&str, Opponent); 5] = [
+    ("Random mover", Opponent::Random),
+    ("Human-like", Opponent::HumanLike),
+    ("Search", Opponent::Negamax),
+    ("Monte Carlo", Opponent::MonteCarlo),
+    ("External UCI engine", Opponent::External),
+];
+
+/// A small window showing an error message with an OK button that closes it
+pub fn error_dialog(title: &str, message: &str) -> WindowDesc<AppState> {
+    message_dialog(title, message)
+}
+
+/// A small window showing a message with an OK button that closes it
+pub fn message_dialog(title: &str, message: &str) -> WindowDesc<AppState> {
+    let content = Flex::column()
+        .with_child(Label::new(message).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(10.0)
+        .with_child(Button::new("OK").on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+        }))
+        .padding(15.0);
+
+    WindowDesc::new(themed(content))
+        .title(title)
+        .window_size((360.0, 160.0))
+        .resizable(false)
+}
+
+/// Display and accessibility settings; changes apply to the board immediately
+pub fn settings_dialog() -> WindowDesc<AppState> {
+    let palettes = HighlightPalette::ALL.iter().map(|&palette| (palette.name(), palette));
+    // The sets found when the settings are opened, after the pieces drawn by the app itself
+    let piece_sets = std::iter::once(("Built-in".to_string(), String::new()))
+        .chain(available_sets().into_iter().map(|name| (name.clone(), name)));
+    let content = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new("Appearance"))
+        .with_child(RadioGroup::row(Appearance::ALL.map(|appearance| (appearance.name(), appearance))).lens(AppState::appearance))
+        .with_child(Checkbox::new("Low-vision mode").lens(AppState::low_vision))
+        .with_spacer(10.0)
+        .with_child(Label::new("Board colors"))
+        .with_child(RadioGroup::row(BoardTheme::ALL.map(|theme| (theme.name(), theme))).lens(AppState::board_theme))
+        .with_child(Flex::row()
+            .with_child(Label::new("Custom light"))
+            .with_child(TextBox::new().with_placeholder("#RRGGBB").fix_width(90.0).lens(AppState::custom_colors.then(CustomColors::light)))
+            .with_child(Label::new("dark"))
+            .with_child(TextBox::new().with_placeholder("#RRGGBB").fix_width(90.0).lens(AppState::custom_colors.then(CustomColors::dark))))
+        .with_child(Label::new("Pieces"))
+        .with_child(RadioGroup::column(piece_sets).lens(AppState::piece_set))
+        .with_child(Checkbox::new("Draw the built-in pieces with Unicode chess symbols").lens(AppState::glyph_pieces))
+        .with_child(Label::new("Highlight colors"))
+        .with_child(RadioGroup::column(palettes).lens(AppState::highlight_palette))
+        .with_child(Flex::row()
+            .with_child(Label::new("Custom selection"))
+            .with_child(TextBox::new().with_placeholder("#RRGGBB").fix_width(90.0).lens(AppState::custom_colors.then(CustomColors::selection)))
+            .with_child(Label::new("last move"))
+            .with_child(TextBox::new().with_placeholder("#RRGGBB").fix_width(90.0).lens(AppState::custom_colors.then(CustomColors::last_move))))
+        .with_child(Label::new("Possible moves"))
+        .with_child(RadioGroup::row(MoveIndicator::ALL.map(|indicator| (indicator.name(), indicator))).lens(AppState::move_indicator))
+        .with_child(Label::new("Piece movement"))
+        .with_child(RadioGroup::row(AnimationSpeed::ALL.map(|speed| (speed.name(), speed))).lens(AppState::animation))
+        .with_child(Checkbox::new("Always promote to a queen").lens(AppState::auto_queen))
+        .with_child(Label::new("Arrows and circles (right mouse button)"))
+        .with_child(RadioGroup::row(MarkColor::ALL.map(|color| (color.name(), color))).lens(AppState::mark_color))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Figurine notation in the move list").lens(AppState::figurine_notation))
+        .with_child(Checkbox::new("Move list on the left (left-handed layout)").lens(AppState::left_handed))
+        .with_child(Checkbox::new("File and rank labels around the board").lens(AppState::show_coordinates))
+        .with_child(Checkbox::new("Rank labels on the right").lens(AppState::mirror_coordinates))
+        .with_child(Checkbox::new("Turn the board to the side to move (two players, one device)").lens(AppState::auto_rotate))
+        .with_child(Checkbox::new("Warn about threatened pieces").lens(AppState::show_threats))
+        .with_child(Checkbox::new("Show the static evaluation").lens(AppState::show_eval))
+        .with_child(Checkbox::new("Calculate exchanges on hovered captures").lens(AppState::show_exchanges))
+        .with_child(Checkbox::new("Blindfold: hide the pieces (hold Space to peek)").lens(AppState::blindfold))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Sounds for moves, captures, checks and the end of the game").lens(AppState::sound_effects))
+        .with_child(Flex::row()
+            .with_child(Label::new("Volume"))
+            .with_child(Slider::new().with_range(0.0, 1.0).lens(AppState::sound_volume)))
+        .with_spacer(10.0)
+        .with_child(Label::new("Exported diagrams"))
+        .with_child(RadioGroup::row([("400 px", 400), ("800 px", 800), ("1600 px", 1600)])
+            .lens(AppState::diagram.then(DiagramOptions::size)))
+        .with_child(Checkbox::new("Coordinates").lens(AppState::diagram.then(DiagramOptions::coordinates)))
+        .with_child(Checkbox::new("Last move").lens(AppState::diagram.then(DiagramOptions::last_move)))
+        .with_child(Label::new("GIF frame delay"))
+        .with_child(RadioGroup::row([("0.5 s", 500), ("1 s", 1000), ("2 s", 2000)])
+            .lens(AppState::diagram.then(DiagramOptions::frame_delay)))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Play vs Computer").lens(AppState::computer.then(ComputerOptions::enabled)))
+        .with_child(RadioGroup::column(OPPONENTS)
+            .lens(AppState::computer.then(ComputerOptions::opponent)))
+        .with_child(RadioGroup::row([("Computer plays White", PieceColor::White), ("Black", PieceColor::Black)])
+            .lens(AppState::computer.then(ComputerOptions::color)))
+        .with_child(Label::new("Human-like opponent rating"))
+        .with_child(RadioGroup::row([("800", 800), ("1200", 1200), ("1600", 1600), ("2000", 2000)])
+            .lens(AppState::computer.then(ComputerOptions::rating)))
+        .with_child(Label::new("Search depth"))
+        .with_child(RadioGroup::row([("1 ply", 1), ("2 plies", 2), ("3 plies", 3), ("4 plies", 4)])
+            .lens(AppState::computer.then(ComputerOptions::depth)))
+        .with_child(Label::new("Search difficulty"))
+        .with_child(RadioGroup::row(Level::ALL.map(|level| (level.name(), level)))
+            .lens(AppState::computer.then(ComputerOptions::level)))
+        .with_child(Label::new("Search hash table"))
+        .with_child(RadioGroup::row([("16 MB", 16), ("64 MB", 64), ("256 MB", 256)])
+            .lens(AppState::computer.then(ComputerOptions::hash_mb)))
+        .with_child(Label::new("Search threads"))
+        .with_child(RadioGroup::row([("1", 1), ("2", 2), ("4", 4), ("All cores", 0)])
+            .lens(AppState::computer.then(ComputerOptions::threads)))
+        .with_child(Label::new("Monte Carlo playouts per move"))
+        .with_child(RadioGroup::row([("500", 500), ("2000", 2000), ("8000", 8000)])
+            .lens(AppState::computer.then(ComputerOptions::playouts)))
+        .with_child(Label::new("Engines play from the opening book for"))
+        .with_child(RadioGroup::row([("Never", 0), ("5 moves", 5), ("10 moves", 10), ("20 moves", 20)])
+            .lens(AppState::computer.then(ComputerOptions::book_moves)))
+        .with_child(RadioGroup::row(BookVariety::ALL.map(|variety| (variety.name(), variety)))
+            .lens(AppState::computer.then(ComputerOptions::book_variety)))
+        .with_child(Checkbox::new("Think on your time (pondering)").lens(AppState::computer.then(ComputerOptions::ponder)))
+        .with_child(Button::new("External engines...").on_click(|ctx, data: &mut AppState, _env| {
+            ctx.new_window(engine_manager_dialog(data.computer.engines.len()));
+        }))
+        .with_child(Checkbox::new("Analyse with the external engine").lens(AppState::computer.then(ComputerOptions::external_analysis)))
+        .with_child(Label::new("Analysis lines"))
+        .with_child(RadioGroup::row([("1", 1), ("2", 2), ("3", 3), ("5", 5)]).lens(AppState::analysis_lines))
+        .with_child(Checkbox::new("Color legal moves by their tablebase outcome").lens(AppState::tablebase_moves))
+        .with_child(Label::new("When the computer moves while the window is in the background"))
+        .with_child(Checkbox::new("Show a notification").lens(AppState::turn_notification))
+        .with_child(Checkbox::new("Play a sound").lens(AppState::turn_sound))
+        .with_spacer(10.0)
+        .with_child(Button::new("Close").on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+        }))
+        .padding(15.0);
+
+    // Taller than most screens, so it scrolls
+    WindowDesc::new(themed(Scroll::new(content).vertical()))
+        .title("Settings")
+        .window_size((440.0, 800.0))
+        .resizable(false)
+}
+
+/// Sets up who plays the next game, where it starts and with which clock
+pub fn new_game_dialog() -> WindowDesc<AppState> {
+    let levels = Level::ALL.iter().map(|&level| (level.name(), level));
+    let content = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Checkbox::new("Play vs Computer").lens(AppState::computer.then(ComputerOptions::enabled)))
+        .with_child(RadioGroup::row([("Computer plays White", PieceColor::White), ("Black", PieceColor::Black)])
+            .lens(AppState::computer.then(ComputerOptions::color)))
+        .with_spacer(10.0)
+        .with_child(Label::new("Opponent"))
+        .with_child(RadioGroup::column(OPPONENTS)
+            .lens(AppState::computer.then(ComputerOptions::opponent)))
+        .with_spacer(10.0)
+        .with_child(Label::new("Search difficulty"))
+        .with_child(RadioGroup::column(levels).lens(AppState::computer.then(ComputerOptions::level)))
+        .with_child(Checkbox::new("Limit the search to an Elo rating instead").lens(AppState::computer.then(ComputerOptions::limit_elo)))
+        .with_child(RadioGroup::row([("1000", 1000), ("1500", 1500), ("1800", 1800), ("2100", 2100), ("2400", 2400)])
+            .lens(AppState::computer.then(ComputerOptions::elo)))
+        .with_spacer(10.0)
+        .with_child(Label::new("Starting position"))
+        .with_child(RadioGroup::row(StartPosition::ALL.map(|start| (start.name(), start))).lens(AppState::start_position))
+        .with_child(TextBox::new().with_placeholder("FEN").fix_width(400.0).lens(AppState::start_fen))
+        .with_spacer(10.0)
+        .with_child(Label::new("Time control (minutes + seconds a move)"))
+        .with_child(RadioGroup::row(TimeControl::ALL.map(|control| (control.name(), control))).lens(AppState::time_control))
+        .with_spacer(10.0)
+        .with_child(Flex::row()
+            .with_child(Button::new("Start").on_click(|ctx, _data: &mut AppState, _env| {
+                ctx.submit_command(NEW_GAME.to(Target::Global));
+                ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+            }))
+            .with_spacer(10.0)
+            .with_child(Button::new("Cancel").on_click(|ctx, _data: &mut AppState, _env| {
+                ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+            })))
+        .padding(15.0);
+
+    WindowDesc::new(themed(content))
+        .title("New game")
+        .window_size((440.0, 640.0))
+        .resizable(false)
+}
+
+/// Registers external UCI engines, edits their options and picks the ones that play and analyse.
+/// Built for the `count` engines registered; adding or removing one opens it anew.
+pub fn engine_manager_dialog(count: usize) -> WindowDesc<AppState> {
+    let engines = || AppState::computer.then(ComputerOptions::engines);
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for i in 0..count {
+        let remove = Button::new("Remove").on_click(move |ctx, data: &mut AppState, _env| {
+            let computer = &mut data.computer;
+            computer.engines.remove(i);
+            // The engines after it move up
+            for picked in [&mut computer.playing_engine, &mut computer.analysis_engine] {
+                if *picked > i {
+                    *picked -= 1;
+                }
+            }
+            ctx.new_window(engine_manager_dialog(computer.engines.len()));
+            ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+        });
+        list.add_child(text_row("Name", "Stockfish", engines().index(i).then(EngineConfig::name)));
+        list.add_child(text_row("Program", "/usr/bin/stockfish", engines().index(i).then(EngineConfig::path)));
+        list.add_child(text_row("Hash (MB)", "Engine default", engines().index(i).then(EngineConfig::hash)));
+        list.add_child(text_row("Threads", "Engine default", engines().index(i).then(EngineConfig::threads)));
+        list.add_child(text_row("SyzygyPath", "No tablebases", engines().index(i).then(EngineConfig::syzygy_path)));
+        list.add_child(Flex::row()
+            .with_child(Radio::new("Plays", i).lens(AppState::computer.then(ComputerOptions::playing_engine)))
+            .with_spacer(10.0)
+            .with_child(Radio::new("Analyses", i).lens(AppState::computer.then(ComputerOptions::analysis_engine)))
+            .with_spacer(10.0)
+            .with_child(remove));
+        list.add_spacer(15.0);
+    }
+
+    let content = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(Scroll::new(list).vertical(), 1.0)
+        .with_spacer(10.0)
+        .with_child(Flex::row()
+            .with_child(Button::new("Add engine").on_click(|ctx, data: &mut AppState, _env| {
+                data.computer.engines.push_back(EngineConfig::new());
+                ctx.new_window(engine_manager_dialog(data.computer.engines.len()));
+                ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+            }))
+            .with_spacer(10.0)
+            .with_child(Button::new("Save").on_click(|ctx, data: &mut AppState, _env| {
+                if let Err(message) = save_engines(&data.computer.engines) {
+                    ctx.new_window(error_dialog("Save failed", &message));
+                }
+            }))
+            .with_spacer(10.0)
+            .with_child(Button::new("Close").on_click(|ctx, _data: &mut AppState, _env| {
+                ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+            })))
+        .padding(15.0);
+
+    WindowDesc::new(themed(content))
+        .title("External engines")
+        .window_size((460.0, 560.0))
+}
+
+/// A labelled text field of the engine manager
+fn text_row(label: &str, placeholder: &str, field: impl Lens<AppState, String> + 'static) -> impl Widget<AppState> {
+    Flex::row()
+        .with_child(Label::new(label).fix_width(110.0))
+        .with_child(TextBox::new().with_placeholder(placeholder).fix_width(300.0).lens(field))
+}
+
+/// Lists the games of a multi-game PGN so that one of them can be opened
+pub fn game_list_dialog(games: &[PgnGame]) -> WindowDesc<AppState> {
+    let row = |number: String, white: &str, black: &str, result: &str, date: &str| {
+        Flex::row()
+            .with_child(Label::new(number).fix_width(40.0))
+            .with_child(Label::new(white.to_string()).fix_width(160.0))
+            .with_child(Label::new(black.to_string()).fix_width(160.0))
+            .with_child(Label::new(result.to_string()).fix_width(60.0))
+            .with_child(Label::new(date.to_string()).fix_width(90.0))
+    };
+
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for (i, game) in games.iter().enumerate() {
+        let result = game.result.as_deref().or(game.tag("Result")).unwrap_or("*");
+        let open = Button::new("Open").on_click(move |ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(OPEN_LISTED_GAME.with(i).to(Target::Global));
+            ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+        });
+        let picked = Checkbox::new("").lens(AppState::bulletin_games.index(i));
+        list.add_child(Flex::row().with_child(picked).with_child(row(
+            (i + 1).to_string(),
+            game.tag("White").unwrap_or("?"),
+            game.tag("Black").unwrap_or("?"),
+            result,
+            game.tag("Date").unwrap_or("?"),
+        )).with_child(open));
+    }
+
+    let content = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Flex::row().with_spacer(30.0).with_child(row("#".to_string(), "White", "Black", "Result", "Date")))
+        .with_spacer(5.0)
+        .with_flex_child(Scroll::new(list).vertical(), 1.0)
+        .with_spacer(10.0)
+        .with_child(Button::new("Print bulletin of the ticked games...").on_click(|ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(PRINT_BULLETIN.to(Target::Global));
+        }))
+        .padding(15.0);
+
+    WindowDesc::new(themed(content))
+        .title(format!("{} games", games.len()))
+        .window_size((650.0, 480.0))
+}
+
+/// The session timeline, newest first, given as (age, label) pairs in the order they were taken
+pub fn history_dialog(entries: &[(String, String)]) -> WindowDesc<AppState> {
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    for (i, (age, label)) in entries.iter().enumerate().rev() {
+        let restore = Button::new("Restore").on_click(move |ctx, _data: &mut AppState, _env| {
+            ctx.submit_command(RESTORE_SNAPSHOT.with(i).to(Target::Global));
+            ctx.submit_command(commands::CLOSE_WINDOW.to(ctx.window_id()));
+        });
+        list.add_child(Flex::row()
+            .with_child(Label::new(age.clone()).fix_width(110.0))
+            .with_child(Label::new(label.clone()).fix_width(300.0))
+            .with_child(restore));
+    }
+    if entries.is_empty() {
+        list.add_child(Label::new("Nothing recorded yet this session."));
+    }
+
+    WindowDesc::new(themed(Scroll::new(list).vertical().padding(15.0)))
+        .title("History")
+        .window_size((520.0, 400.0))
+}
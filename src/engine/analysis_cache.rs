@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use serde::{Deserialize, Serialize};
+use super::search::Analysis;
+
+/// Positions kept at most; the shallowest analysis makes room for a new position
+const MAX_POSITIONS: usize = 20_000;
+
+/// One line of the cache file: an analysis of the position with the Polyglot key `key`
+#[derive(Serialize, Deserialize)]
+struct Record {
+    key: u64,
+    analysis: Analysis,
+}
+
+/// The deepest analysis found of each position looked at, kept between runs, so that a position
+/// analysed before shows it at once, in this game or another. The file gets a line for each
+/// deeper analysis, the last line about a position counting; it is rewritten once mostly
+/// replaced lines are left.
+pub struct AnalysisCache {
+    path: String,
+    entries: HashMap<u64, Analysis>,
+}
+
+impl AnalysisCache {
+    /// Reads the cache kept in `path`, empty when there is none yet. Lines that cannot be read,
+    /// such as one cut short, are skipped.
+    pub fn open(path: &str) -> Self {
+        let mut cache = Self { path: path.to_string(), entries: HashMap::new() };
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let mut records = 0;
+        for line in text.lines() {
+            if let Ok(Record { key, analysis }) = serde_json::from_str(line) {
+                cache.entries.insert(key, analysis);
+                records += 1;
+            }
+        }
+        if records > 2 * cache.entries.len().max(MAX_POSITIONS / 2) {
+            let _ = cache.rewrite();
+        }
+        cache
+    }
+
+    /// The analysis of the position with `key`, when one with at least `lines` best moves is kept
+    pub fn get(&self, key: u64, lines: usize) -> Option<Analysis> {
+        let cached = self.entries.get(&key).filter(|cached| cached.lines.len() >= lines)?;
+        Some(Analysis { lines: cached.lines.iter().take(lines).cloned().collect(), ..cached.clone() })
+    }
+
+    /// Keeps `analysis` of the position with `key` unless a deeper one is kept already
+    pub fn insert(&mut self, key: u64, analysis: &Analysis) -> Result<(), String> {
+        if self.entries.get(&key).is_some_and(|cached| cached.depth > analysis.depth || (cached.depth == analysis.depth && cached.lines.len() >= analysis.lines.len())) {
+            return Ok(());
+        }
+        if self.entries.len() >= MAX_POSITIONS && !self.entries.contains_key(&key) {
+            if let Some(shallowest) = self.entries.iter().min_by_key(|(_, cached)| cached.depth).map(|(&key, _)| key) {
+                self.entries.remove(&shallowest);
+            }
+        }
+        self.entries.insert(key, analysis.clone());
+
+        let line = serde_json::to_string(&Record { key, analysis: analysis.clone() }).map_err(|e| e.to_string())?;
+        OpenOptions::new().create(true).append(true).open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("cannot write {}: {}", self.path, e))
+    }
+
+    /// Writes the file again with one line per position
+    fn rewrite(&self) -> Result<(), String> {
+        let mut text = String::new();
+        for (&key, analysis) in &self.entries {
+            text.push_str(&serde_json::to_string(&Record { key, analysis: analysis.clone() }).map_err(|e| e.to_string())?);
+            text.push('\n');
+        }
+        std::fs::write(&self.path, text).map_err(|e| format!("cannot write {}: {}", self.path, e))
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::book::polyglot::polyglot_key;
+use crate::game::fen::{parse_fen, to_fen, STARTING_FEN};
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::game::pgn::PgnGame;
+use crate::game::player::Player;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::eval::evaluate;
+use super::predicted_reply;
+use super::search::{scored_best_move, scored_best_move_until};
+use super::tablebase::{Tablebase, TablebaseResult};
+use super::uci::UciEngine;
+
+/// One side of an engine match
+pub enum MatchPlayer {
+    Builtin { depth: u32 }, // The negamax search at full strength
+    External(UciEngine),
+}
+
+impl MatchPlayer {
+    pub fn name(&self) -> String {
+        match self {
+            MatchPlayer::Builtin { depth } => format!("chess_rust (depth {})", depth),
+            MatchPlayer::External(engine) => engine.name.clone(),
+        }
+    }
+
+    fn new_game(&mut self, ponder: bool) -> Result<(), String> {
+        match self {
+            MatchPlayer::Builtin { .. } => Ok(()),
+            MatchPlayer::External(engine) => {
+                // Engines manage their time differently when they know they may ponder
+                engine.set_option("Ponder", if ponder { "true" } else { "false" })?;
+                engine.new_game()
+            }
+        }
+    }
+
+    /// The player's move with its score for the side to move, when it gives one. A search
+    /// `pondering` the position is taken over, or stopped when the opponent played otherwise.
+    fn play(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration, pondering: Option<Pondering>) -> Result<Option<(ParsedMove, Option<i32>)>, String> {
+        let hit = pondering.as_ref().is_some_and(|pondering| pondering.key() == polyglot_key(state, board));
+        match (self, pondering) {
+            (MatchPlayer::Builtin { .. }, Some(Pondering::Builtin { search, .. })) if hit => {
+                Ok(search.join().map_err(|_| "the pondering search failed".to_string())?.map(|(mv, score)| (mv, Some(score))))
+            }
+            (MatchPlayer::External(engine), Some(Pondering::External { .. })) if hit => engine.ponder_hit(state, board, movetime),
+            (player, pondering) => {
+                if let Some(pondering) = pondering {
+                    player.stop_pondering(pondering)?;
+                }
+                match player {
+                    MatchPlayer::Builtin { depth } => Ok(scored_best_move(state, board, *depth, 64, 1, evaluate).map(|(mv, score)| (mv, Some(score)))),
+                    MatchPlayer::External(engine) => engine.scored_best_move(state, board, movetime),
+                }
+            }
+        }
+    }
+
+    /// Starts thinking on the opponent's time, in `state` after the player's own move, about the
+    /// reply it expects: the one the engine names, or the built-in engine's guess
+    fn ponder(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<Pondering>, String> {
+        match self {
+            MatchPlayer::Builtin { depth } => {
+                let Some((from, to, promotion)) = predicted_reply(state, board) else { return Ok(None) };
+                let (mut state, mut board) = (state.clone(), board.clone());
+                state.make_move_with_promotion(from, to, promotion, &mut board);
+                let (key, depth) = (polyglot_key(&state, &board), *depth);
+                let stop = Arc::new(AtomicBool::new(false));
+                let stopped = stop.clone();
+                let search = thread::spawn(move || scored_best_move_until(&state, &board, depth, 64, 1, evaluate, &stopped));
+                Ok(Some(Pondering::Builtin { key, stop, search }))
+            }
+            MatchPlayer::External(engine) => Ok(engine.ponder(state, board, movetime)?.map(|key| Pondering::External { key })),
+        }
+    }
+
+    /// Gives up pondering, once the opponent played another reply or the game is over
+    fn stop_pondering(&mut self, pondering: Pondering) -> Result<(), String> {
+        match (self, pondering) {
+            (_, Pondering::Builtin { stop, search, .. }) => {
+                stop.store(true, Ordering::Relaxed);
+                let _ = search.join();
+                Ok(())
+            }
+            (MatchPlayer::External(engine), Pondering::External { .. }) => engine.stop_pondering(),
+            (MatchPlayer::Builtin { .. }, Pondering::External { .. }) => Ok(()),
+        }
+    }
+}
+
+/// A player thinking on its opponent's time about its answer to the reply it expects
+enum Pondering {
+    Builtin { key: u64, stop: Arc<AtomicBool>, search: JoinHandle<Option<(ParsedMove, i32)>> },
+    External { key: u64 },
+}
+
+impl Pondering {
+    /// Key of the position pondered, after the reply expected
+    fn key(&self) -> u64 {
+        match self {
+            Pondering::Builtin { key, .. } | Pondering::External { key } => *key,
+        }
+    }
+}
+
+/// How an engine match is played and when its games are adjudicated
+pub struct MatchSettings {
+    pub games: u32,
+    pub openings: Vec<(GameState, Vec<Option<Piece>>)>, // Each played twice, once with either color; the standard start when empty
+    pub movetime: Duration, // Thinking time of external engines per move
+    pub resign_score: i32, // A side loses once its engine sees itself this many centipawns behind...
+    pub resign_moves: u32, // ...on this many of its moves in a row; 0 never adjudicates a loss
+    pub draw_score: i32, // A game is drawn once both engines see a score within this many centipawns...
+    pub draw_moves: u32, // ...for this many moves in a row; 0 never adjudicates a draw
+    pub draw_after: u32, // ...but not before this move
+    pub max_moves: u32, // Games still going at this move are drawn
+    pub tablebase: Option<Tablebase>, // Decides games once few enough pieces are left
+    pub ponder: bool, // Whether the players think on their opponent's time, as the computer does with its ponder setting
+}
+
+impl MatchSettings {
+    pub fn new() -> Self {
+        Self { games: 10, openings: Vec::new(), movetime: Duration::from_secs(1), resign_score: 1000, resign_moves: 3, draw_score: 10, draw_moves: 8, draw_after: 40, max_moves: 200, tablebase: None, ponder: false }
+    }
+}
+
+/// Games won, lost and drawn by the first player of a match
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Plays `settings.games` games between two players, the first taking White in the even games.
+/// Each finished game is passed to `report` with its number as PGN, tagged with how it ended.
+pub fn run_match(first: &mut MatchPlayer, second: &mut MatchPlayer, settings: &MatchSettings, mut report: impl FnMut(u32, &PgnGame)) -> Result<MatchScore, String> {
+    let mut score = MatchScore::default();
+    for game in 0..settings.games {
+        let (state, board) = match settings.openings.len() {
+            0 => parse_fen(STARTING_FEN)?,
+            count => settings.openings[(game as usize / 2) % count].clone(),
+        };
+        let first_white = game % 2 == 0;
+        let (white, black) = if first_white { (&mut *first, &mut *second) } else { (&mut *second, &mut *first) };
+        let mut pgn = play_game(white, black, state, board, settings)?;
+        pgn.set_tag("Event", "Engine match");
+        pgn.set_tag("Round", &(game + 1).to_string());
+
+        match (pgn.result.as_deref(), first_white) {
+            (Some("1-0"), true) | (Some("0-1"), false) => score.wins += 1,
+            (Some("1-0"), false) | (Some("0-1"), true) => score.losses += 1,
+            _ => score.draws += 1,
+        }
+        report(game + 1, &pgn);
+    }
+    Ok(score)
+}
+
+/// Plays one game to its end or adjudication
+fn play_game(white: &mut MatchPlayer, black: &mut MatchPlayer, mut state: GameState, mut board: Vec<Option<Piece>>, settings: &MatchSettings) -> Result<PgnGame, String> {
+    white.new_game(settings.ponder)?;
+    black.new_game(settings.ponder)?;
+    let player = |name| Some(Player { name, rating: None, title: None, country: None });
+    state.white_player = player(white.name());
+    state.black_player = player(black.name());
+
+    let mut seen: HashMap<u64, u32> = HashMap::new();
+    *seen.entry(polyglot_key(&state, &board)).or_insert(0) += 1;
+    let mut losing = [0, 0]; // Moves in a row each side saw itself lost, White first
+    let mut drawish = 0; // Plies in a row with a score close to even
+    let mut pondering: [Option<Pondering>; 2] = [None, None]; // White's first
+    let (result, termination) = loop {
+        let mover = state.current_turn;
+        let loss = if mover == PieceColor::White { "0-1" } else { "1-0" };
+        match state.status {
+            GameStatus::Checkmate => break (loss, "checkmate"),
+            GameStatus::Stalemate => break ("1/2-1/2", "stalemate"),
+            _ => {}
+        }
+        if state.halfmove_clock >= 100 {
+            break ("1/2-1/2", "fifty-move rule");
+        }
+        if seen[&polyglot_key(&state, &board)] >= 3 {
+            break ("1/2-1/2", "threefold repetition");
+        }
+        if board.iter().flatten().all(|piece| piece.piece_type == PieceType::King) {
+            break ("1/2-1/2", "insufficient material");
+        }
+        if state.fullmove_number > settings.max_moves {
+            break ("1/2-1/2", "move limit");
+        }
+        let win = if mover == PieceColor::White { "1-0" } else { "0-1" };
+        match settings.tablebase.as_ref().and_then(|tablebase| tablebase.probe(&state, &board)) {
+            Some(TablebaseResult::Win(_)) => break (win, "tablebase"),
+            Some(TablebaseResult::Loss(_)) => break (loss, "tablebase"),
+            Some(TablebaseResult::Draw) => break ("1/2-1/2", "tablebase"),
+            None => {}
+        }
+
+        let side = if mover == PieceColor::White { 0 } else { 1 };
+        let player = if mover == PieceColor::White { &mut *white } else { &mut *black };
+        let ((from, to, promotion), score) = player.play(&state, &board, settings.movetime, pondering[side].take())?
+            .ok_or_else(|| format!("{} gave no move in {}", player.name(), to_fen(&state, &board)))?;
+
+        if let Some(score) = score {
+            losing[side] = if score <= -settings.resign_score { losing[side] + 1 } else { 0 };
+            if settings.resign_moves > 0 && losing[side] >= settings.resign_moves {
+                break (loss, "adjudicated loss");
+            }
+            drawish = if state.fullmove_number >= settings.draw_after && score.abs() <= settings.draw_score { drawish + 1 } else { 0 };
+            if settings.draw_moves > 0 && drawish >= 2 * settings.draw_moves {
+                break ("1/2-1/2", "adjudicated draw");
+            }
+        }
+
+        if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+            return Err(format!("{} played an illegal move", player.name()));
+        }
+        *seen.entry(polyglot_key(&state, &board)).or_insert(0) += 1;
+        if settings.ponder && !state.is_over() {
+            pondering[side] = player.ponder(&state, &board, settings.movetime)?;
+        }
+    };
+    // A player may still be pondering a reply that never came
+    for (player, pondering) in [&mut *white, &mut *black].into_iter().zip(pondering) {
+        if let Some(pondering) = pondering {
+            player.stop_pondering(pondering)?;
+        }
+    }
+
+    let mut pgn = PgnGame::from_game(&state);
+    pgn.result = Some(result.to_string());
+    pgn.set_tag("Result", result);
+    pgn.set_tag("Termination", termination);
+    Ok(pgn)
+}
@@ -0,0 +1,203 @@
+use crate::game::threats::piece_value;
+use crate::pieces::{Piece, PieceColor, PieceType};
+
+/// A static evaluation in centipawns from White's side
+pub type Evaluator = fn(&[Option<Piece>]) -> i32;
+
+// Piece-square tables in centipawns from White's side, indexed like the board (a8 first).
+// Middlegame tables follow Michniewski's simplified evaluation function; the king and pawns
+// get separate endgame tables, where the king should centralize and passers advance.
+
+const PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const PAWN_ENDGAME: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     20,  20,  20,  20,  20,  20,  20,  20,
+     10,  10,  10,  10,  10,  10,  10,  10,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+const KING: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+const KING_ENDGAME: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+/// Game phase with all pieces on the board; minor pieces count 1, rooks 2 and queens 4
+const OPENING_PHASE: i32 = 24;
+
+/// Material and piece-square values for the middlegame and the endgame
+fn piece_scores(piece_type: PieceType, square: usize) -> (i32, i32) {
+    match piece_type {
+        PieceType::Pawn => (82 + PAWN[square], 94 + PAWN_ENDGAME[square]),
+        PieceType::Knight => (337 + KNIGHT[square], 281 + KNIGHT[square]),
+        PieceType::Bishop => (365 + BISHOP[square], 297 + BISHOP[square]),
+        PieceType::Rook => (477 + ROOK[square], 512 + ROOK[square]),
+        PieceType::Queen => (1025 + QUEEN[square], 936 + QUEEN[square]),
+        PieceType::King => (KING[square], KING_ENDGAME[square]),
+    }
+}
+
+fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        PieceType::Pawn | PieceType::King => 0,
+    }
+}
+
+/// Static evaluation in centipawns from White's side: material plus piece-square tables,
+/// blended from the middlegame to the endgame values as pieces come off
+pub fn evaluate(board: &[Option<Piece>]) -> i32 {
+    let (mut middlegame, mut endgame, mut phase) = (0, 0, 0);
+    for (i, piece) in board.iter().enumerate() {
+        let Some(piece) = piece else { continue };
+        // Black's tables are White's mirrored top to bottom
+        let (square, sign) = match piece.color {
+            PieceColor::White => (i, 1),
+            PieceColor::Black => ((7 - i / 8) * 8 + i % 8, -1),
+        };
+        let (mg, eg) = piece_scores(piece.piece_type, square);
+        middlegame += sign * mg;
+        endgame += sign * eg;
+        phase += phase_weight(piece.piece_type);
+    }
+
+    let phase = phase.min(OPENING_PHASE);
+    (middlegame * phase + endgame * (OPENING_PHASE - phase)) / OPENING_PHASE
+}
+
+/// Material alone in centipawns from White's side, for weak levels that do not know where their
+/// pieces stand well
+pub fn material(board: &[Option<Piece>]) -> i32 {
+    board.iter().flatten()
+        .map(|piece| {
+            let value = piece_value(piece.piece_type) * 100;
+            if piece.color == PieceColor::White { value } else { -value }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fen::{parse_fen, STARTING_FEN};
+
+    fn board(fen: &str) -> Vec<Option<Piece>> {
+        parse_fen(fen).unwrap().1
+    }
+
+    /// The board turned top to bottom with the colors swapped, so that each side has the other's
+    /// position
+    fn mirrored(board: &[Option<Piece>]) -> Vec<Option<Piece>> {
+        (0..64)
+            .map(|i| board[(7 - i / 8) * 8 + i % 8].map(|piece| Piece {
+                color: if piece.color == PieceColor::White { PieceColor::Black } else { PieceColor::White },
+                ..piece
+            }))
+            .collect()
+    }
+
+    #[test]
+    fn start_position_is_level() {
+        assert_eq!(evaluate(&board(STARTING_FEN)), 0);
+    }
+
+    #[test]
+    fn mirrored_position_negates_the_score() {
+        let position = board("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5");
+        let score = evaluate(&position);
+        assert_ne!(score, 0);
+        assert_eq!(evaluate(&mirrored(&position)), -score);
+    }
+
+    #[test]
+    fn bare_kings_use_the_endgame_table() {
+        // White king on e4 (square 36), Black king on a8, which mirrors to a1 (square 56)
+        let score = evaluate(&board("k7/8/8/8/4K3/8/8/8 w - - 0 1"));
+        assert_eq!(score, KING_ENDGAME[36] - KING_ENDGAME[56]);
+    }
+
+    #[test]
+    fn score_tapers_with_the_pieces_left() {
+        // The knights cancel out, but bring the phase to 2 of 24
+        let score = evaluate(&board("kn6/8/8/8/4K3/8/8/1N6 w - - 0 1"));
+        let (middlegame, endgame) = (KING[36] - KING[56], KING_ENDGAME[36] - KING_ENDGAME[56]);
+        assert_eq!(score, (middlegame * 2 + endgame * (OPENING_PHASE - 2)) / OPENING_PHASE);
+        assert!(middlegame < score && score < endgame);
+    }
+}
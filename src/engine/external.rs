@@ -0,0 +1,51 @@
+use druid::im::Vector;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+use super::uci::UciEngine;
+
+/// Where the registered engines are kept, relative to the working directory
+pub const ENGINES_PATH: &str = "engines.json";
+
+/// An external UCI engine registered in the engine manager. Options left empty keep the
+/// engine's own default.
+#[derive(Clone, PartialEq, Debug, Data, Lens, Serialize, Deserialize)]
+pub struct EngineConfig {
+    pub name: String,
+    pub path: String, // The engine program
+    pub hash: String, // Hash table size in megabytes
+    pub threads: String,
+    pub syzygy_path: String, // Folder of the Syzygy endgame tablebases
+}
+
+impl EngineConfig {
+    pub fn new() -> Self {
+        Self { name: "New engine".to_string(), path: String::new(), hash: String::new(), threads: String::new(), syzygy_path: String::new() }
+    }
+
+    /// Starts the engine with the configured options set
+    pub fn start(&self) -> Result<UciEngine, String> {
+        let mut engine = UciEngine::start(&self.path)?;
+        for (option, value) in [("Hash", &self.hash), ("Threads", &self.threads), ("SyzygyPath", &self.syzygy_path)] {
+            if !value.trim().is_empty() {
+                engine.set_option(option, value.trim())?;
+            }
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+}
+
+/// The registered engines, none when nothing was saved yet
+pub fn load_engines() -> Result<Vector<EngineConfig>, String> {
+    let text = match std::fs::read_to_string(ENGINES_PATH) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vector::new()),
+        Err(e) => return Err(format!("cannot read {}: {}", ENGINES_PATH, e)),
+    };
+    serde_json::from_str(&text).map_err(|e| format!("corrupt {}: {}", ENGINES_PATH, e))
+}
+
+pub fn save_engines(engines: &Vector<EngineConfig>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(engines).map_err(|e| e.to_string())?;
+    std::fs::write(ENGINES_PATH, json).map_err(|e| format!("cannot write {}: {}", ENGINES_PATH, e))
+}
@@ -0,0 +1,65 @@
+use rand::Rng;
+use crate::book::polyglot::PolyglotBook;
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::game::threats::{exchange_gain, piece_value};
+use crate::pieces::{Piece, PieceType};
+
+/// Picks a move the way a club player of the given rating might: book moves in proportion to
+/// how often they are played, otherwise a random move favoring the ones that look good at a
+/// glance, with weaker players straying further from the best one
+pub fn human_like_move(state: &GameState, board: &Vec<Option<Piece>>, book: Option<&PolyglotBook>, rating: u32) -> Option<ParsedMove> {
+    let mut rng = rand::thread_rng();
+
+    let book_moves = book.map(|book| book.moves(state, board)).unwrap_or_default();
+    let total_weight: u32 = book_moves.iter().map(|m| m.weight as u32).sum();
+    if total_weight > 0 {
+        let mut pick = rng.gen_range(0..total_weight);
+        for book_move in &book_moves {
+            if pick < book_move.weight as u32 {
+                return Some((book_move.from, book_move.to, book_move.promotion.unwrap_or(PieceType::Queen)));
+            }
+            pick -= book_move.weight as u32;
+        }
+    }
+
+    // Softmax over a shallow material score; the temperature is in pawns
+    let temperature = ((2200.0 - rating as f64) / 600.0).clamp(0.15, 3.0);
+    let scored: Vec<_> = state.legal_moves(board).into_iter()
+        .map(|(from, to)| (from, to, glance_score(state, board, from, to)))
+        .collect();
+    let best = scored.iter().map(|&(_, _, score)| score).fold(f64::MIN, f64::max);
+    let weights: Vec<f64> = scored.iter().map(|&(_, _, score)| ((score - best) / temperature).exp()).collect();
+
+    let mut pick = rng.gen_range(0.0..weights.iter().sum::<f64>().max(f64::MIN_POSITIVE));
+    for (&(from, to, _), weight) in scored.iter().zip(&weights) {
+        if pick < *weight {
+            return Some((from, to, PieceType::Queen));
+        }
+        pick -= weight;
+    }
+    scored.last().map(|&(from, to, _)| (from, to, PieceType::Queen))
+}
+
+/// Material won by the move minus the most the opponent can then win back by a capture,
+/// with mate counting above everything
+fn glance_score(state: &GameState, board: &[Option<Piece>], from: (usize, usize), to: (usize, usize)) -> f64 {
+    let mut after_state = state.clone();
+    let mut after_board = board.to_vec();
+    after_state.make_move(from, to, &mut after_board);
+    if after_state.status == GameStatus::Checkmate {
+        return 1000.0;
+    }
+
+    let captured = board[to.0 * 8 + to.1].map_or(0, |piece| piece_value(piece.piece_type));
+    let is_pawn = board[from.0 * 8 + from.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+    let promoted = if is_pawn && (to.0 == 0 || to.0 == 7) { 8 } else { 0 };
+    let opponent = after_state.current_turn;
+    let risk = (0..64)
+        .filter(|&i| after_board[i].is_some_and(|piece| piece.color != opponent && piece.piece_type != PieceType::King))
+        .filter_map(|i| exchange_gain(&after_board, (i / 8, i % 8), opponent))
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    (captured + promoted - risk) as f64
+}
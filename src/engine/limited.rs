@@ -0,0 +1,30 @@
+use rand::Rng;
+use crate::game::game_state::GameState;
+use crate::game::notation::ParsedMove;
+use crate::pieces::Piece;
+use super::search::scored_moves;
+
+/// Deepest search of the Elo-limited opponent; the node limit usually stops it sooner
+const MAX_DEPTH: u32 = 8;
+
+/// Plays like an engine of about the given Elo rating. The search is cut off after a number of
+/// positions that doubles every 150 points, then a move is drawn among all of them, weighted by
+/// how close their score is to the best one; weaker ratings draw from a flatter distribution.
+pub fn elo_limited_move(state: &GameState, board: &Vec<Option<Piece>>, elo: u32, hash_mb: usize) -> Option<ParsedMove> {
+    let max_nodes = (50.0 * 2f64.powf((elo as f64 - 800.0) / 150.0)) as u64;
+    let scored = scored_moves(state, board, MAX_DEPTH, max_nodes, hash_mb);
+
+    // How many centipawns worse a move has to be to be played e times less often
+    let temperature = ((2500.0 - elo as f64) / 6.0).clamp(5.0, 250.0);
+    let best = scored.iter().map(|&(_, score)| score).max()?;
+    let weights: Vec<f64> = scored.iter().map(|&(_, score)| ((score - best) as f64 / temperature).exp()).collect();
+
+    let mut pick = rand::thread_rng().gen_range(0.0..weights.iter().sum::<f64>());
+    for (&(mv, _), weight) in scored.iter().zip(&weights) {
+        if pick < *weight {
+            return Some(mv);
+        }
+        pick -= weight;
+    }
+    scored.first().map(|&(mv, _)| mv)
+}
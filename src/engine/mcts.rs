@@ -0,0 +1,117 @@
+use rand::Rng;
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::eval::evaluate;
+
+/// Random moves played from a new node before the evaluation judges the position; playing
+/// games out to the end would take far longer and say little more
+const PLAYOUT_PLIES: usize = 8;
+
+/// Weight of exploring rarely visited moves against playing the ones that scored well
+const EXPLORATION: f64 = 1.4;
+
+type Move = ((usize, usize), (usize, usize));
+
+struct Node {
+    mv: Option<Move>, // The move leading here; `None` at the root
+    mover: PieceColor, // Side that played `mv`
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Move>, // Legal moves without a child node yet
+    visits: u32,
+    score: f64, // Sum of the playout results for `mover`, 1 for a win and 0 for a loss
+}
+
+impl Node {
+    fn new(mv: Option<Move>, mover: PieceColor, parent: Option<usize>, state: &GameState, board: &Vec<Option<Piece>>) -> Self {
+        let untried = match state.status {
+            GameStatus::Checkmate | GameStatus::Stalemate => Vec::new(),
+            _ => state.legal_moves(board),
+        };
+        Self { mv, mover, parent, children: Vec::new(), untried, visits: 0, score: 0.0 }
+    }
+
+    /// Upper confidence bound of the node's score (UCT)
+    fn uct(&self, parent_visits: u32) -> f64 {
+        let visits = self.visits as f64;
+        self.score / visits + EXPLORATION * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+/// Picks a move by Monte Carlo tree search: each of the `playouts` walks down the tree by the
+/// UCT formula, adds one new move, plays on at random for a few moves and credits the evaluated
+/// outcome to the moves on the way. The move tried most often is played.
+pub fn mcts_move(state: &GameState, board: &Vec<Option<Piece>>, playouts: u32) -> Option<ParsedMove> {
+    let mut rng = rand::thread_rng();
+    let last_mover = if state.current_turn == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+    let mut tree = vec![Node::new(None, last_mover, None, state, board)];
+    if tree[0].untried.is_empty() {
+        return None;
+    }
+
+    for _ in 0..playouts {
+        let mut node = 0;
+        let mut state = state.clone();
+        let mut board = board.clone();
+
+        // Selection
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            let parent_visits = tree[node].visits;
+            node = tree[node].children.iter().copied()
+                .max_by(|&a, &b| tree[a].uct(parent_visits).total_cmp(&tree[b].uct(parent_visits)))
+                .unwrap();
+            if let Some((from, to)) = tree[node].mv {
+                state.make_move(from, to, &mut board);
+            }
+        }
+
+        // Expansion
+        if !tree[node].untried.is_empty() {
+            let untried = &mut tree[node].untried;
+            let (from, to) = untried.swap_remove(rng.gen_range(0..untried.len()));
+            let mover = state.current_turn;
+            state.make_move(from, to, &mut board);
+            tree.push(Node::new(Some((from, to)), mover, Some(node), &state, &board));
+            let child = tree.len() - 1;
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        // Simulation, then backpropagation
+        let white_score = playout(&mut state, &mut board, &mut rng);
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut tree[index];
+            node.visits += 1;
+            node.score += if node.mover == PieceColor::White { white_score } else { 1.0 - white_score };
+            current = node.parent;
+        }
+    }
+
+    tree[0].children.iter()
+        .max_by_key(|&&child| tree[child].visits)
+        .and_then(|&child| tree[child].mv)
+        .map(|(from, to)| (from, to, PieceType::Queen))
+}
+
+/// Plays random moves for a few plies and scores the result for White between 0 and 1
+fn playout(state: &mut GameState, board: &mut Vec<Option<Piece>>, rng: &mut impl Rng) -> f64 {
+    for _ in 0..PLAYOUT_PLIES {
+        if matches!(state.status, GameStatus::Checkmate | GameStatus::Stalemate) {
+            break;
+        }
+        let moves = state.legal_moves(board);
+        let (from, to) = moves[rng.gen_range(0..moves.len())];
+        state.make_move(from, to, board);
+    }
+
+    match state.status {
+        // The side to move is mated
+        GameStatus::Checkmate if state.current_turn == PieceColor::White => 0.0,
+        GameStatus::Checkmate => 1.0,
+        GameStatus::Stalemate => 0.5,
+        // A pawn up is about a 64% expected score
+        _ => 1.0 / (1.0 + 10f64.powf(-evaluate(board) as f64 / 400.0)),
+    }
+}
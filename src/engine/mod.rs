@@ -0,0 +1,221 @@
+pub mod analysis_cache;
+pub mod engine_match;
+pub mod eval;
+pub mod external;
+pub mod human_like;
+pub mod limited;
+pub mod mcts;
+pub mod random;
+pub mod review;
+pub mod search;
+pub mod tablebase;
+pub mod tt;
+pub mod uci;
+
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use druid::im::Vector;
+use druid::{Data, Lens};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::book::polyglot::PolyglotBook;
+use crate::game::game_state::GameState;
+use crate::game::notation::ParsedMove;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use eval::{evaluate, material, Evaluator};
+use external::EngineConfig;
+
+/// How the computer picks its moves
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum Opponent {
+    Random,
+    HumanLike,
+    Negamax,
+    MonteCarlo,
+    External, // A UCI engine such as Stockfish
+}
+
+/// Difficulty of the search opponent. Weaker levels look fewer plies ahead, whatever the depth
+/// setting, judge positions by material alone and now and then play a random move instead.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum Level {
+    Beginner,
+    Casual,
+    Club,
+    Full,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Beginner, Level::Casual, Level::Club, Level::Full];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Beginner => "Beginner",
+            Level::Casual => "Casual",
+            Level::Club => "Club player",
+            Level::Full => "Full strength",
+        }
+    }
+
+    fn max_depth(self) -> u32 {
+        match self {
+            Level::Beginner => 1,
+            Level::Casual => 2,
+            Level::Club => 3,
+            Level::Full => u32::MAX,
+        }
+    }
+
+    /// Chance of playing a random legal move instead of the one searched
+    fn blunder_chance(self) -> f64 {
+        match self {
+            Level::Beginner => 0.3,
+            Level::Casual => 0.15,
+            Level::Club => 0.05,
+            Level::Full => 0.0,
+        }
+    }
+
+    fn evaluator(self) -> Evaluator {
+        match self {
+            Level::Beginner | Level::Casual => material,
+            Level::Club | Level::Full => evaluate,
+        }
+    }
+}
+
+/// How the engines pick among the book moves of a position
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum BookVariety {
+    Best, // Always the heaviest
+    Weighted, // In proportion to the weights
+    Uniform, // Any book move alike
+}
+
+impl BookVariety {
+    pub const ALL: [BookVariety; 3] = [BookVariety::Best, BookVariety::Weighted, BookVariety::Uniform];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BookVariety::Best => "Best move",
+            BookVariety::Weighted => "By weight",
+            BookVariety::Uniform => "Any move",
+        }
+    }
+}
+
+/// Settings of the "Play vs Computer" mode
+#[derive(Clone, Data, Lens, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComputerOptions {
+    pub enabled: bool,
+    pub opponent: Opponent,
+    pub color: PieceColor, // Side the computer plays
+    pub rating: u32, // Strength the human-like opponent imitates; lower ratings pick worse moves more often
+    pub depth: u32, // Plies the negamax search looks ahead
+    pub level: Level, // Difficulty of the negamax search
+    pub limit_elo: bool, // Whether the search plays at the `elo` rating instead of its level
+    pub elo: u32,
+    pub hash_mb: u32, // Size of the search's transposition table in megabytes
+    pub threads: u32, // Search threads; 0 uses every core
+    pub playouts: u32, // Playouts of the Monte Carlo tree search per move
+    #[serde(skip)]
+    pub engines: Vector<EngineConfig>, // External UCI engines registered in the engine manager, kept in ENGINES_PATH
+    pub playing_engine: usize, // Index into `engines` of the one playing as the external opponent
+    pub analysis_engine: usize, // And of the one analysing when `external_analysis` is set
+    pub external_analysis: bool,
+    pub ponder: bool, // Whether the computer thinks on the opponent's time
+    pub book_moves: u32, // Moves of the game the engines take from the opening book; 0 never does
+    pub book_variety: BookVariety,
+}
+
+impl ComputerOptions {
+    pub fn new() -> Self {
+        Self { enabled: false, opponent: Opponent::HumanLike, color: PieceColor::Black, rating: 1200, depth: 3, level: Level::Full, limit_elo: false, elo: 1500, hash_mb: 64, threads: 0, playouts: 2000, engines: Vector::new(), playing_engine: 0, analysis_engine: 0, external_analysis: false, ponder: false, book_moves: 10, book_variety: BookVariety::Weighted }
+    }
+
+    /// The registered engine at `index`, or an error telling the user to register one
+    pub fn external_engine(&self, index: usize) -> Result<&EngineConfig, String> {
+        self.engines.get(index).ok_or_else(|| "No external engine is registered. Add one in the engine manager.".to_string())
+    }
+}
+
+impl Default for ComputerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plies searched to guess the opponent's reply when pondering
+const PREDICTION_DEPTH: u32 = 3;
+
+/// The computer takes a draw unless it sees itself ahead by more than this many centipawns
+const DRAW_ACCEPT_SCORE: i32 = 25;
+
+/// Thinking time given to the external engine for each move
+const EXTERNAL_MOVE_TIME: Duration = Duration::from_secs(1);
+
+/// The computer's move in the position, or `None` when it has no legal move. Only the external
+/// engine can fail. Searching can take a while, so call this off the UI thread; setting `stop`
+/// cuts the search short, once its answer is no longer wanted.
+pub fn computer_move(options: &ComputerOptions, state: &GameState, board: &Vec<Option<Piece>>, book: Option<&PolyglotBook>, stop: &AtomicBool) -> Result<Option<ParsedMove>, String> {
+    // Engines play from the book early on, which is instant and varies their games
+    if matches!(options.opponent, Opponent::Negamax | Opponent::MonteCarlo | Opponent::External) {
+        if let Some(found) = engine_book_move(options, state, board, book) {
+            return Ok(Some(found));
+        }
+    }
+    let found = match options.opponent {
+        Opponent::Random => random::random_move(state, board),
+        Opponent::HumanLike => human_like::human_like_move(state, board, book, options.rating),
+        Opponent::Negamax if options.limit_elo => limited::elo_limited_move(state, board, options.elo, options.hash_mb as usize),
+        Opponent::Negamax => {
+            let level = options.level;
+            if rand::thread_rng().gen_bool(level.blunder_chance()) {
+                return Ok(random::random_move(state, board));
+            }
+            let threads = match options.threads {
+                0 => std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+                threads => threads as usize,
+            };
+            search::scored_best_move_until(state, board, options.depth.min(level.max_depth()), options.hash_mb as usize, threads, level.evaluator(), stop).map(|(mv, _)| mv)
+        }
+        Opponent::MonteCarlo => mcts::mcts_move(state, board, options.playouts),
+        // Started for each move, so that no engine is left running once the user switches opponents
+        Opponent::External => return options.external_engine(options.playing_engine)?.start()?.best_move(state, board, EXTERNAL_MOVE_TIME),
+    };
+    Ok(found)
+}
+
+/// A book move while the game is within its first `book_moves` moves
+fn engine_book_move(options: &ComputerOptions, state: &GameState, board: &Vec<Option<Piece>>, book: Option<&PolyglotBook>) -> Option<ParsedMove> {
+    if state.fullmove_number > options.book_moves {
+        return None;
+    }
+    let moves = book?.moves(state, board);
+    let mut rng = rand::thread_rng();
+    let picked = match options.book_variety {
+        BookVariety::Best => moves.first(),
+        // A book of zero weights only lists moves, so any of them will do
+        BookVariety::Weighted => match WeightedIndex::new(moves.iter().map(|m| m.weight)) {
+            Ok(weights) => moves.get(weights.sample(&mut rng)),
+            Err(_) => moves.choose(&mut rng),
+        },
+        BookVariety::Uniform => moves.choose(&mut rng),
+    }?;
+    Some((picked.from, picked.to, picked.promotion.unwrap_or(PieceType::Queen)))
+}
+
+/// Whether the computer takes a draw offered by the side to move, judged with a shallow search
+pub fn accepts_draw(state: &GameState, board: &Vec<Option<Piece>>) -> bool {
+    search::scored_best_move(state, board, PREDICTION_DEPTH, 16, 1, evaluate)
+        .is_none_or(|(_, score)| -score <= DRAW_ACCEPT_SCORE)
+}
+
+/// The reply the computer expects from its opponent, whose answer it can search while they
+/// think. A shallow search, whatever the opponent, so that the pondering starts right away.
+pub fn predicted_reply(state: &GameState, board: &Vec<Option<Piece>>) -> Option<ParsedMove> {
+    search::best_move(state, board, PREDICTION_DEPTH, 16, 1, evaluate)
+}
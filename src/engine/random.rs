@@ -0,0 +1,11 @@
+use rand::seq::SliceRandom;
+use crate::game::game_state::GameState;
+use crate::game::notation::ParsedMove;
+use crate::pieces::{Piece, PieceType};
+
+/// Picks any legal move with equal probability, promoting to a queen
+pub fn random_move(state: &GameState, board: &Vec<Option<Piece>>) -> Option<ParsedMove> {
+    state.legal_moves(board)
+        .choose(&mut rand::thread_rng())
+        .map(|&(from, to)| (from, to, PieceType::Queen))
+}
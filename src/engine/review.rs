@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use crate::game::annotation::{Evaluation, MoveQuality, PlayerSummary};
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::game::pgn::PgnGame;
+use crate::pieces::{Piece, PieceColor};
+use super::eval::evaluate;
+use super::search::scored_best_move;
+
+/// Plies searched in each position of a reviewed game
+const REVIEW_DEPTH: u32 = 3;
+
+/// Scores are capped at ten pawns, so that a missed mate counts as a large loss but not as an
+/// arbitrary one
+const SCORE_CAP: i32 = 1000;
+
+/// What the review found about a game
+#[derive(Clone, Debug)]
+pub struct Review {
+    pub moves: Vec<ReviewedMove>,
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+/// What the review found about one move
+#[derive(Clone, Debug)]
+pub struct ReviewedMove {
+    pub mover: PieceColor,
+    pub score: i32, // Of the position before the move for the mover, with the best move
+    pub after: Option<i32>, // Of the position after the move for the mover, unless it mates
+    pub loss: i32, // Centipawns the move gave away against the best one
+    pub quality: Option<MoveQuality>, // Inaccuracy, mistake or blunder, when it is one
+    pub better: Option<String>, // The best move in SAN, when another move was played
+}
+
+impl ReviewedMove {
+    /// The score of the position after the move from White's side, as PGN writes evaluations
+    pub fn evaluation(&self) -> Option<Evaluation> {
+        let sign = if self.mover == PieceColor::White { 1 } else { -1 };
+        self.after.map(|after| Evaluation::Centipawns(sign * after))
+    }
+}
+
+/// Searches every position of a game, given from the start to the end, and judges each move by
+/// the centipawns it lost: from 50 an inaccuracy, from 100 a mistake and from 300 a blunder
+pub fn review_game(positions: &[(GameState, Vec<Option<Piece>>)]) -> Review {
+    let moves: Vec<ReviewedMove> = positions.windows(2)
+        .map(|pair| {
+            let ((state, board), (after, after_board)) = (&pair[0], &pair[1]);
+            let mover = state.current_turn;
+            let Some((best, best_score)) = score(state, board, REVIEW_DEPTH) else {
+                return ReviewedMove { mover, score: 0, after: None, loss: 0, quality: None, better: None };
+            };
+            let (from, to, promotion) = best;
+            if Some((from, to)) == after.last_move {
+                let after = (after.status != GameStatus::Checkmate).then_some(best_score);
+                return ReviewedMove { mover, score: best_score, after, loss: 0, quality: None, better: None };
+            }
+
+            // The move played is searched a ply less deep from the position after it, so that both
+            // moves are looked at to the same horizon
+            let played_score = match score(after, after_board, REVIEW_DEPTH - 1) {
+                Some((_, score)) => -score,
+                None if after.status == GameStatus::Checkmate => SCORE_CAP,
+                None => 0,
+            };
+            let loss = (best_score - played_score).max(0);
+            let quality = match loss {
+                300.. => Some(MoveQuality::Blunder),
+                100.. => Some(MoveQuality::Mistake),
+                50.. => Some(MoveQuality::Dubious),
+                _ => None,
+            };
+            let (mut state, mut board) = (state.clone(), board.clone());
+            state.make_move_with_promotion(from, to, promotion, &mut board);
+            let after = (after.status != GameStatus::Checkmate).then_some(played_score);
+            ReviewedMove { mover, score: best_score, after, loss, quality, better: state.san_moves.last().cloned() }
+        })
+        .collect();
+
+    let white = summarize(moves.iter().filter(|reviewed| reviewed.mover == PieceColor::White));
+    let black = summarize(moves.iter().filter(|reviewed| reviewed.mover == PieceColor::Black));
+    Review { moves, white, black }
+}
+
+/// Reviews `games` on `threads` threads at once, each going on with the next game left once it
+/// is done with one, and returns them in order, annotated with the quality of the moves and the
+/// evaluation after each. `progress` is told the games and moves reviewed so far whenever a game
+/// is done.
+pub fn review_games(games: &[PgnGame], threads: usize, mut progress: impl FnMut(usize, usize)) -> Vec<Result<PgnGame, String>> {
+    let mut reviewed: Vec<Option<Result<PgnGame, String>>> = vec![None; games.len()];
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..threads.clamp(1, games.len().max(1)) {
+            let (sender, next) = (sender.clone(), &next);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(game) = games.get(index) else { break };
+                let _ = sender.send((index, annotated(game)));
+            });
+        }
+        // The workers hold the only senders left, so that the loop ends with the last game
+        drop(sender);
+        let (mut done, mut moves) = (0, 0);
+        for (index, result) in receiver {
+            done += 1;
+            moves += games[index].moves.len();
+            reviewed[index] = Some(result);
+            progress(done, moves);
+        }
+    });
+    reviewed.into_iter().map(|result| result.unwrap_or_else(|| Err("not reviewed".to_string()))).collect()
+}
+
+/// A game with the review's findings written into it
+fn annotated(game: &PgnGame) -> Result<PgnGame, String> {
+    let review = review_game(&game.replay_positions()?);
+    let mut game = game.clone();
+    game.qualities.resize(game.moves.len(), None);
+    game.evals.resize(game.moves.len(), None);
+    for (ply, reviewed) in review.moves.iter().enumerate() {
+        game.qualities[ply] = reviewed.quality.or(game.qualities[ply]);
+        game.evals[ply] = reviewed.evaluation().or(game.evals[ply]);
+    }
+    Ok(game)
+}
+
+/// Sums up one side's moves. A move's accuracy falls off with the winning chances it gave away,
+/// as in the usual online reviews, so that losing a pawn matters less in a won position.
+fn summarize<'a>(moves: impl Iterator<Item = &'a ReviewedMove>) -> PlayerSummary {
+    let mut summary = PlayerSummary { accuracy: 0.0, acpl: 0, inaccuracies: 0, mistakes: 0, blunders: 0 };
+    let (mut count, mut total_loss) = (0, 0);
+    for reviewed in moves {
+        count += 1;
+        total_loss += reviewed.loss;
+        let lost_chances = winning_chances(reviewed.score) - winning_chances(reviewed.score - reviewed.loss);
+        summary.accuracy += (103.1668 * (-0.04354 * lost_chances).exp() - 3.1669).clamp(0.0, 100.0);
+        match reviewed.quality {
+            Some(MoveQuality::Dubious) => summary.inaccuracies += 1,
+            Some(MoveQuality::Mistake) => summary.mistakes += 1,
+            Some(MoveQuality::Blunder) => summary.blunders += 1,
+            _ => {}
+        }
+    }
+    if count > 0 {
+        summary.accuracy /= count as f64;
+        summary.acpl = (total_loss / count) as u32;
+    } else {
+        summary.accuracy = 100.0;
+    }
+    summary
+}
+
+/// Chances in percent of winning a position with the score `centipawns`, draws counting half
+fn winning_chances(centipawns: i32) -> f64 {
+    100.0 / (1.0 + (-0.00368208 * centipawns as f64).exp())
+}
+
+/// The best move of a position and its capped score for the side to move, `None` once the game
+/// is over
+fn score(state: &GameState, board: &Vec<Option<Piece>>, depth: u32) -> Option<(ParsedMove, i32)> {
+    scored_best_move(state, board, depth, 16, 1, evaluate).map(|(best, score)| (best, score.clamp(-SCORE_CAP, SCORE_CAP)))
+}
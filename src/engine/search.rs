@@ -0,0 +1,435 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::book::polyglot::polyglot_key;
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::game::threats::piece_value;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::eval::{evaluate, Evaluator};
+use super::tt::{Bound, Entry, TranspositionTable};
+
+/// Score of being mated now; mates further away score closer to zero so the shortest is preferred
+const MATE: i32 = 100_000;
+
+/// Scores beyond this are mates, whose distance is counted from the root
+const MATE_BOUND: i32 = MATE - 1000;
+
+// Move ordering ranks: the hash move, then captures, then killer moves, then the other quiet
+// moves by their history score, which stays below these
+const HASH_MOVE: i32 = 3_000_000;
+const CAPTURE: i32 = 2_000_000;
+const KILLER: i32 = 1_000_000;
+
+type Move = ((usize, usize), (usize, usize));
+
+/// Finds the best move by a negamax search with alpha-beta pruning, `depth` plies deep, using
+/// a transposition table of `hash_mb` megabytes. Quiet positions are judged by `evaluate`.
+///
+/// With several `threads`, helper threads run the same search alongside (Lazy SMP). They share
+/// the table, so the positions they settle save the main search the work; only its move counts.
+pub fn best_move(state: &GameState, board: &Vec<Option<Piece>>, depth: u32, hash_mb: usize, threads: usize, evaluate: Evaluator) -> Option<ParsedMove> {
+    scored_best_move(state, board, depth, hash_mb, threads, evaluate).map(|(mv, _)| mv)
+}
+
+/// The best move as `best_move` finds it, with its score for the side to move
+pub fn scored_best_move(state: &GameState, board: &Vec<Option<Piece>>, depth: u32, hash_mb: usize, threads: usize, evaluate: Evaluator) -> Option<(ParsedMove, i32)> {
+    search(state, board, depth, hash_mb, threads, evaluate, &AtomicBool::new(false)).0
+}
+
+/// The best move and its score as `scored_best_move` finds them, or those of the last depth
+/// completed once `stop` is set from another thread. The search sets `stop` itself when it is done.
+pub fn scored_best_move_until(state: &GameState, board: &Vec<Option<Piece>>, depth: u32, hash_mb: usize, threads: usize, evaluate: Evaluator, stop: &AtomicBool) -> Option<(ParsedMove, i32)> {
+    search(state, board, depth, hash_mb, threads, evaluate, stop).0
+}
+
+/// Positions searched on a single thread to find the best move, to measure the speed of the
+/// search and the move generator
+pub fn count_nodes(state: &GameState, board: &Vec<Option<Piece>>, depth: u32, hash_mb: usize) -> u64 {
+    search(state, board, depth, hash_mb, 1, evaluate, &AtomicBool::new(false)).1
+}
+
+/// Every legal move with its score for the side to move, each searched with a full window so
+/// that the scores can be compared. Iterative deepening goes on until `max_depth`, or stops once
+/// `max_nodes` positions are searched, and the scores of the last depth completed are returned.
+pub fn scored_moves(state: &GameState, board: &Vec<Option<Piece>>, max_depth: u32, max_nodes: u64, hash_mb: usize) -> Vec<(ParsedMove, i32)> {
+    let table = TranspositionTable::new(hash_mb);
+    let stop = AtomicBool::new(false);
+    let mut search = Search::new(&table, &stop, evaluate);
+    let key = polyglot_key(state, board);
+    let mut scored = Vec::new();
+    for depth in 1..=max_depth.max(1) {
+        // The first depth always completes, so that there is something to return
+        search.max_nodes = if depth == 1 { u64::MAX } else { max_nodes };
+        let mut scores = Vec::new();
+        for (from, to) in search.ordered(state.legal_moves(board), board, table.probe(key), 0) {
+            let (child, child_board) = play(state, board, from, to);
+            let score = -search.negamax(&child, &child_board, depth - 1, 1, -MATE - 1, MATE + 1);
+            scores.push(((from, to, PieceType::Queen), score));
+        }
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        scored = scores;
+    }
+    scored
+}
+
+/// Deepest analysis; far more than it can reach in practice
+const MAX_ANALYSIS_DEPTH: u32 = 64;
+
+/// What the analysis found at one depth
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Analysis {
+    pub depth: u32,
+    pub nodes: u64, // Positions searched since the analysis started
+    pub elapsed: Duration,
+    pub lines: Vec<AnalysisLine>, // Best first
+}
+
+impl Analysis {
+    pub fn nodes_per_second(&self) -> u64 {
+        (self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-3)) as u64
+    }
+}
+
+/// One of the best moves with the line expected to follow it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnalysisLine {
+    pub first: ParsedMove,
+    pub score: i32, // From White's side, in centipawns unless it is beyond `MATE_BOUND`
+    pub moves: Vec<String>, // In SAN, starting with `first`
+}
+
+impl AnalysisLine {
+    /// The score as shown to the user, e.g. "+0.35", or "#3" when White mates in 3 moves
+    pub fn score_text(&self) -> String {
+        if self.score.abs() > MATE_BOUND {
+            let moves = (MATE - self.score.abs() + 1) / 2;
+            format!("#{}{}", if self.score < 0 { "-" } else { "" }, moves)
+        } else {
+            format!("{:+.2}", self.score as f64 / 100.0)
+        }
+    }
+}
+
+/// Score of mating in `moves` moves for the side to move, or of being mated in as many when
+/// negative, on the scale of the search's scores
+pub fn mate_score(moves: i32) -> i32 {
+    if moves > 0 {
+        MATE - (2 * moves - 1)
+    } else {
+        -MATE - 2 * moves
+    }
+}
+
+/// Searches the position deeper and deeper until `stop` is set, reporting the best `lines` moves
+/// at each depth completed (multi-PV): each is searched with the ones found before it left out
+pub fn analyse(state: &GameState, board: &Vec<Option<Piece>>, lines: usize, hash_mb: usize, stop: &AtomicBool, mut report: impl FnMut(Analysis)) {
+    let started = Instant::now();
+    let table = TranspositionTable::new(hash_mb);
+    let mut search = Search::new(&table, stop, evaluate);
+    let sign = if state.current_turn == PieceColor::White { 1 } else { -1 };
+    for depth in 1..=MAX_ANALYSIS_DEPTH {
+        let mut found: Vec<AnalysisLine> = Vec::new();
+        while found.len() < lines.max(1) {
+            let excluded: Vec<Move> = found.iter().map(|line| (line.first.0, line.first.1)).collect();
+            let Some((first, score)) = search.root(state, board, depth, &excluded) else { break };
+            let (child, child_board) = play(state, board, first.0, first.1);
+            let mut moves: Vec<String> = child.san_moves.last().cloned().into_iter().collect();
+            moves.extend(principal_variation(&table, &child, &child_board, depth - 1));
+            found.push(AnalysisLine { first, score: sign * score, moves });
+        }
+        if found.is_empty() || stop.load(Ordering::Relaxed) {
+            return;
+        }
+        report(Analysis { depth, nodes: search.nodes, elapsed: started.elapsed(), lines: found });
+    }
+}
+
+/// The line both sides are expected to play, following the best moves left in the table
+fn principal_variation(table: &TranspositionTable, state: &GameState, board: &[Option<Piece>], depth: u32) -> Vec<String> {
+    let (mut state, mut board) = (state.clone(), board.to_vec());
+    let mut line = Vec::new();
+    while line.len() < depth as usize {
+        let Some((from, to)) = table.probe(polyglot_key(&state, &board)).and_then(|entry| entry.best) else { break };
+        let from = (from as usize / 8, from as usize % 8);
+        let to = (to as usize / 8, to as usize % 8);
+        // The table can hold a move of another position with the same key
+        if !state.make_move(from, to, &mut board) {
+            break;
+        }
+        line.extend(state.san_moves.last().cloned());
+    }
+    line
+}
+
+/// The best move with its score and the positions the main thread searched for it
+fn search(state: &GameState, board: &Vec<Option<Piece>>, depth: u32, hash_mb: usize, threads: usize, evaluate: Evaluator, stop: &AtomicBool) -> (Option<(ParsedMove, i32)>, u64) {
+    let table = TranspositionTable::new(hash_mb);
+    let depth = depth.max(1);
+
+    thread::scope(|scope| {
+        for helper in 1..threads {
+            let mut search = Search::new(&table, stop, evaluate);
+            // Every other helper aims a ply deeper, so that the threads spread over more of the tree
+            let helper_depth = depth + (helper % 2) as u32;
+            scope.spawn(move || {
+                for depth in 1..=helper_depth {
+                    search.root(state, board, depth, &[]);
+                }
+            });
+        }
+
+        // Iterative deepening: each iteration leaves best moves in the table, killers and history
+        // that make the next, deeper one cut off sooner
+        let mut search = Search::new(&table, stop, evaluate);
+        let mut best = None;
+        for depth in 1..=depth {
+            let found = search.root(state, board, depth, &[]);
+            // A depth cut short by the caller has not looked at every move
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            best = found.or(best);
+        }
+        stop.store(true, Ordering::Relaxed);
+        (best, search.nodes)
+    })
+}
+
+/// What a search thread learns while running that orders the moves of later nodes
+struct Search<'a> {
+    table: &'a TranspositionTable, // Shared by all threads
+    stop: &'a AtomicBool, // Set to give up: when the main thread is done, or the analysis or search is given up
+    killers: Vec<[Option<Move>; 2]>, // Last two quiet moves that caused a cutoff at each ply
+    history: Vec<i32>, // How well quiet moves did in cutoffs, indexed by from * 64 + to
+    nodes: u64, // Positions searched
+    max_nodes: u64, // The search stops once this many positions are searched
+    evaluate: Evaluator,
+}
+
+impl<'a> Search<'a> {
+    fn new(table: &'a TranspositionTable, stop: &'a AtomicBool, evaluate: Evaluator) -> Self {
+        Self { table, stop, killers: Vec::new(), history: vec![0; 64 * 64], nodes: 0, max_nodes: u64::MAX, evaluate }
+    }
+
+    /// The best move but the `excluded` ones, and its score for the side to move
+    fn root(&mut self, state: &GameState, board: &Vec<Option<Piece>>, depth: u32, excluded: &[Move]) -> Option<(ParsedMove, i32)> {
+        let key = polyglot_key(state, board);
+        let mut best = None;
+        let mut alpha = -MATE - 1;
+        let moves = state.legal_moves(board).into_iter().filter(|mv| !excluded.contains(mv)).collect();
+        for (from, to) in self.ordered(moves, board, self.table.probe(key), 0) {
+            // Deeper in the tree pawns only promote to queens, but here every piece is tried
+            for &promotion in promotions(board, from, to) {
+                let (child, child_board) = play_promoting(state, board, from, to, promotion);
+                let score = -self.negamax(&child, &child_board, depth - 1, 1, -MATE - 1, -alpha);
+                if best.is_none() || score > alpha {
+                    alpha = score;
+                    best = Some((from, to, promotion));
+                }
+            }
+        }
+
+        let (from, to, promotion) = best?;
+        // The best move of the position is not among the lines searched without it, and a
+        // stopped search scores nothing
+        if excluded.is_empty() && !self.stop.load(Ordering::Relaxed) {
+            self.table.store(Entry { key, depth, score: alpha, bound: Bound::Exact, best: Some(compact((from, to))) });
+        }
+        Some(((from, to, promotion), alpha))
+    }
+
+    /// Score of the position for the side to move; `ply` is the distance from the root
+    fn negamax(&mut self, state: &GameState, board: &Vec<Option<Piece>>, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        if self.nodes >= self.max_nodes {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        // Scores of a stopped search are never used, and could spoil the table
+        if self.stop.load(Ordering::Relaxed) {
+            return 0;
+        }
+        match state.status {
+            GameStatus::Checkmate => return -MATE + ply,
+            GameStatus::Stalemate => return 0,
+            _ => {}
+        }
+        if depth == 0 {
+            return self.quiesce(state, board, ply, alpha, beta);
+        }
+        self.nodes += 1;
+
+        // A search at least as deep of the same position may settle it already
+        let key = polyglot_key(state, board);
+        let entry = self.table.probe(key);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            let score = from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return beta,
+                Bound::Upper if score <= alpha => return alpha,
+                _ => {}
+            }
+        }
+
+        let mut best = None;
+        for (from, to) in self.ordered(state.legal_moves(board), board, entry, ply) {
+            let (child, child_board) = play(state, board, from, to);
+            let score = -self.negamax(&child, &child_board, depth - 1, ply + 1, -beta, -alpha);
+            if self.stop.load(Ordering::Relaxed) {
+                return 0;
+            }
+            if score >= beta {
+                if captured_value(board, (from, to)).is_none() {
+                    self.remember_cutoff((from, to), depth, ply);
+                }
+                self.table.store(Entry { key, depth, score: to_table(beta, ply), bound: Bound::Lower, best: Some(compact((from, to))) });
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best = Some(compact((from, to)));
+            }
+        }
+
+        // Without a move raising alpha, every move was refuted and alpha only bounds the score
+        let bound = if best.is_some() { Bound::Exact } else { Bound::Upper };
+        self.table.store(Entry { key, depth, score: to_table(alpha, ply), bound, best });
+        alpha
+    }
+
+    /// Notes a quiet move that refuted the position, to try it early in sibling positions
+    /// (killer moves) and wherever it is legal (history)
+    fn remember_cutoff(&mut self, mv: Move, depth: u32, ply: i32) {
+        let ply = ply as usize;
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        let (from, to) = compact(mv);
+        self.history[from as usize * 64 + to as usize] += (depth * depth) as i32;
+    }
+
+    /// The moves most likely to cause a cutoff first: the best move from the table, captures of
+    /// the most valuable victims by the least valuable attackers, killer moves, then by history
+    fn ordered(&self, mut moves: Vec<Move>, board: &[Option<Piece>], entry: Option<Entry>, ply: i32) -> Vec<Move> {
+        let hash_move = entry.and_then(|entry| entry.best);
+        let killers = self.killers.get(ply as usize).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|&mv| {
+            let (from, to) = compact(mv);
+            let rank = if hash_move == Some((from, to)) {
+                HASH_MOVE
+            } else if let Some(victim) = captured_value(board, mv) {
+                CAPTURE + mvv_lva(board, mv, victim)
+            } else if killers.contains(&Some(mv)) {
+                KILLER
+            } else {
+                self.history[from as usize * 64 + to as usize].min(KILLER - 1)
+            };
+            -rank
+        });
+        moves
+    }
+
+    /// Searches captures only until the position is quiet, so that the evaluation is not taken
+    /// in the middle of an exchange, e.g. right after winning a pawn defended by another
+    fn quiesce(&mut self, state: &GameState, board: &Vec<Option<Piece>>, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        match state.status {
+            GameStatus::Checkmate => return -MATE + ply,
+            GameStatus::Stalemate => return 0,
+            _ => {}
+        }
+
+        // Standing pat: the side to move need not capture, so the static score is a lower bound
+        let score = (self.evaluate)(board);
+        let stand_pat = if state.current_turn == PieceColor::White { score } else { -score };
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut captures: Vec<(Move, i32)> = state.legal_moves(board).into_iter()
+            .filter_map(|mv| captured_value(board, mv).map(|victim| (mv, mvv_lva(board, mv, victim))))
+            .collect();
+        captures.sort_by_key(|&(_, rank)| -rank);
+        for ((from, to), _) in captures {
+            let (child, child_board) = play(state, board, from, to);
+            let score = -self.quiesce(&child, &child_board, ply + 1, -beta, -alpha);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/// Value of the piece the move takes, en passant included, or `None` for a quiet move
+fn captured_value(board: &[Option<Piece>], (from, to): Move) -> Option<i32> {
+    match board[to.0 * 8 + to.1] {
+        Some(victim) => Some(piece_value(victim.piece_type)),
+        None if board[from.0 * 8 + from.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn) && from.1 != to.1 => Some(piece_value(PieceType::Pawn)),
+        None => None,
+    }
+}
+
+/// Most valuable victim, least valuable attacker: ranks captures by what they take first and
+/// by what they risk second
+fn mvv_lva(board: &[Option<Piece>], (from, _): Move, victim: i32) -> i32 {
+    let attacker = board[from.0 * 8 + from.1].map_or(0, |piece| piece_value(piece.piece_type));
+    victim * 1000 - attacker
+}
+
+fn play(state: &GameState, board: &[Option<Piece>], from: (usize, usize), to: (usize, usize)) -> (GameState, Vec<Option<Piece>>) {
+    play_promoting(state, board, from, to, PieceType::Queen)
+}
+
+fn play_promoting(state: &GameState, board: &[Option<Piece>], from: (usize, usize), to: (usize, usize), promotion: PieceType) -> (GameState, Vec<Option<Piece>>) {
+    let mut child = state.clone();
+    let mut child_board = board.to_vec();
+    child.make_move_with_promotion(from, to, promotion, &mut child_board);
+    (child, child_board)
+}
+
+/// The pieces a move can promote to, best first; just the queen for a move that does not promote
+fn promotions(board: &[Option<Piece>], from: (usize, usize), to: (usize, usize)) -> &'static [PieceType] {
+    let pawn = board[from.0 * 8 + from.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+    if pawn && (to.0 == 0 || to.0 == 7) {
+        &[PieceType::Queen, PieceType::Knight, PieceType::Rook, PieceType::Bishop]
+    } else {
+        &[PieceType::Queen]
+    }
+}
+
+fn compact((from, to): Move) -> (u8, u8) {
+    ((from.0 * 8 + from.1) as u8, (to.0 * 8 + to.1) as u8)
+}
+
+/// Mate scores count plies from the root; the table stores them from the position instead,
+/// since it can be reached at other distances from the root
+fn to_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply
+    } else if score < -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply
+    } else if score < -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
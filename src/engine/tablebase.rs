@@ -0,0 +1,112 @@
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess};
+use crate::game::fen::to_fen;
+use crate::game::game_state::{GameState, GameStatus};
+use crate::game::notation::ParsedMove;
+use crate::pieces::{Piece, PieceType};
+
+/// Plies without a capture or pawn move after which the fifty-move rule draws the game
+const FIFTY_MOVES: u32 = 100;
+
+/// Outcome of a position with perfect play, for the side to move. Wins and losses carry the
+/// distance to zeroing (DTZ): the plies until the next capture or pawn move that keeps the result.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TablebaseResult {
+    Win(u32),
+    Draw,
+    Loss(u32),
+}
+
+impl TablebaseResult {
+    pub fn text(self) -> String {
+        match self {
+            TablebaseResult::Win(dtz) => format!("win in {}", dtz),
+            TablebaseResult::Draw => "draw".to_string(),
+            TablebaseResult::Loss(dtz) => format!("loss in {}", dtz),
+        }
+    }
+
+    /// The same outcome for the other side
+    pub fn reversed(self) -> Self {
+        match self {
+            TablebaseResult::Win(dtz) => TablebaseResult::Loss(dtz),
+            TablebaseResult::Draw => TablebaseResult::Draw,
+            TablebaseResult::Loss(dtz) => TablebaseResult::Win(dtz),
+        }
+    }
+}
+
+/// Syzygy endgame tablebases, probed for the exact outcome of positions with few pieces
+pub struct Tablebase {
+    tables: shakmaty_syzygy::Tablebase<Chess>,
+}
+
+impl Tablebase {
+    /// Opens the tables in `folder`, failing when there are none
+    pub fn open(folder: &str) -> Result<Self, String> {
+        let mut tables = shakmaty_syzygy::Tablebase::new();
+        let found = tables.add_directory(folder).map_err(|e| format!("cannot read {}: {}", folder, e))?;
+        if found == 0 {
+            return Err(format!("no Syzygy tables in {}", folder));
+        }
+        Ok(Self { tables })
+    }
+
+    /// The outcome for the side to move, or `None` when the position has more pieces than the
+    /// tables, castling rights or a material balance whose table is missing
+    pub fn probe(&self, state: &GameState, board: &[Option<Piece>]) -> Option<TablebaseResult> {
+        if board.iter().flatten().count() > self.tables.max_pieces() {
+            return None;
+        }
+        let fen: Fen = to_fen(state, board).parse().ok()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard).ok()?;
+        let dtz = self.tables.probe_dtz(&position).ok()?.ignore_rounding().0;
+        Some(match dtz.unsigned_abs() {
+            0 => TablebaseResult::Draw,
+            // A win that cannot zero before the fifty-move rule runs out is drawn by it
+            plies if plies + state.halfmove_clock > FIFTY_MOVES => TablebaseResult::Draw,
+            plies if dtz > 0 => TablebaseResult::Win(plies),
+            plies => TablebaseResult::Loss(plies),
+        })
+    }
+    /// The move the tablebase plays: a mate, else the fastest win, zeroing the fifty-move count
+    /// when it can; a draw when there is no win; the slowest loss otherwise. `None` when the
+    /// tablebase does not know the outcome of every move.
+    pub fn best_move(&self, state: &GameState, board: &Vec<Option<Piece>>) -> Option<ParsedMove> {
+        let mut best = None;
+        for (from, to) in state.legal_moves(board) {
+            let (mut child, mut child_board) = (state.clone(), board.clone());
+            child.make_move_with_promotion(from, to, PieceType::Queen, &mut child_board);
+            let rank = match child.status {
+                GameStatus::Checkmate => (0, 0),
+                _ => match self.probe(&child, &child_board)?.reversed() {
+                    TablebaseResult::Win(_) if child.halfmove_clock == 0 => (1, 0),
+                    TablebaseResult::Win(dtz) => (2, dtz),
+                    TablebaseResult::Draw => (3, 0),
+                    TablebaseResult::Loss(dtz) => (4, u32::MAX - dtz),
+                },
+            };
+            if best.is_none_or(|(best_rank, _)| rank < best_rank) {
+                best = Some((rank, (from, to, PieceType::Queen)));
+            }
+        }
+        best.map(|(_, mv)| mv)
+    }
+
+    /// The tablebase's best move with the line of at most `plies` moves in SAN, that move first,
+    /// expected to follow it with perfect play
+    pub fn line(&self, state: &GameState, board: &Vec<Option<Piece>>, plies: usize) -> Option<(ParsedMove, Vec<String>)> {
+        let first = self.best_move(state, board)?;
+        let (mut state, mut board) = (state.clone(), board.clone());
+        let mut moves = Vec::new();
+        let mut next = Some(first);
+        while let Some((from, to, promotion)) = next.filter(|_| moves.len() < plies) {
+            if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+                break;
+            }
+            moves.extend(state.san_moves.last().cloned());
+            next = if state.is_over() { None } else { self.best_move(&state, &board) };
+        }
+        Some((first, moves))
+    }
+}
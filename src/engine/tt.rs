@@ -0,0 +1,85 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How a stored score relates to the true score of the position
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // The search failed high, the position is worth at least this
+    Upper, // The search failed low, the position is worth at most this
+}
+
+/// What a search of a position found
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub key: u64, // Zobrist hash of the position, telling apart positions sharing a slot
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best: Option<(u8, u8)>, // From and to square indexes
+}
+
+impl Entry {
+    /// Packs everything but the key in 64 bits: score, depth, bound and best move. The bound
+    /// is never zero, so an empty slot never reads as an entry.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let best = match self.best {
+            Some((from, to)) => 1 << 12 | (from as u64) << 6 | to as u64,
+            None => 0,
+        };
+        (self.score as u32 as u64) << 32 | (self.depth.min(255) as u64) << 24 | bound << 16 | best
+    }
+
+    fn unpack(key: u64, data: u64) -> Self {
+        let bound = match data >> 16 & 0xff {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best = (data & 1 << 12 != 0).then_some(((data >> 6 & 0x3f) as u8, (data & 0x3f) as u8));
+        Self { key, depth: (data >> 24 & 0xff) as u32, score: (data >> 32) as u32 as i32, bound, best }
+    }
+}
+
+/// Fixed-size hash table of searched positions, so that positions reached again by another
+/// move order are not searched twice and the best move found earlier is tried first.
+///
+/// Search threads share it without locking: a slot holds the packed entry and the key XORed
+/// with it, so an entry torn by two threads writing at once no longer matches its key and is
+/// ignored.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>, // Key XOR data, data
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let len = (size_mb * 1024 * 1024 / size_of::<[AtomicU64; 2]>()).max(1);
+        Self { slots: (0..len).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect() }
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let [check, data] = self.slot(key);
+        let (check, data) = (check.load(Ordering::Relaxed), data.load(Ordering::Relaxed));
+        (data != 0 && check ^ data == key).then(|| Entry::unpack(key, data))
+    }
+
+    /// Stores the entry in its slot, unless a deeper search of the same position is there
+    pub fn store(&self, entry: Entry) {
+        if self.probe(entry.key).is_some_and(|old| old.depth > entry.depth) {
+            return;
+        }
+        let [check, data] = self.slot(entry.key);
+        let packed = entry.pack();
+        check.store(entry.key ^ packed, Ordering::Relaxed);
+        data.store(packed, Ordering::Relaxed);
+    }
+}
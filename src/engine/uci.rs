@@ -0,0 +1,293 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::book::polyglot::polyglot_key;
+use crate::game::fen::{square_name, to_fen};
+use crate::game::game_state::GameState;
+use crate::game::notation::{parse_uci_move, piece_letter, ParsedMove};
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::search::{mate_score, Analysis, AnalysisLine};
+
+/// How long the engine may take to answer `uci`, `isready` or `stop` before it is given up on
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running analysis checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An external engine such as Stockfish, driven through the Universal Chess Interface
+pub struct UciEngine {
+    process: Child,
+    input: ChildStdin,
+    output: Receiver<String>, // Lines the engine prints, read on another thread so that waiting can time out
+    pub name: String, // As the engine introduces itself
+    expected_reply: Option<String>, // Reply the engine expects to its last move, as it named it after `bestmove`
+    chess960: bool, // Whether the engine was told to castle as in Chess960, the king taking its own rook
+}
+
+impl UciEngine {
+    /// Starts the engine at `path` and waits until it is ready
+    pub fn start(path: &str) -> Result<Self, String> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("cannot start {}: {}", path, e))?;
+        let input = process.stdin.take().expect("stdin is piped");
+        let stdout = process.stdout.take().expect("stdout is piped");
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self { process, input, output, name: path.to_string(), expected_reply: None, chess960: false };
+        engine.send("uci")?;
+        loop {
+            let line = engine.receive(ANSWER_TIMEOUT)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    pub fn wait_ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        while self.receive(ANSWER_TIMEOUT)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /// The engine's move after thinking for `movetime`, or `None` when it has no legal move
+    pub fn best_move(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<ParsedMove>, String> {
+        Ok(self.scored_best_move(state, board, movetime)?.map(|(mv, _)| mv))
+    }
+
+    /// The engine's move as `best_move` gives it, with the last score it reported for the side
+    /// to move, if any
+    pub fn scored_best_move(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<(ParsedMove, Option<i32>)>, String> {
+        self.position(state, board)?;
+        self.send(&format!("go movetime {}", movetime.as_millis()))?;
+        self.read_best_move(state, board, movetime)
+    }
+
+    /// Starts thinking on the opponent's time about the reply the engine expects to its last
+    /// move, searching for `movetime` once `ponder_hit` tells it the reply was played. `state` is
+    /// the position after the engine's move. Returns the key of the position pondered, or `None`
+    /// when the engine named no legal reply and does not ponder.
+    pub fn ponder(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<u64>, String> {
+        let expected = self.expected_reply.take().and_then(|uci| parse_uci_move(state, board, &uci));
+        let Some((from, to, promotion)) = expected else { return Ok(None) };
+        let (mut state, mut board) = (state.clone(), board.clone());
+        if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+            return Ok(None);
+        }
+        self.position(&state, &board)?;
+        self.send(&format!("go ponder movetime {}", movetime.as_millis()))?;
+        Ok(Some(polyglot_key(&state, &board)))
+    }
+
+    /// The opponent played the reply pondered: the engine goes on with a normal search, and its
+    /// move is returned as `scored_best_move` returns it
+    pub fn ponder_hit(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<(ParsedMove, Option<i32>)>, String> {
+        self.send("ponderhit")?;
+        self.read_best_move(state, board, movetime)
+    }
+
+    /// The opponent played another reply than the one pondered, or the game is over: the search
+    /// is stopped and its move dropped
+    pub fn stop_pondering(&mut self) -> Result<(), String> {
+        self.send("stop")?;
+        while !self.receive(ANSWER_TIMEOUT)?.starts_with("bestmove") {}
+        Ok(())
+    }
+
+    /// Reads the engine's output until its move, keeping the last score it reported and the reply
+    /// it expects
+    fn read_best_move(&mut self, state: &GameState, board: &Vec<Option<Piece>>, movetime: Duration) -> Result<Option<(ParsedMove, Option<i32>)>, String> {
+        let mut score = None;
+        loop {
+            let line = self.receive(movetime + ANSWER_TIMEOUT)?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.first() == Some(&"info") {
+                score = parse_score(&tokens).or(score);
+            } else if tokens.first() == Some(&"bestmove") {
+                // e.g. "bestmove e2e4 ponder e7e5"
+                self.expected_reply = tokens.get(3).filter(|_| tokens.get(2) == Some(&"ponder")).map(|uci| uci.to_string());
+                // "(none)" or "0000" when there is no move
+                let uci = tokens.get(1).copied().unwrap_or_default();
+                return Ok(parse_uci_move(state, board, uci).map(|mv| (mv, score)));
+            }
+        }
+    }
+
+    /// Tells the engine that the next position is from another game
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    /// Analyses the position until `stop` is set, reporting the best `lines` moves each time the
+    /// engine has sent all of them for a new depth
+    pub fn analyse(&mut self, state: &GameState, board: &Vec<Option<Piece>>, lines: usize, stop: &AtomicBool, mut report: impl FnMut(Analysis)) -> Result<(), String> {
+        let lines = lines.max(1).min(state.legal_moves(board).len());
+        if lines == 0 {
+            return Ok(());
+        }
+        self.set_option("MultiPV", &lines.to_string())?;
+        self.position(state, board)?;
+        self.send("go infinite")?;
+
+        let mut found: Vec<Option<AnalysisLine>> = vec![None; lines];
+        while !stop.load(Ordering::Relaxed) {
+            let line = match self.output.recv_timeout(POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(format!("{} has quit", self.name)),
+            };
+            let Some(info) = parse_info(state, board, &line) else { continue };
+            if let Some(slot) = found.get_mut(info.multipv - 1) {
+                *slot = Some(info.line);
+            }
+            if info.multipv == lines && found.iter().all(Option::is_some) {
+                report(Analysis {
+                    depth: info.depth,
+                    nodes: info.nodes,
+                    elapsed: info.elapsed,
+                    lines: found.iter().flatten().cloned().collect(),
+                });
+            }
+        }
+
+        self.send("stop")?;
+        while !self.receive(ANSWER_TIMEOUT)?.starts_with("bestmove") {}
+        Ok(())
+    }
+
+    /// Sets up the position as the game's start and the moves played since, so that the engine
+    /// knows which positions were seen already and avoids or seeks repeating them
+    fn position(&mut self, state: &GameState, board: &[Option<Piece>]) -> Result<(), String> {
+        let chess960 = state.castling_files.is_some();
+        if chess960 != self.chess960 {
+            self.set_option("UCI_Chess960", if chess960 { "true" } else { "false" })?;
+            self.chess960 = chess960;
+        }
+        // A game saved without the moves to take back only has its current position
+        if state.undo_stack.len() != state.san_moves.len() {
+            return self.send(&format!("position fen {}", to_fen(state, board)));
+        }
+        let mut command = match &state.start_fen {
+            Some(fen) => format!("position fen {}", fen),
+            None => "position startpos".to_string(),
+        };
+        if !state.undo_stack.is_empty() {
+            command.push_str(" moves");
+        }
+        for undo in &state.undo_stack {
+            // Castling is already written as the king's move, or in Chess960 as it taking its rook
+            command.push_str(&format!(" {}{}", square_name(undo.from), square_name(undo.to)));
+            if undo.moved.piece_type == PieceType::Pawn && (undo.to.0 == 0 || undo.to.0 == 7) {
+                command.push(piece_letter(undo.promotion).to_ascii_lowercase());
+            }
+        }
+        self.send(&command)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", command)
+            .and_then(|_| self.input.flush())
+            .map_err(|e| format!("cannot write to {}: {}", self.name, e))
+    }
+
+    /// The next line the engine prints, waiting at most `timeout`
+    fn receive(&self, timeout: Duration) -> Result<String, String> {
+        self.output.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => format!("{} does not answer", self.name),
+            RecvTimeoutError::Disconnected => format!("{} has quit", self.name),
+        })
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // An engine ignoring `quit` must not be left running in the background
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while matches!(self.process.try_wait(), Ok(None)) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// A line of an `info` message, e.g. `info depth 12 multipv 1 score cp 31 nodes 52000 time 80 pv e2e4 e7e5`
+struct Info {
+    depth: u32,
+    multipv: usize,
+    nodes: u64,
+    elapsed: Duration,
+    line: AnalysisLine,
+}
+
+/// Reads an `info` message carrying a principal variation; others, like `info string`, give `None`
+fn parse_info(state: &GameState, board: &[Option<Piece>], message: &str) -> Option<Info> {
+    let tokens: Vec<&str> = message.split_whitespace().collect();
+    if tokens.first() != Some(&"info") {
+        return None;
+    }
+    let number = |name: &str| {
+        let at = tokens.iter().position(|&token| token == name)?;
+        tokens.get(at + 1)?.parse::<u64>().ok()
+    };
+
+    let score = parse_score(&tokens)?;
+    let sign = if state.current_turn == PieceColor::White { 1 } else { -1 };
+
+    // The PV is given in UCI notation; it is replayed to write it in SAN
+    let pv_at = tokens.iter().position(|&token| token == "pv")?;
+    let (mut state, mut board) = (state.clone(), board.to_vec());
+    let mut first = None;
+    let mut moves = Vec::new();
+    for uci in &tokens[pv_at + 1..] {
+        let Some((from, to, promotion)) = parse_uci_move(&state, &board, uci) else { break };
+        if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+            break;
+        }
+        first.get_or_insert((from, to, promotion));
+        moves.extend(state.san_moves.last().cloned());
+    }
+
+    Some(Info {
+        depth: number("depth").unwrap_or(0) as u32,
+        multipv: number("multipv").unwrap_or(1).max(1) as usize,
+        nodes: number("nodes").unwrap_or(0),
+        elapsed: Duration::from_millis(number("time").unwrap_or(0)),
+        line: AnalysisLine { first: first?, score: sign * score, moves },
+    })
+}
+
+/// The score of an `info` message for the side to move, given in centipawns or as moves to mate
+fn parse_score(tokens: &[&str]) -> Option<i32> {
+    let score_at = tokens.iter().position(|&token| token == "score")?;
+    let amount: i32 = tokens.get(score_at + 2)?.parse().ok()?;
+    match tokens[score_at + 1] {
+        "cp" => Some(amount),
+        "mate" => Some(mate_score(amount)),
+        _ => None,
+    }
+}
@@ -0,0 +1,180 @@
+use druid::im::Vector;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use super::fen::{parse_square, square_name};
+
+/// Move assessment from review or analysis, stored in PGN as the NAGs `$1` to `$6`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum MoveQuality {
+    Brilliant,
+    Good,
+    Interesting,
+    Dubious,
+    Mistake,
+    Blunder,
+}
+
+impl MoveQuality {
+    pub fn from_nag(nag: u32) -> Option<Self> {
+        match nag {
+            1 => Some(MoveQuality::Good),
+            2 => Some(MoveQuality::Mistake),
+            3 => Some(MoveQuality::Brilliant),
+            4 => Some(MoveQuality::Blunder),
+            5 => Some(MoveQuality::Interesting),
+            6 => Some(MoveQuality::Dubious),
+            _ => None,
+        }
+    }
+
+    pub fn nag(self) -> u32 {
+        match self {
+            MoveQuality::Good => 1,
+            MoveQuality::Mistake => 2,
+            MoveQuality::Brilliant => 3,
+            MoveQuality::Blunder => 4,
+            MoveQuality::Interesting => 5,
+            MoveQuality::Dubious => 6,
+        }
+    }
+
+    pub fn glyph(self) -> &'static str {
+        match self {
+            MoveQuality::Brilliant => "!!",
+            MoveQuality::Good => "!",
+            MoveQuality::Interesting => "!?",
+            MoveQuality::Dubious => "?!",
+            MoveQuality::Mistake => "?",
+            MoveQuality::Blunder => "??",
+        }
+    }
+}
+
+/// Evaluation of the position after a move from White's side, stored in PGN as `[%eval 0.43]` or
+/// `[%eval #-3]`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum Evaluation {
+    Centipawns(i32),
+    Mate(i32), // Moves to mate, negative when Black mates
+}
+
+impl Evaluation {
+    /// As written in `[%eval]`, e.g. "0.43", "-1.20" or "#-3"
+    pub fn text(self) -> String {
+        match self {
+            Evaluation::Centipawns(centipawns) => format!("{:.2}", centipawns as f64 / 100.0),
+            Evaluation::Mate(moves) => format!("#{}", moves),
+        }
+    }
+
+    /// Reads the value of an `[%eval]` command, in pawns or as moves to mate
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.strip_prefix('#') {
+            Some(moves) => moves.parse().ok().map(Evaluation::Mate),
+            None => text.parse::<f64>().ok().filter(|pawns| pawns.is_finite()).map(|pawns| Evaluation::Centipawns((pawns * 100.0).round() as i32)),
+        }
+    }
+}
+
+/// Colors of the arrows and circles drawn on the board with the right mouse button
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl MarkColor {
+    pub const ALL: [MarkColor; 4] = [MarkColor::Green, MarkColor::Red, MarkColor::Blue, MarkColor::Yellow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MarkColor::Green => "Green",
+            MarkColor::Red => "Red",
+            MarkColor::Blue => "Blue",
+            MarkColor::Yellow => "Yellow",
+        }
+    }
+
+    /// The letter standing for the color in `[%cal]` and `[%csl]`
+    pub fn letter(self) -> char {
+        self.name().chars().next().unwrap()
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|color| color.letter() == letter)
+    }
+}
+
+/// Arrows and circled squares drawn on the position after a move, stored in PGN as
+/// `[%cal Ge2e4,Rd1d8]` and `[%csl Rd4]`. Squares are indices on the board, a8 first.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Data, Serialize, Deserialize)]
+pub struct Marks {
+    pub arrows: Vector<(usize, usize, MarkColor)>, // From and to square
+    pub circles: Vector<(usize, MarkColor)>,
+}
+
+impl Marks {
+    /// The `[%csl]` and `[%cal]` commands for the marks, without the empty ones
+    pub fn commands(&self) -> Vec<String> {
+        let name = |square: usize| square_name((square / 8, square % 8));
+        let circles: Vec<String> = self.circles.iter().map(|&(square, color)| format!("{}{}", color.letter(), name(square))).collect();
+        let arrows: Vec<String> = self.arrows.iter().map(|&(from, to, color)| format!("{}{}{}", color.letter(), name(from), name(to))).collect();
+        [("csl", circles), ("cal", arrows)].into_iter()
+            .filter(|(_, marks)| !marks.is_empty())
+            .map(|(command, marks)| format!("[%{} {}]", command, marks.join(",")))
+            .collect()
+    }
+
+    /// Adds the marks of a `[%csl]` or `[%cal]` command, given by its name and value; marks
+    /// that cannot be read are skipped
+    pub fn add_command(&mut self, command: &str, value: &str) {
+        let square = |name: &str| parse_square(name).map(|(row, col)| row * 8 + col);
+        for mark in value.split(',').map(str::trim) {
+            let Some(color) = mark.chars().next().and_then(MarkColor::from_letter) else { continue };
+            let squares = &mark[1..];
+            match (command, squares.get(..2).and_then(square), squares.get(2..).and_then(square)) {
+                ("csl", Some(square), None) if squares.len() == 2 => self.circles.push_back((square, color)),
+                ("cal", Some(from), Some(to)) => self.arrows.push_back((from, to, color)),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arrows.is_empty() && self.circles.is_empty()
+    }
+}
+
+/// How one side played in a reviewed game
+#[derive(Clone, Copy, PartialEq, Debug, Data, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub accuracy: f64, // Percent; 100 when no move lost winning chances against the best one
+    pub acpl: u32, // Average centipawn loss per move
+    pub inaccuracies: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+}
+
+impl PlayerSummary {
+    pub fn text(&self) -> String {
+        format!("accuracy {:.1}%, average centipawn loss {}, {} inaccuracies, {} mistakes, {} blunders",
+            self.accuracy, self.acpl, self.inaccuracies, self.mistakes, self.blunders)
+    }
+}
+
+/// Splits a suffix annotation such as "!?" off a SAN move, e.g. "Nxe5?!" into "Nxe5" and Dubious
+pub fn split_suffix(san: &str) -> (&str, Option<MoveQuality>) {
+    let stripped = san.trim_end_matches(['!', '?']);
+    let quality = match &san[stripped.len()..] {
+        "!!" => Some(MoveQuality::Brilliant),
+        "!" => Some(MoveQuality::Good),
+        "!?" => Some(MoveQuality::Interesting),
+        "?!" => Some(MoveQuality::Dubious),
+        "?" => Some(MoveQuality::Mistake),
+        "??" => Some(MoveQuality::Blunder),
+        _ => None,
+    };
+    (stripped, quality)
+}
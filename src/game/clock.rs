@@ -0,0 +1,120 @@
+use druid::im::Vector;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use crate::pieces::PieceColor;
+
+/// Time controls offered for a new game
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum TimeControl {
+    Unlimited,
+    Bullet, // 1 minute
+    Blitz, // 3 minutes and 2 seconds a move
+    Rapid, // 10 minutes
+    RapidIncrement, // 15 minutes and 10 seconds a move
+    Classical, // 30 minutes
+}
+
+impl TimeControl {
+    pub const ALL: [TimeControl; 6] = [
+        TimeControl::Unlimited,
+        TimeControl::Bullet,
+        TimeControl::Blitz,
+        TimeControl::Rapid,
+        TimeControl::RapidIncrement,
+        TimeControl::Classical,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeControl::Unlimited => "No clock",
+            TimeControl::Bullet => "1+0",
+            TimeControl::Blitz => "3+2",
+            TimeControl::Rapid => "10+0",
+            TimeControl::RapidIncrement => "15+10",
+            TimeControl::Classical => "30+0",
+        }
+    }
+
+    /// A full clock for a game with this time control, none when it is unlimited
+    pub fn clock(self) -> Option<Clock> {
+        let (minutes, increment) = match self {
+            TimeControl::Unlimited => return None,
+            TimeControl::Bullet => (1, 0),
+            TimeControl::Blitz => (3, 2),
+            TimeControl::Rapid => (10, 0),
+            TimeControl::RapidIncrement => (15, 10),
+            TimeControl::Classical => (30, 0),
+        };
+        Some(Clock { white_ms: minutes * 60_000, black_ms: minutes * 60_000, increment_ms: increment * 1000, history: Vector::new() })
+    }
+}
+
+/// Time left for each side, in milliseconds
+#[derive(Clone, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub struct Clock {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub increment_ms: u64, // Added after each move of a side
+    history: Vector<(u64, u64)>, // White's and Black's time before each move, for taking moves back
+}
+
+impl Clock {
+    pub fn remaining(&self, color: PieceColor) -> u64 {
+        match color {
+            PieceColor::White => self.white_ms,
+            PieceColor::Black => self.black_ms,
+        }
+    }
+
+    fn remaining_mut(&mut self, color: PieceColor) -> &mut u64 {
+        match color {
+            PieceColor::White => &mut self.white_ms,
+            PieceColor::Black => &mut self.black_ms,
+        }
+    }
+
+    /// Takes `elapsed_ms` off the time of `color`, true when it has run out
+    pub fn run(&mut self, color: PieceColor, elapsed_ms: u64) -> bool {
+        let remaining = self.remaining_mut(color);
+        *remaining = remaining.saturating_sub(elapsed_ms);
+        *remaining == 0
+    }
+
+    /// Adds the increment once `color` has moved
+    pub fn moved(&mut self, color: PieceColor) {
+        self.history.push_back((self.white_ms, self.black_ms));
+        let increment = self.increment_ms;
+        *self.remaining_mut(color) += increment;
+    }
+
+    /// Sets both clocks back to the times they showed before the last `moves` moves
+    pub fn take_back(&mut self, moves: usize) {
+        let mut times = None;
+        for _ in 0..moves {
+            times = self.history.pop_back().or(times);
+        }
+        if let Some((white_ms, black_ms)) = times {
+            (self.white_ms, self.black_ms) = (white_ms, black_ms);
+        }
+    }
+
+    /// The time each move left its side, increment included, oldest first. `last` made the last
+    /// move the clock ran for, and the sides took turns before it.
+    pub fn left_after_moves(&self, last: PieceColor) -> Vec<u64> {
+        let count = self.history.len();
+        self.history.iter().enumerate().map(|(i, &(white_ms, black_ms))| {
+            let by_last = (count - i) % 2 == 1;
+            let left = if by_last == (last == PieceColor::White) { white_ms } else { black_ms };
+            left + self.increment_ms
+        }).collect()
+    }
+}
+
+/// A time on the clock as "m:ss", with tenths in the last ten seconds
+pub fn time_text(ms: u64) -> String {
+    if ms < 10_000 {
+        format!("0:{:02}.{}", ms / 1000, ms % 1000 / 100)
+    } else {
+        format!("{}:{:02}", ms / 60_000, ms / 1000 % 60)
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use druid::Data;
+use druid::im::Vector;
+use serde::{Deserialize, Serialize};
+
+/// Opening lines in the tab-separated layout of the lichess chess-openings files
+const ECO_TABLE: &str = include_str!("eco.tsv");
+
+#[derive(Clone, Debug, PartialEq, Eq, Data, Serialize, Deserialize)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+}
+
+/// The opening whose line is exactly this sequence of SAN moves from the standard position
+pub fn classify(san_moves: &Vector<String>) -> Option<Opening> {
+    static OPENINGS: OnceLock<HashMap<String, Opening>> = OnceLock::new();
+    let openings = OPENINGS.get_or_init(|| {
+        ECO_TABLE.lines()
+            .skip(1)
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let eco = fields.next()?.to_string();
+                let name = fields.next()?.to_string();
+                let moves: Vec<&str> = fields.next()?
+                    .split_whitespace()
+                    .filter(|token| !token.ends_with('.'))
+                    .collect();
+                Some((line_key(&moves), Opening { eco, name }))
+            })
+            .collect()
+    });
+
+    let moves: Vec<&str> = san_moves.iter().map(String::as_str).collect();
+    openings.get(&line_key(&moves)).cloned()
+}
+
+fn line_key(moves: &[&str]) -> String {
+    moves.iter()
+        .map(|san| san.trim_end_matches(['+', '#']))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,145 @@
+use crate::pieces::Piece;
+use super::fen::{parse_fen, to_fen};
+use super::game_state::GameState;
+
+/// One EPD operation such as `bm Nf3;` or `id "WAC.001";`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpdOperation {
+    pub opcode: String,
+    pub operands: Vec<String>,
+}
+
+/// A position from an EPD line together with its operations
+#[derive(Clone, Debug)]
+pub struct EpdRecord {
+    pub state: GameState,
+    pub board: Vec<Option<Piece>>,
+    pub operations: Vec<EpdOperation>,
+}
+
+impl EpdRecord {
+    pub fn new(state: GameState, board: Vec<Option<Piece>>) -> Self {
+        Self { state, board, operations: Vec::new() }
+    }
+
+    /// Operands of the first operation with this opcode, e.g. the SAN moves of `bm`
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter()
+            .find(|op| op.opcode == opcode)
+            .map(|op| op.operands.as_slice())
+    }
+
+    pub fn to_epd(&self) -> String {
+        // EPD only carries the first four FEN fields
+        let fen = to_fen(&self.state, &self.board);
+        let mut epd: String = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+
+        for op in &self.operations {
+            epd.push(' ');
+            epd.push_str(&op.opcode);
+            for operand in &op.operands {
+                epd.push(' ');
+                if needs_quotes(&op.opcode, operand) {
+                    epd.push('"');
+                    epd.push_str(operand);
+                    epd.push('"');
+                } else {
+                    epd.push_str(operand);
+                }
+            }
+            epd.push(';');
+        }
+        epd
+    }
+}
+
+/// Parses a single EPD line
+pub fn parse_epd(line: &str) -> Result<EpdRecord, String> {
+    let mut rest = line.trim();
+    let mut position = Vec::with_capacity(4);
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err("expected piece placement, side to move, castling and en passant fields".to_string());
+        }
+        position.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let (state, board) = parse_fen(&position.join(" "))?;
+    let mut record = EpdRecord::new(state, board);
+    record.operations = parse_operations(rest)?;
+
+    if let Some(hmvc) = record.operands("hmvc").and_then(|ops| ops.first()) {
+        record.state.halfmove_clock = hmvc.parse().map_err(|_| format!("invalid hmvc '{}'", hmvc))?;
+    }
+    if let Some(fmvn) = record.operands("fmvn").and_then(|ops| ops.first()) {
+        record.state.fullmove_number = fmvn.parse().map_err(|_| format!("invalid fmvn '{}'", fmvn))?;
+    }
+
+    Ok(record)
+}
+
+/// Parses every non-empty line of an EPD file, reporting the first bad line
+pub fn parse_epd_file(text: &str) -> Result<Vec<EpdRecord>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_epd(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn parse_operations(text: &str) -> Result<Vec<EpdOperation>, String> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut quoted = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    quoted.push(c);
+                }
+                if !closed {
+                    return Err("unterminated string operand".to_string());
+                }
+                words.push(quoted);
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if words.is_empty() {
+                    return Err("empty operation".to_string());
+                }
+                let opcode = words.remove(0);
+                operations.push(EpdOperation { opcode, operands: std::mem::take(&mut words) });
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+
+    if !word.is_empty() || !words.is_empty() {
+        return Err("operation is missing its terminating ';'".to_string());
+    }
+    Ok(operations)
+}
+
+fn needs_quotes(opcode: &str, operand: &str) -> bool {
+    let is_string_opcode = opcode == "id"
+        || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit());
+    is_string_opcode || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"')
+}
@@ -0,0 +1,242 @@
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::game_state::GameState;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Parses a FEN string into a game state and board.
+/// The halfmove clock and fullmove number are optional and default to `0 1`.
+pub fn parse_fen(fen: &str) -> Result<(GameState, Vec<Option<Piece>>), String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 || fields.len() > 6 {
+        return Err(format!("expected 4 to 6 fields, found {}", fields.len()));
+    }
+
+    let board = parse_placement(fields[0])?;
+
+    let mut state = GameState::new();
+    state.current_turn = match fields[1] {
+        "w" => PieceColor::White,
+        "b" => PieceColor::Black,
+        other => return Err(format!("invalid side to move '{}'", other)),
+    };
+
+    state.white_can_castle_kingside = false;
+    state.white_can_castle_queenside = false;
+    state.black_can_castle_kingside = false;
+    state.black_can_castle_queenside = false;
+    // Columns of the castling rooks, and whether some king or rook stands off the standard squares
+    let (mut kingside_col, mut queenside_col) = (7, 0);
+    let mut chess960 = false;
+    if fields[2] != "-" {
+        for c in fields[2].chars() {
+            let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+            let row = if color == PieceColor::White { 7 } else { 0 };
+            let holds = |col: usize, piece_type| matches!(board[row * 8 + col], Some(p) if p.piece_type == piece_type && p.color == color);
+            let king_col = (0..8).find(|&col| holds(col, PieceType::King)).unwrap_or(4);
+            // K and Q name the outermost rook on their side (X-FEN), a file names the rook on it (Shredder-FEN)
+            let rook_col = match c.to_ascii_lowercase() {
+                'k' => (king_col + 1..8).rev().find(|&col| holds(col, PieceType::Rook)).unwrap_or(7),
+                'q' => (0..king_col).find(|&col| holds(col, PieceType::Rook)).unwrap_or(0),
+                file @ 'a'..='h' => file as usize - 'a' as usize,
+                _ => return Err(format!("invalid castling flag '{}'", c)),
+            };
+            let kingside = rook_col > king_col;
+            match (color, kingside) {
+                (PieceColor::White, true) => state.white_can_castle_kingside = true,
+                (PieceColor::White, false) => state.white_can_castle_queenside = true,
+                (PieceColor::Black, true) => state.black_can_castle_kingside = true,
+                (PieceColor::Black, false) => state.black_can_castle_queenside = true,
+            }
+            if kingside { kingside_col = rook_col } else { queenside_col = rook_col }
+            chess960 |= king_col != 4 || rook_col != if kingside { 7 } else { 0 };
+        }
+    }
+    if chess960 {
+        state.castling_files = Some((kingside_col, queenside_col));
+    }
+
+    if fields[3] != "-" {
+        let (row, col) = parse_square(fields[3])
+            .ok_or_else(|| format!("invalid en passant square '{}'", fields[3]))?;
+        // The pawn that moved two squares stands just past the square
+        let (pawn_row, color) = match row {
+            5 => (4, PieceColor::White),
+            2 => (3, PieceColor::Black),
+            _ => return Err(format!("invalid en passant square '{}'", fields[3])),
+        };
+        match board[pawn_row * 8 + col] {
+            Some(p) if p.piece_type == PieceType::Pawn && p.color == color && color != state.current_turn => {}
+            _ => return Err(format!("no pawn can be captured en passant on '{}'", fields[3])),
+        }
+        state.en_passant = Some((row, col));
+    }
+
+    if let Some(halfmove) = fields.get(4) {
+        state.halfmove_clock = halfmove.parse()
+            .map_err(|_| format!("invalid halfmove clock '{}'", halfmove))?;
+    }
+    if let Some(fullmove) = fields.get(5) {
+        state.fullmove_number = match fullmove.parse() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("invalid fullmove number '{}'", fullmove)),
+        };
+    }
+
+    // The first four fields decide the position; the counters alone do not make it a custom start
+    let standard: Vec<&str> = STARTING_FEN.split(' ').take(4).collect();
+    if fields[..4] != standard[..] {
+        state.start_fen = Some(fields.join(" "));
+    }
+
+    state.update_game_status(&board);
+    Ok((state, board))
+}
+
+/// Serializes the position to a full six-field FEN string
+pub fn to_fen(state: &GameState, board: &[Option<Piece>]) -> String {
+    let mut fen = String::new();
+
+    for row in 0..8 {
+        let mut empty = 0;
+        for col in 0..8 {
+            match board[row * 8 + col] {
+                Some(piece) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(piece_to_char(piece));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if row < 7 {
+            fen.push('/');
+        }
+    }
+
+    fen.push(' ');
+    fen.push(if state.current_turn == PieceColor::White { 'w' } else { 'b' });
+
+    fen.push(' ');
+    let mut castling = String::new();
+    let rights = [
+        (state.white_can_castle_kingside, PieceColor::White, true),
+        (state.white_can_castle_queenside, PieceColor::White, false),
+        (state.black_can_castle_kingside, PieceColor::Black, true),
+        (state.black_can_castle_queenside, PieceColor::Black, false),
+    ];
+    for (_, color, kingside) in rights.into_iter().filter(|&(allowed, _, _)| allowed) {
+        let flag = castling_flag(state, board, color, kingside);
+        castling.push(if color == PieceColor::White { flag.to_ascii_uppercase() } else { flag });
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    fen.push_str(&castling);
+
+    fen.push(' ');
+    match state.en_passant {
+        Some(square) => fen.push_str(&square_name(square)),
+        None => fen.push('-'),
+    }
+
+    fen.push_str(&format!(" {} {}", state.halfmove_clock, state.fullmove_number));
+    fen
+}
+
+/// The castling flag of `color` towards `kingside` in lower case: `k` or `q`, or in Chess960 the
+/// rook's file when another rook stands further out on that side, as X-FEN writes it
+fn castling_flag(state: &GameState, board: &[Option<Piece>], color: PieceColor, kingside: bool) -> char {
+    let letter = if kingside { 'k' } else { 'q' };
+    let Some((kingside_col, queenside_col)) = state.castling_files else { return letter };
+    let row = if color == PieceColor::White { 7 } else { 0 };
+    let (rook_col, mut outer) = if kingside { (kingside_col, kingside_col + 1..8) } else { (queenside_col, 0..queenside_col) };
+    if outer.any(|col| matches!(board[row * 8 + col], Some(p) if p.piece_type == PieceType::Rook && p.color == color)) {
+        (b'a' + rook_col as u8) as char
+    } else {
+        letter
+    }
+}
+
+/// Parses the piece placement field, requiring exactly one king per side
+fn parse_placement(placement: &str) -> Result<Vec<Option<Piece>>, String> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("expected 8 ranks, found {}", ranks.len()));
+    }
+
+    let mut board = vec![None; 64];
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut col = 0;
+        for c in rank.chars() {
+            if let Some(skip) = c.to_digit(10) {
+                col += skip as usize;
+            } else {
+                let piece = char_to_piece(c).ok_or_else(|| format!("invalid piece '{}'", c))?;
+                if col < 8 {
+                    board[row * 8 + col] = Some(piece);
+                }
+                col += 1;
+            }
+            if col > 8 {
+                break;
+            }
+        }
+        if col != 8 {
+            return Err(format!("rank {} does not describe 8 squares", 8 - row));
+        }
+    }
+
+    for color in [PieceColor::White, PieceColor::Black] {
+        let kings = board.iter()
+            .filter(|p| matches!(p, Some(p) if p.piece_type == PieceType::King && p.color == color))
+            .count();
+        if kings != 1 {
+            return Err(format!("expected one {:?} king, found {}", color, kings));
+        }
+    }
+
+    Ok(board)
+}
+
+pub fn parse_square(name: &str) -> Option<(usize, usize)> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+        return None;
+    }
+    Some(((b'8' - bytes[1]) as usize, (bytes[0] - b'a') as usize))
+}
+
+pub fn square_name(pos: (usize, usize)) -> String {
+    format!("{}{}", (b'a' + pos.1 as u8) as char, 8 - pos.0)
+}
+
+fn char_to_piece(c: char) -> Option<Piece> {
+    let piece_type = match c.to_ascii_lowercase() {
+        'k' => PieceType::King,
+        'q' => PieceType::Queen,
+        'r' => PieceType::Rook,
+        'b' => PieceType::Bishop,
+        'n' => PieceType::Knight,
+        'p' => PieceType::Pawn,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+    Some(Piece { piece_type, color })
+}
+
+fn piece_to_char(piece: Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::King => 'k',
+        PieceType::Queen => 'q',
+        PieceType::Rook => 'r',
+        PieceType::Bishop => 'b',
+        PieceType::Knight => 'n',
+        PieceType::Pawn => 'p',
+    };
+    if piece.color == PieceColor::White { c.to_ascii_uppercase() } else { c }
+}
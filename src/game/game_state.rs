@@ -1,25 +1,91 @@
 use crate::pieces::{Piece, PieceColor, PieceType};
 use druid::Data;
 use druid::im::Vector;
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Data)]
+use serde::{Deserialize, Serialize};
+use super::annotation::{Evaluation, Marks, MoveQuality, PlayerSummary};
+use super::bitboard::{squares, Bitboards};
+use super::variation::SideLine;
+use super::eco::{classify, Opening};
+use super::notation::{move_to_san, ParsedMove};
+use super::player::Player;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
 pub enum GameStatus {
     InProgress,
     Check,
     Checkmate,
     Stalemate,
+    TimeForfeit, // The side to move ran out of time
+    DrawOnTime, // The side to move ran out of time, but the other had nothing left to mate with
+    Resigned, // The side to move resigned
+    DrawAgreed,
 }
 
-#[derive(Clone, Debug, Data)]
+/// What a move changed, so that it can be taken back
+#[derive(Clone, Debug, Data, Serialize, Deserialize)]
+pub struct UndoMove {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: PieceType,
+    pub moved: Piece,
+    pub captured: Option<(usize, Piece)>, // With its square, which differs from `to` en passant
+    pub castling_rights: (bool, bool, bool, bool), // White kingside and queenside, then Black's
+    pub last_move: Option<((usize, usize), (usize, usize))>,
+    #[serde(default)]
+    pub en_passant: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    pub status: GameStatus,
+    pub opening: Option<Opening>,
+    pub history_line: Option<String>, // Last line of the move history before Black's move completed it
+    #[serde(default)]
+    pub draw_offer: Option<PieceColor>, // Offer the move declined by being played instead of an answer
+}
+
+#[derive(Clone, Debug, Data, Serialize, Deserialize)]
 pub struct GameState {
     pub current_turn: PieceColor,
     pub status: GameStatus,
-    pub last_move: Option<((usize, usize), (usize, usize))>, // For en passant detection
+    pub last_move: Option<((usize, usize), (usize, usize))>, // Highlighted on the board
+    #[serde(default)]
+    pub en_passant: Option<(usize, usize)>, // Square behind a pawn that just moved two squares, where it can be taken en passant
     pub white_can_castle_kingside: bool,
     pub white_can_castle_queenside: bool,
     pub black_can_castle_kingside: bool,
     pub black_can_castle_queenside: bool,
+    #[serde(default)]
+    pub castling_files: Option<(usize, usize)>, // Columns of the kingside and queenside rooks in Chess960, when they do not start in the corners
     pub move_history: Vector<String>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub san_moves: Vector<String>, // For PGN export
+    pub move_comments: Vector<Option<String>>, // Comment after each move, kept in step with san_moves
+    pub move_qualities: Vector<Option<MoveQuality>>, // Review annotation of each move, likewise
+    pub start_fen: Option<String>, // Set when the game did not start from the standard position
+    pub opening: Option<Opening>,
+    #[serde(default)]
+    pub flipped: Option<bool>, // Board orientation picked for this game; None follows the side the user plays
+    #[serde(default)]
+    pub white_player: Option<Player>, // Known when the game came from a PGN naming its players
+    #[serde(default)]
+    pub black_player: Option<Player>,
+    #[serde(default)]
+    pub review: Option<(PlayerSummary, PlayerSummary)>, // How White and Black played, once the game is reviewed
+    #[serde(default)]
+    pub captures: Vector<Option<Piece>>, // Piece taken by each move, kept in step with san_moves
+    #[serde(default)]
+    pub undo_stack: Vector<UndoMove>, // Moves that can be taken back, the last one played at the end
+    #[serde(default)]
+    pub draw_offer: Option<PieceColor>, // Side whose draw offer the opponent has not answered yet
+    #[serde(default)]
+    pub redo_stack: Vector<ParsedMove>, // Moves taken back, the last one taken back at the end
+    #[serde(default)]
+    pub variations: Vector<SideLine>, // Side lines read from PGN or kept from trying moves
+    #[serde(default)]
+    pub move_clocks: Vector<Option<u64>>, // Time the mover had left after each move, in milliseconds, as a PGN recorded it
+    #[serde(default)]
+    pub move_evals: Vector<Option<Evaluation>>, // Evaluation after each move, from the review or a PGN
+    #[serde(default)]
+    pub move_marks: Vector<Option<Marks>>, // Arrows and circles drawn on the position after each move
 }
 
 impl GameState {
@@ -28,25 +94,132 @@ impl GameState {
             current_turn: PieceColor::White,
             status: GameStatus::InProgress,
             last_move: None,
+            en_passant: None,
             white_can_castle_kingside: true,
             white_can_castle_queenside: true,
             black_can_castle_kingside: true,
             black_can_castle_queenside: true,
+            castling_files: None,
             move_history: Vector::new(),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            san_moves: Vector::new(),
+            move_comments: Vector::new(),
+            move_qualities: Vector::new(),
+            start_fen: None,
+            opening: None,
+            flipped: None,
+            white_player: None,
+            black_player: None,
+            review: None,
+            captures: Vector::new(),
+            undo_stack: Vector::new(),
+            draw_offer: None,
+            redo_stack: Vector::new(),
+            variations: Vector::new(),
+            move_clocks: Vector::new(),
+            move_evals: Vector::new(),
+            move_marks: Vector::new(),
+        }
+    }
+
+    /// Whether the game has ended, so that no more moves can be played
+    pub fn is_over(&self) -> bool {
+        matches!(self.status, GameStatus::Checkmate | GameStatus::Stalemate | GameStatus::TimeForfeit | GameStatus::DrawOnTime | GameStatus::Resigned | GameStatus::DrawAgreed)
+    }
+
+    /// The result as written in PGN, "*" while the game is going
+    pub fn result(&self) -> &'static str {
+        match self.status {
+            GameStatus::Checkmate | GameStatus::TimeForfeit | GameStatus::Resigned if self.current_turn == PieceColor::White => "0-1",
+            GameStatus::Checkmate | GameStatus::TimeForfeit | GameStatus::Resigned => "1-0",
+            GameStatus::Stalemate | GameStatus::DrawOnTime | GameStatus::DrawAgreed => "1/2-1/2",
+            _ => "*",
+        }
+    }
+
+    /// Whose move it is and whether they are in check, or how the game ended
+    pub fn status_text(&self) -> String {
+        let (mover, other) = match self.current_turn {
+            PieceColor::White => ("White", "Black"),
+            PieceColor::Black => ("Black", "White"),
+        };
+        let text = match self.status {
+            GameStatus::InProgress => format!("{} to move", mover),
+            GameStatus::Check => format!("{} to move - in check!", mover),
+            GameStatus::Checkmate => format!("{} wins by checkmate", other),
+            GameStatus::Stalemate => "Draw by stalemate".to_string(),
+            GameStatus::TimeForfeit => format!("{} wins on time", other),
+            GameStatus::DrawOnTime => format!("Draw - {} ran out of time, but {} cannot mate", mover, other),
+            GameStatus::Resigned => format!("{} resigns - {} wins", mover, other),
+            GameStatus::DrawAgreed => "Draw by agreement".to_string(),
+        };
+        match self.draw_offer {
+            _ if self.is_over() => format!("{} ({})", text, self.result()),
+            Some(color) if color != self.current_turn => format!("{} - {} offers a draw", text, other),
+            _ => text,
+        }
+    }
+
+    /// The time `color` had left after its last move, when the game recorded it
+    pub fn clock_after(&self, color: PieceColor) -> Option<u64> {
+        // The last move was played by the side not to move, the one before by the side to move
+        let last = self.move_clocks.len().checked_sub(if self.current_turn == color { 2 } else { 1 })?;
+        self.move_clocks.get(last).copied().flatten()
+    }
+
+    /// The side to move has run out of time: a loss, unless its opponent could never mate it
+    pub fn flag_fall(&mut self, board: &[Option<Piece>]) {
+        let opponent = if self.current_turn == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+        self.status = if Self::has_mating_material(opponent, board) { GameStatus::TimeForfeit } else { GameStatus::DrawOnTime };
+    }
+
+    /// Whether `color` has more than a lone king, or a king with a single knight or bishop
+    pub fn has_mating_material(color: PieceColor, board: &[Option<Piece>]) -> bool {
+        let pieces: Vec<PieceType> = board.iter().flatten()
+            .filter(|piece| piece.color == color && piece.piece_type != PieceType::King)
+            .map(|piece| piece.piece_type)
+            .collect();
+        !matches!(pieces[..], [] | [PieceType::Knight] | [PieceType::Bishop])
+    }
+
+    /// The side to move gives up the game
+    pub fn resign(&mut self) {
+        self.status = GameStatus::Resigned;
+    }
+
+    /// The side to move offers a draw, or takes the one its opponent offered. An offer stands
+    /// until the opponent moves.
+    pub fn offer_draw(&mut self) {
+        if self.draw_offer.is_some_and(|color| color != self.current_turn) {
+            self.accept_draw();
+        } else {
+            self.draw_offer = Some(self.current_turn);
         }
     }
 
+    /// Ends the game in a draw, as both sides agreed
+    pub fn accept_draw(&mut self) {
+        self.status = GameStatus::DrawAgreed;
+        self.draw_offer = None;
+    }
+
     pub fn is_valid_move(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
         let piece = match board[from.0 * 8 + from.1] {
             Some(p) => p,
             None => return false,
         };
 
-        // Check if it's the correct player's turn
-        if piece.color != self.current_turn {
+        // Check if it's the correct player's turn, in a game still going
+        if piece.color != self.current_turn || matches!(self.status, GameStatus::TimeForfeit | GameStatus::DrawOnTime | GameStatus::Resigned | GameStatus::DrawAgreed) {
             return false;
         }
 
+        // Castling is the only two-square king move, so it never shows up in the raw moves
+        if self.is_castling_move(from, to, board) {
+            return self.is_valid_castling(from, to, board);
+        }
+
         // Convert coordinates for piece movement check
         let from_coords = (from.0 as i32, from.1 as i32);
         let to_coords = (to.0 as i32, to.1 as i32);
@@ -64,11 +237,6 @@ impl GameState {
                 return false;
             }
 
-            // Castling
-            if self.is_castling_move(from, to, board) {
-                return self.is_valid_castling(from, to, board);
-            }
-
             // For regular king moves, check if target square contains friendly piece
             if let Some(target) = board[to.0 * 8 + to.1] {
                 if target.color == piece.color {
@@ -143,49 +311,85 @@ impl GameState {
         true
     }
 
-    fn is_castling_move(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
+    /// Whether the king moving from `from` to `to` castles: two squares towards a corner rook in
+    /// standard chess, onto one of its castling rooks in Chess960
+    pub fn is_castling_move(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
         let piece = board[from.0 * 8 + from.1].unwrap();
-        if piece.piece_type != PieceType::King {
+        if piece.piece_type != PieceType::King || from.0 != to.0 {
             return false;
         }
 
-        // Check if it's a horizontal move of 2 squares
-        from.0 == to.0 && (to.1 as i32 - from.1 as i32).abs() == 2
+        match self.castling_files {
+            None => from.1.abs_diff(to.1) == 2,
+            Some((kingside, queenside)) => {
+                let rook_col = if to.1 > from.1 { kingside } else { queenside };
+                to.1 == rook_col && matches!(board[to.0 * 8 + to.1], Some(p) if p.piece_type == PieceType::Rook && p.color == piece.color)
+            }
+        }
+    }
+
+    /// Columns the king and the rook end on when castling towards `to`, after the rook's own
+    pub fn castling_columns(&self, from: (usize, usize), to: (usize, usize)) -> (usize, usize, usize) {
+        let (kingside, queenside) = self.castling_files.unwrap_or((7, 0));
+        if to.1 > from.1 { (6, kingside, 5) } else { (2, queenside, 3) }
+    }
+
+    /// The castling move of the side to move towards the kingside or queenside rook, as the king
+    /// plays it, whether or not it is legal
+    pub fn castling_move(&self, board: &[Option<Piece>], kingside: bool) -> Option<((usize, usize), (usize, usize))> {
+        let home_row = if self.current_turn == PieceColor::White { 7 } else { 0 };
+        let col = (0..8).find(|&col| matches!(board[home_row * 8 + col], Some(p) if p.piece_type == PieceType::King && p.color == self.current_turn))?;
+        let to_col = match self.castling_files {
+            None => if kingside { 6 } else { 2 },
+            Some((kingside_rook, queenside_rook)) => if kingside { kingside_rook } else { queenside_rook },
+        };
+        Some(((home_row, col), (home_row, to_col)))
     }
 
     fn is_valid_castling(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
         let piece = board[from.0 * 8 + from.1].unwrap();
 
         // Check if king and rook haven't moved
-        match (piece.color, to.1) {
-            (PieceColor::White, 6) if !self.white_can_castle_kingside => return false,
-            (PieceColor::White, 2) if !self.white_can_castle_queenside => return false,
-            (PieceColor::Black, 6) if !self.black_can_castle_kingside => return false,
-            (PieceColor::Black, 2) if !self.black_can_castle_queenside => return false,
+        let kingside = to.1 > from.1;
+        match (piece.color, kingside) {
+            (PieceColor::White, true) if !self.white_can_castle_kingside => return false,
+            (PieceColor::White, false) if !self.white_can_castle_queenside => return false,
+            (PieceColor::Black, true) if !self.black_can_castle_kingside => return false,
+            (PieceColor::Black, false) if !self.black_can_castle_queenside => return false,
             _ => {}
         }
 
-        // Check if path is clear
+        // King must be on its home rank, on the e-file in standard chess, with the matching rook in place
         let row = from.0;
-        let path_range = if to.1 == 6 { 5..7 } else { 1..4 };
+        let home_row = if piece.color == PieceColor::White { 7 } else { 0 };
+        let (king_col, rook_col, rook_to_col) = self.castling_columns(from, to);
+        if row != home_row || (self.castling_files.is_none() && from.1 != 4) {
+            return false;
+        }
+        match board[row * 8 + rook_col] {
+            Some(rook) if rook.piece_type == PieceType::Rook && rook.color == piece.color => {}
+            _ => return false,
+        }
 
-        // Check if squares between king and rook are empty
-        for col in path_range {
-            if board[row * 8 + col].is_some() {
-                return false;
-            }
+        // Every square the king and the rook cross or land on must be empty but for the two of them
+        let cols = [from.1, king_col, rook_col, rook_to_col];
+        let (first, last) = (*cols.iter().min().unwrap(), *cols.iter().max().unwrap());
+        if (first..=last).any(|col| col != from.1 && col != rook_col && board[row * 8 + col].is_some()) {
+            return false;
         }
 
         // Check if king is in check or would pass through check
-        let direction = if to.1 == 6 { 1 } else { -1 };
-        for col_offset in 0..=2 {
-            let check_pos = (row, (from.1 as i32 + col_offset * direction) as usize);
-            if self.is_square_attacked(check_pos, piece.color, board) {
-                return false;
-            }
+        let (low, high) = (from.1.min(king_col), from.1.max(king_col));
+        if (low..=high).any(|col| self.is_square_attacked((row, col), piece.color, board)) {
+            return false;
         }
-
-        true
+        // The rook leaving its square can open a line onto the king's in Chess960
+        let mut castled = board.clone();
+        castled[row * 8 + from.1] = None;
+        castled[row * 8 + rook_col] = None;
+        castled[row * 8 + king_col] = Some(piece);
+        castled[row * 8 + rook_to_col] = Some(Piece { piece_type: PieceType::Rook, color: piece.color });
+        !self.is_square_attacked((row, king_col), piece.color, &castled)
     }
 
     fn is_en_passant_move(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
@@ -198,87 +402,23 @@ impl GameState {
             return false;
         }
 
-        // Check if there was a last move and it was a pawn moving two squares
-        if let Some((_, last_to)) = self.last_move {
-            let last_piece = board[last_to.0 * 8 + last_to.1].unwrap();
-            if last_piece.piece_type == PieceType::Pawn {
-                let forward = if piece.color == PieceColor::White { -1 } else { 1 };
-                let expected_row = from.0 as i32 + forward;
-
-                // Check if the move is diagonal and captures the pawn that just moved
-                if to.0 as i32 == expected_row && (to.1 as i32 - from.1 as i32).abs() == 1 {
-                    if last_to.0 == from.0 && last_to.1 == to.1 {
-                        return true;
-                    }
-                }
-            }
-        }
-
-        false
+        // A diagonal step onto the square the pawn that just moved two squares passed over
+        let forward = if piece.color == PieceColor::White { -1 } else { 1 };
+        self.en_passant == Some(to) && to.0 as i32 == from.0 as i32 + forward && to.1.abs_diff(from.1) == 1
     }
 
     fn is_square_attacked(&self, pos: (usize, usize), defending_color: PieceColor, board: &Vec<Option<Piece>>) -> bool {
-        for row in 0..8 {
-            for col in 0..8 {
-                if let Some(piece) = board[row * 8 + col] {
-                    if piece.color != defending_color {
-                        let from_coords = (row as i32, col as i32);
-                        let to_coords = (pos.0 as i32, pos.1 as i32);
-
-                        // Get raw moves for the attacking piece
-                        let raw_moves = piece.get_raw_moves(from_coords);
-                        if !raw_moves.contains(&to_coords) {
-                            continue;
-                        }
-
-                        // For pawns, only consider diagonal attacks
-                        if piece.piece_type == PieceType::Pawn {
-                            let dx = (to_coords.1 - from_coords.1).abs();
-                            let dy = to_coords.0 - from_coords.0;
-                            let forward = if piece.color == PieceColor::White { -1 } else { 1 };
-                            if dx != 1 || dy != forward {
-                                continue;
-                            }
-                            return true;
-                        }
-
-                        // For other pieces, check if path is clear
-                        if piece.piece_type == PieceType::Knight {
-                            return true;
-                        }
-
-                        // Check if path is clear for other pieces
-                        let dx = to_coords.1 - from_coords.1;
-                        let dy = to_coords.0 - from_coords.0;
-                        let step_x = if dx == 0 { 0 } else { dx / dx.abs() };
-                        let step_y = if dy == 0 { 0 } else { dy / dy.abs() };
-
-                        let mut x = from_coords.1 + step_x;
-                        let mut y = from_coords.0 + step_y;
-                        let mut path_clear = true;
-
-                        while (x, y) != (to_coords.1, to_coords.0) {
-                            if board[(y as usize) * 8 + (x as usize)].is_some() {
-                                path_clear = false;
-                                break;
-                            }
-                            x += step_x;
-                            y += step_y;
-                        }
-
-                        if path_clear {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        false
+        let attacking_color = if defending_color == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+        Bitboards::new(board).is_attacked(pos.0 * 8 + pos.1, attacking_color)
     }
 
     fn would_be_in_check(&self, from: (usize, usize), to: (usize, usize), board: &Vec<Option<Piece>>) -> bool {
         // Create a temporary board with the move applied
         let mut temp_board = board.clone();
+        // En passant also takes a pawn off the mover's rank, which can uncover a check along it
+        if self.is_en_passant_move(from, to, board) {
+            temp_board[from.0 * 8 + to.1] = None;
+        }
         let moving_piece = temp_board[from.0 * 8 + from.1].take();
         temp_board[to.0 * 8 + to.1] = moving_piece;
 
@@ -321,20 +461,37 @@ impl GameState {
     }
 
     pub fn make_move(&mut self, from: (usize, usize), to: (usize, usize), board: &mut Vec<Option<Piece>>) -> bool {
+        self.make_move_with_promotion(from, to, PieceType::Queen, board)
+    }
+
+    /// Same as `make_move`, but a pawn reaching the last rank becomes `promotion` instead of a queen
+    pub fn make_move_with_promotion(&mut self, from: (usize, usize), to: (usize, usize), promotion: PieceType, board: &mut Vec<Option<Piece>>) -> bool {
         if !self.is_valid_move(from, to, board) {
             return false;
         }
 
         let piece = board[from.0 * 8 + from.1].unwrap();
-        let is_capture = board[to.0 * 8 + to.1].is_some() || self.is_en_passant_move(from, to, board);
         let is_castling = self.is_castling_move(from, to, board);
-
-        // Handle castling
-        if is_castling {
-            let row = from.0;
-            let (rook_from_col, rook_to_col) = if to.1 == 6 { (7, 5) } else { (0, 3) };
-            board[row * 8 + rook_to_col] = board[row * 8 + rook_from_col].take();
-        }
+        let captured_square = if self.is_en_passant_move(from, to, board) { from.0 * 8 + to.1 } else { to.0 * 8 + to.1 };
+        // In Chess960 the king castles onto its own rook, which is not taken
+        let captured = board[captured_square].filter(|_| !is_castling);
+        let is_capture = captured.is_some();
+        let mut undo = UndoMove {
+            from,
+            to,
+            promotion,
+            moved: piece,
+            captured: captured.map(|captured| (captured_square, captured)),
+            castling_rights: (self.white_can_castle_kingside, self.white_can_castle_queenside, self.black_can_castle_kingside, self.black_can_castle_queenside),
+            last_move: self.last_move,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            status: self.status,
+            opening: self.opening.clone(),
+            history_line: None,
+            draw_offer: self.draw_offer,
+        };
+        let mut san = move_to_san(self, board, from, to, promotion);
 
         // Handle en passant
         if self.is_en_passant_move(from, to, board) {
@@ -342,6 +499,7 @@ impl GameState {
         }
 
         // Update castling rights
+        let (kingside_col, queenside_col) = self.castling_files.unwrap_or((7, 0));
         match piece.piece_type {
             PieceType::King => {
                 if piece.color == PieceColor::White {
@@ -353,25 +511,44 @@ impl GameState {
                 }
             }
             PieceType::Rook => {
-                match (from.0, from.1) {
-                    (7, 0) => self.white_can_castle_queenside = false,
-                    (7, 7) => self.white_can_castle_kingside = false,
-                    (0, 0) => self.black_can_castle_queenside = false,
-                    (0, 7) => self.black_can_castle_kingside = false,
+                match from {
+                    (7, col) if col == queenside_col => self.white_can_castle_queenside = false,
+                    (7, col) if col == kingside_col => self.white_can_castle_kingside = false,
+                    (0, col) if col == queenside_col => self.black_can_castle_queenside = false,
+                    (0, col) if col == kingside_col => self.black_can_castle_kingside = false,
                     _ => {}
                 }
             }
             _ => {}
         }
 
-        // Make the move
-        board[to.0 * 8 + to.1] = board[from.0 * 8 + from.1].take();
+        // Capturing a rook on its home square also removes that castling right
+        match to {
+            (7, col) if col == queenside_col => self.white_can_castle_queenside = false,
+            (7, col) if col == kingside_col => self.white_can_castle_kingside = false,
+            (0, col) if col == queenside_col => self.black_can_castle_queenside = false,
+            (0, col) if col == kingside_col => self.black_can_castle_kingside = false,
+            _ => {}
+        }
+
+        // Make the move; castling lifts the king and the rook before putting them down, as in
+        // Chess960 either may land where the other stood
+        if is_castling {
+            let row = from.0;
+            let (king_col, rook_col, rook_to_col) = self.castling_columns(from, to);
+            let king = board[from.0 * 8 + from.1].take();
+            let rook = board[row * 8 + rook_col].take();
+            board[row * 8 + rook_to_col] = rook;
+            board[row * 8 + king_col] = king;
+        } else {
+            board[to.0 * 8 + to.1] = board[from.0 * 8 + from.1].take();
+        }
 
         // Record the move in algebraic notation
         let mut move_text = String::new();
 
         if is_castling {
-            move_text = if to.1 == 6 { "O-O".to_string() } else { "O-O-O".to_string() };
+            move_text = if to.1 > from.1 { "O-O".to_string() } else { "O-O-O".to_string() };
         } else {
             move_text.push_str(Self::get_piece_symbol(piece));
             move_text.push_str(&Self::get_square_name(from));
@@ -385,50 +562,184 @@ impl GameState {
         if piece.piece_type == PieceType::Pawn {
             if (piece.color == PieceColor::White && to.0 == 0) ||
                (piece.color == PieceColor::Black && to.0 == 7) {
-                // Promote to queen by default
                 board[to.0 * 8 + to.1] = Some(Piece {
-                    piece_type: PieceType::Queen,
+                    piece_type: promotion,
                     color: piece.color,
                 });
-                move_text.push_str("=Q");
+                move_text.push('=');
+                move_text.push_str(Self::get_piece_symbol(Piece { piece_type: promotion, color: piece.color }));
             }
         }
 
-        // Update game status
+        self.last_move = Some((from, to));
+        self.en_passant = (piece.piece_type == PieceType::Pawn && from.0.abs_diff(to.0) == 2).then_some(((from.0 + to.0) / 2, from.1));
+
+        // Update the move counters used by FEN
+        if piece.piece_type == PieceType::Pawn || is_capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        let move_number = self.fullmove_number;
+        if piece.color == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+
+        // Switch turns
+        self.current_turn = if self.current_turn == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+
+        // Update game status for the side that is now to move
         self.update_game_status(board);
 
         // Add check or checkmate symbol
-        match self.status {
-            GameStatus::Check => move_text.push('+'),
-            GameStatus::Checkmate => move_text.push('#'),
-            _ => {}
+        let suffix = match self.status {
+            GameStatus::Check => "+",
+            GameStatus::Checkmate => "#",
+            _ => "",
+        };
+        move_text.push_str(suffix);
+        san.push_str(suffix);
+        self.san_moves.push_back(san);
+        self.move_comments.push_back(None);
+        self.move_qualities.push_back(None);
+        self.move_clocks.push_back(None);
+        self.move_evals.push_back(None);
+        self.move_marks.push_back(None);
+        self.captures.push_back(captured);
+        // The review no longer covers the whole game
+        self.review = None;
+
+        // Keep the deepest named opening the game has passed through
+        if self.start_fen.is_none() {
+            if let Some(opening) = classify(&self.san_moves) {
+                self.opening = Some(opening);
+            }
         }
 
         // Add move to history
         if piece.color == PieceColor::White {
-            self.move_history.push_back(format!("{}. {}", self.move_history.len() / 2 + 1, move_text));
+            self.move_history.push_back(format!("{}. {}", move_number, move_text));
+        } else if let Some(last) = self.move_history.pop_back() {
+            self.move_history.push_back(format!("{} {}", last, move_text));
+            undo.history_line = Some(last);
         } else {
-            if let Some(last) = self.move_history.last() {
-                let mut new_last = last.clone();
-                new_last.push_str(&format!(" {}", move_text));
-                self.move_history.pop_back();
-                self.move_history.push_back(new_last);
-            }
+            // Game started from a position with Black to move
+            self.move_history.push_back(format!("{}... {}", move_number, move_text));
+        }
+        self.undo_stack.push_back(undo);
+        self.redo_stack.clear();
+        // Moving instead of answering declines the opponent's draw offer
+        if self.draw_offer != Some(piece.color) {
+            self.draw_offer = None;
         }
 
-        self.last_move = Some((from, to));
+        true
+    }
 
-        // Switch turns
-        self.current_turn = if self.current_turn == PieceColor::White {
-            PieceColor::Black
+    /// Takes back the last move, on `board` as well; false when there is none to take back
+    pub fn undo_move(&mut self, board: &mut [Option<Piece>]) -> bool {
+        let Some(undo) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        let (from, to) = (undo.from, undo.to);
+        if self.was_castling(&undo) {
+            let (king_col, rook_col, rook_to_col) = self.castling_columns(from, to);
+            let rook = board[from.0 * 8 + rook_to_col].take();
+            board[from.0 * 8 + king_col] = None;
+            board[from.0 * 8 + rook_col] = rook;
+            board[from.0 * 8 + from.1] = Some(undo.moved);
         } else {
-            PieceColor::White
+            board[to.0 * 8 + to.1] = None;
+            board[from.0 * 8 + from.1] = Some(undo.moved);
+        }
+        if let Some((square, piece)) = undo.captured {
+            board[square] = Some(piece);
+        }
+
+        (self.white_can_castle_kingside, self.white_can_castle_queenside, self.black_can_castle_kingside, self.black_can_castle_queenside) = undo.castling_rights;
+        self.last_move = undo.last_move;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.status = undo.status;
+        self.opening = undo.opening;
+        self.draw_offer = undo.draw_offer;
+        self.current_turn = undo.moved.color;
+        if undo.moved.color == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+        self.san_moves.pop_back();
+        self.move_comments.pop_back();
+        self.move_qualities.pop_back();
+        self.move_clocks.pop_back();
+        self.move_evals.pop_back();
+        self.move_marks.pop_back();
+        self.captures.pop_back();
+        self.move_history.pop_back();
+        if let Some(line) = undo.history_line {
+            self.move_history.push_back(line);
+        }
+        self.review = None;
+        self.redo_stack.push_back((from, to, undo.promotion));
+        true
+    }
+
+    /// Whether the move `undo` takes back was castling: in Chess960 the king went onto a rook
+    /// square it still had the right to castle with, which no other king move can reach
+    fn was_castling(&self, undo: &UndoMove) -> bool {
+        let (from, to) = (undo.from, undo.to);
+        if undo.moved.piece_type != PieceType::King || from.0 != to.0 {
+            return false;
+        }
+        let Some((kingside, queenside)) = self.castling_files else {
+            return from.1.abs_diff(to.1) == 2;
         };
+        let (white_kingside, white_queenside, black_kingside, black_queenside) = undo.castling_rights;
+        let (kingside_right, queenside_right) = if undo.moved.color == PieceColor::White { (white_kingside, white_queenside) } else { (black_kingside, black_queenside) };
+        (to.1 > from.1 && to.1 == kingside && kingside_right) || (to.1 < from.1 && to.1 == queenside && queenside_right)
+    }
 
+    /// Plays the last move taken back again; false when there is none
+    pub fn redo_move(&mut self, board: &mut Vec<Option<Piece>>) -> bool {
+        let mut redo_stack = self.redo_stack.clone();
+        let Some((from, to, promotion)) = redo_stack.pop_back() else {
+            return false;
+        };
+        if !self.make_move_with_promotion(from, to, promotion, board) {
+            return false;
+        }
+        self.redo_stack = redo_stack;
         true
     }
 
-    fn update_game_status(&mut self, board: &Vec<Option<Piece>>) {
+    /// All legal moves for the side to move
+    pub fn legal_moves(&self, board: &Vec<Option<Piece>>) -> Vec<((usize, usize), (usize, usize))> {
+        // Only the squares each piece can reach on the bitboards need the full rules checked
+        let bitboards = Bitboards::new(board);
+        let mut moves = Vec::new();
+        for from in 0..64 {
+            let piece = match board[from] {
+                Some(piece) if piece.color == self.current_turn => piece,
+                _ => continue,
+            };
+            let mut targets = bitboards.targets(from, piece);
+            // A Chess960 king castles onto its own rooks, which the bitboards leave out
+            if let (PieceType::King, Some((kingside, queenside))) = (piece.piece_type, self.castling_files) {
+                targets |= 1 << (from / 8 * 8 + kingside) | 1 << (from / 8 * 8 + queenside);
+            }
+            for to in squares(targets) {
+                if self.is_valid_move((from / 8, from % 8), (to / 8, to % 8), board) {
+                    moves.push(((from / 8, from % 8), (to / 8, to % 8)));
+                }
+            }
+        }
+        moves
+    }
+
+    pub fn update_game_status(&mut self, board: &Vec<Option<Piece>>) {
         // Find the current player's king
         let mut king_pos = None;
         for row in 0..8 {
@@ -0,0 +1,56 @@
+use crate::pieces::Piece;
+use super::epd::parse_epd_file;
+use super::fen::parse_fen;
+use super::game_state::GameState;
+use super::pgn::parse_pgn;
+use super::uci_log::{is_uci_log, parse_uci_log};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportFormat {
+    Fen,
+    Epd,
+    Pgn,
+    UciLog,
+}
+
+/// Guesses whether a piece of text is a FEN, an EPD, a PGN or a UCI transcript from its shape
+pub fn detect_format(text: &str) -> Option<ImportFormat> {
+    let text = text.trim();
+    let first_field = text.split_whitespace().next()?;
+
+    if is_uci_log(text) {
+        Some(ImportFormat::UciLog)
+    } else if text.starts_with('[') {
+        Some(ImportFormat::Pgn)
+    } else if first_field.matches('/').count() == 7 {
+        // EPD has no move counters: four fields, then opcodes
+        let first_line: Vec<&str> = text.lines().next()?.split_whitespace().collect();
+        let is_fen = first_line.len() == 6 && first_line[4..].iter().all(|f| f.parse::<u32>().is_ok());
+        Some(if is_fen { ImportFormat::Fen } else { ImportFormat::Epd })
+    } else if first_field.starts_with(|c: char| c.is_ascii_digit()) && first_field.contains('.') {
+        Some(ImportFormat::Pgn)
+    } else {
+        None
+    }
+}
+
+/// Loads a position from FEN, EPD, PGN or UCI transcript text, detecting the format automatically.
+/// For multi-line EPD text the first record is used.
+pub fn import_text(text: &str) -> Result<(GameState, Vec<Option<Piece>>), String> {
+    match detect_format(text) {
+        Some(ImportFormat::Fen) => parse_fen(text.trim()).map_err(|e| format!("Invalid FEN: {}", e)),
+        Some(ImportFormat::Epd) => parse_epd_file(text)
+            .map_err(|e| format!("Invalid EPD: {}", e))?
+            .into_iter()
+            .next()
+            .map(|record| (record.state, record.board))
+            .ok_or_else(|| "Invalid EPD: no records".to_string()),
+        Some(ImportFormat::Pgn) => parse_pgn(text)
+            .and_then(|game| game.replay())
+            .map_err(|e| format!("Invalid PGN: {}", e)),
+        Some(ImportFormat::UciLog) => parse_uci_log(text)
+            .and_then(|game| game.replay())
+            .map_err(|e| format!("Invalid UCI log: {}", e)),
+        None => Err("The text is not a FEN, EPD, PGN or UCI log.".to_string()),
+    }
+}
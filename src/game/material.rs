@@ -0,0 +1,52 @@
+use druid::im::Vector;
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::threats::piece_value;
+
+/// Pieces of each kind a side starts with
+const STARTING_PIECES: [(PieceType, usize); 5] = [
+    (PieceType::Queen, 1),
+    (PieceType::Rook, 2),
+    (PieceType::Bishop, 2),
+    (PieceType::Knight, 2),
+    (PieceType::Pawn, 8),
+];
+
+/// Pieces of `color` no longer on the board, most valuable first. A piece beyond the starting
+/// count was promoted from a pawn, which is then not counted as captured.
+pub fn captured_pieces(board: &[Option<Piece>], color: PieceColor) -> Vec<PieceType> {
+    let count = |piece_type: PieceType| {
+        board.iter().flatten().filter(|piece| piece.color == color && piece.piece_type == piece_type).count()
+    };
+    let mut captured = Vec::new();
+    let mut promoted = 0;
+    for (piece_type, start) in STARTING_PIECES {
+        let left = count(piece_type);
+        if piece_type == PieceType::Pawn {
+            captured.extend(std::iter::repeat_n(piece_type, start.saturating_sub(left + promoted)));
+        } else if left > start {
+            promoted += left - start;
+        } else {
+            captured.extend(std::iter::repeat_n(piece_type, start - left));
+        }
+    }
+    captured
+}
+
+/// Pieces of `color` taken in the game, most valuable first, from the capture log of
+/// `GameState::captures`
+pub fn captured_in_game(captures: &Vector<Option<Piece>>, color: PieceColor) -> Vec<PieceType> {
+    let mut captured: Vec<PieceType> = captures.iter().flatten()
+        .filter(|piece| piece.color == color)
+        .map(|piece| piece.piece_type)
+        .collect();
+    captured.sort_by_key(|&piece_type| -piece_value(piece_type));
+    captured
+}
+
+/// Material of `color` minus the opponent's, in pawns
+pub fn material_balance(board: &[Option<Piece>], color: PieceColor) -> i32 {
+    board.iter().flatten()
+        .filter(|piece| piece.piece_type != PieceType::King)
+        .map(|piece| if piece.color == color { piece_value(piece.piece_type) } else { -piece_value(piece.piece_type) })
+        .sum()
+}
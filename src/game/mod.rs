@@ -1 +1,18 @@
-pub mod game_state;
\ No newline at end of file
+pub mod annotation;
+pub mod bitboard;
+pub mod clock;
+pub mod eco;
+pub mod epd;
+pub mod fen;
+pub mod game_state;
+pub mod import;
+pub mod material;
+pub mod notation;
+pub mod perft;
+pub mod pgn;
+pub mod player;
+pub mod save;
+pub mod start;
+pub mod threats;
+pub mod uci_log;
+pub mod variation;
\ No newline at end of file
@@ -0,0 +1,199 @@
+use crate::pieces::{Piece, PieceType};
+use super::fen::{parse_square, square_name};
+use super::game_state::GameState;
+
+pub type ParsedMove = ((usize, usize), (usize, usize), PieceType);
+
+/// Resolves a move written in SAN (`Nbd7`, `exd5`, `O-O`, `e8=Q+`) against the legal moves of
+/// the position. Long algebraic moves as written in the move history (`Ng1f3`) are accepted too.
+/// The returned piece type is the promotion choice, which is a queen for non-promoting moves.
+pub fn parse_san(state: &GameState, board: &Vec<Option<Piece>>, san: &str) -> Option<ParsedMove> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    match san {
+        "O-O" | "0-0" => return state.castling_move(board, true).and_then(|(from, to)| find_move(state, board, from, to)),
+        "O-O-O" | "0-0-0" => return state.castling_move(board, false).and_then(|(from, to)| find_move(state, board, from, to)),
+        _ => {}
+    }
+
+    let mut chars: Vec<char> = san.chars().filter(|&c| c != 'x' && c != '-').collect();
+
+    // Promotion suffix, with or without '='
+    let mut promotion = None;
+    if let Some(&last) = chars.last() {
+        if let Some(piece_type) = piece_from_letter(last) {
+            if chars.len() >= 3 && piece_type != PieceType::King && piece_type != PieceType::Pawn {
+                let before = chars[chars.len() - 2];
+                if before == '=' || before.is_ascii_digit() {
+                    promotion = Some(piece_type);
+                    chars.pop();
+                    if before == '=' {
+                        chars.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    let piece_type = match chars.first().copied().and_then(piece_from_letter) {
+        Some(piece_type) => {
+            chars.remove(0);
+            piece_type
+        }
+        None => PieceType::Pawn,
+    };
+
+    if chars.len() < 2 || chars.len() > 4 {
+        return None;
+    }
+    let target: String = chars[chars.len() - 2..].iter().collect();
+    let to = parse_square(&target)?;
+
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in &chars[..chars.len() - 2] {
+        match c {
+            'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(8 - c.to_digit(10)? as usize),
+            _ => return None,
+        }
+    }
+
+    let candidates: Vec<_> = state.legal_moves(board).into_iter()
+        .filter(|&(from, move_to)| {
+            move_to == to
+                && matches!(board[from.0 * 8 + from.1], Some(p) if p.piece_type == piece_type)
+                && from_file.is_none_or(|col| from.1 == col)
+                && from_rank.is_none_or(|row| from.0 == row)
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [(from, to)] => Some((*from, *to, promotion.unwrap_or(PieceType::Queen))),
+        _ => None,
+    }
+}
+
+/// Resolves a move in UCI coordinate notation (`e2e4`, `e7e8q`, castling as `e1g1`, or as the
+/// king taking its own rook in Chess960)
+pub fn parse_uci_move(state: &GameState, board: &Vec<Option<Piece>>, uci: &str) -> Option<ParsedMove> {
+    if !uci.is_ascii() || uci.len() < 4 || uci.len() > 5 {
+        return None;
+    }
+    let from = parse_square(&uci[0..2])?;
+    let to = parse_square(&uci[2..4])?;
+    let promotes = board[from.0 * 8 + from.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn) && (to.0 == 0 || to.0 == 7);
+    let promotion = match uci[4..].chars().next() {
+        // Only a pawn reaching the last rank promotes, written in lower case, and never to a king
+        Some(c @ ('q' | 'r' | 'b' | 'n')) if promotes => piece_from_letter(c.to_ascii_uppercase())?,
+        Some(_) => return None,
+        None => PieceType::Queen,
+    };
+    if state.is_valid_move(from, to, board) {
+        Some((from, to, promotion))
+    } else {
+        None
+    }
+}
+
+/// Writes a legal move in SAN. The check suffix is left to the caller, since it depends on
+/// the position after the move.
+pub fn move_to_san(state: &GameState, board: &Vec<Option<Piece>>, from: (usize, usize), to: (usize, usize), promotion: PieceType) -> String {
+    let piece = match board[from.0 * 8 + from.1] {
+        Some(p) => p,
+        None => return String::new(),
+    };
+
+    if state.is_castling_move(from, to, board) {
+        return if to.1 > from.1 { "O-O".to_string() } else { "O-O-O".to_string() };
+    }
+
+    let mut san = String::new();
+    if piece.piece_type == PieceType::Pawn {
+        // Pawns only change file when capturing, en passant included
+        if from.1 != to.1 {
+            san.push((b'a' + from.1 as u8) as char);
+            san.push('x');
+        }
+        san.push_str(&square_name(to));
+        if to.0 == 0 || to.0 == 7 {
+            san.push('=');
+            san.push(piece_letter(promotion));
+        }
+        return san;
+    }
+
+    san.push(piece_letter(piece.piece_type));
+
+    // Other pieces of the same kind that could also reach the target square
+    let rivals: Vec<(usize, usize)> = (0..64)
+        .map(|i| (i / 8, i % 8))
+        .filter(|&square| {
+            square != from
+                && matches!(board[square.0 * 8 + square.1], Some(p) if p.piece_type == piece.piece_type && p.color == piece.color)
+                && state.is_valid_move(square, to, board)
+        })
+        .collect();
+    if !rivals.is_empty() {
+        let file = (b'a' + from.1 as u8) as char;
+        let rank = char::from_digit(8 - from.0 as u32, 10).unwrap();
+        if rivals.iter().all(|square| square.1 != from.1) {
+            san.push(file);
+        } else if rivals.iter().all(|square| square.0 != from.0) {
+            san.push(rank);
+        } else {
+            san.push(file);
+            san.push(rank);
+        }
+    }
+
+    if board[to.0 * 8 + to.1].is_some() {
+        san.push('x');
+    }
+    san.push_str(&square_name(to));
+    san
+}
+
+/// Replaces the piece letters of a move with Unicode figurines, e.g. `Nf3` becomes `♞f3`
+pub fn to_figurine(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'K' => '♚',
+            'Q' => '♛',
+            'R' => '♜',
+            'B' => '♝',
+            'N' => '♞',
+            c => c,
+        })
+        .collect()
+}
+
+fn find_move(state: &GameState, board: &Vec<Option<Piece>>, from: (usize, usize), to: (usize, usize)) -> Option<ParsedMove> {
+    if state.is_valid_move(from, to, board) {
+        Some((from, to, PieceType::Queen))
+    } else {
+        None
+    }
+}
+
+fn piece_from_letter(c: char) -> Option<PieceType> {
+    match c {
+        'K' => Some(PieceType::King),
+        'Q' => Some(PieceType::Queen),
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'N' => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+pub fn piece_letter(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn => 'P',
+    }
+}
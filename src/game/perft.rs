@@ -0,0 +1,81 @@
+use crate::pieces::{Piece, PieceType};
+use super::fen::square_name;
+use super::game_state::GameState;
+use super::notation::piece_letter;
+
+/// Pieces a pawn can promote to; perft counts each promotion as a separate move
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+/// Number of leaf positions of the legal move tree `depth` plies deep, to compare the move
+/// generator with published counts
+pub fn perft(state: &GameState, board: &Vec<Option<Piece>>, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    moves(state, board).into_iter()
+        .map(|(_, child, child_board)| perft(&child, &child_board, depth - 1))
+        .sum()
+}
+
+/// Perft of the subtree of each legal move, as the move in UCI notation and its node count.
+/// Comparing these with another generator's shows which move's subtree goes wrong.
+pub fn divide(state: &GameState, board: &Vec<Option<Piece>>, depth: u32) -> Vec<(String, u64)> {
+    moves(state, board).into_iter()
+        .map(|(uci, child, child_board)| (uci, perft(&child, &child_board, depth.saturating_sub(1))))
+        .collect()
+}
+
+/// Every legal move with the position it leads to, promotions expanded to all four pieces
+fn moves(state: &GameState, board: &Vec<Option<Piece>>) -> Vec<(String, GameState, Vec<Option<Piece>>)> {
+    let mut moves = Vec::new();
+    for (from, to) in state.legal_moves(board) {
+        let promotes = board[from.0 * 8 + from.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn) && (to.0 == 0 || to.0 == 7);
+        let promotions: &[PieceType] = if promotes { &PROMOTIONS } else { &[PieceType::Queen] };
+        for &promotion in promotions {
+            let mut child = state.clone();
+            let mut child_board = board.clone();
+            child.make_move_with_promotion(from, to, promotion, &mut child_board);
+            let mut uci = format!("{}{}", square_name(from), square_name(to));
+            if promotes {
+                uci.push(piece_letter(promotion).to_ascii_lowercase());
+            }
+            moves.push((uci, child, child_board));
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fen::{parse_fen, STARTING_FEN};
+
+    fn perft_of(fen: &str, depth: u32) -> u64 {
+        let (state, board) = parse_fen(fen).unwrap();
+        perft(&state, &board, depth)
+    }
+
+    #[test]
+    fn start_position() {
+        assert_eq!(perft_of(STARTING_FEN, 4), 197_281);
+    }
+
+    /// Castling through and out of check, en passant and promotions
+    #[test]
+    fn kiwipete() {
+        assert_eq!(perft_of("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3), 97_862);
+    }
+
+    /// En passant captures that would leave the king in check along the rank
+    #[test]
+    fn position_3() {
+        assert_eq!(perft_of("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5), 674_624);
+    }
+
+    /// Chess960 castling with the king and rooks off their usual squares, in Shredder-FEN
+    #[test]
+    fn chess960() {
+        assert_eq!(perft_of("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 4), 326_672);
+        assert_eq!(perft_of("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 3), 31_058);
+    }
+}
@@ -0,0 +1,471 @@
+use druid::im::Vector;
+use crate::pieces::{Piece, PieceColor};
+use super::annotation::{split_suffix, Evaluation, Marks, MoveQuality};
+use super::clock::Clock;
+use super::fen::{parse_fen, STARTING_FEN};
+use super::game_state::GameState;
+use super::notation::parse_san;
+use super::player::Player;
+use super::variation::SideLine;
+
+/// A game state together with its board
+pub type Position = (GameState, Vec<Option<Piece>>);
+
+/// A single game read from PGN: its tag pairs, the mainline moves in SAN with the comment, move
+/// quality annotation, evaluation, clock time and board marks following each of them, the side
+/// lines and the result token
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub comments: Vec<Option<String>>, // One entry per move
+    pub qualities: Vec<Option<MoveQuality>>, // One entry per move
+    pub clocks: Vec<Option<u64>>, // One entry per move: the mover's time left after it, in milliseconds
+    pub evals: Vec<Option<Evaluation>>, // One entry per move
+    pub marks: Vec<Option<Marks>>, // One entry per move
+    pub variations: Vec<SideLine>, // Without their comments and annotations
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    /// Builds the PGN record of a game played in the app, with its opening tags when known
+    pub fn from_game(state: &GameState) -> Self {
+        let result = state.result();
+
+        let mut tags: Vec<(String, String)> = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", result),
+        ].iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+
+        // Known players replace the placeholder names, with their other tags after the result
+        for (side, player) in [("White", &state.white_player), ("Black", &state.black_player)] {
+            if let Some(player) = player {
+                let mut player_tags = player.to_tags(side).into_iter();
+                if let Some((_, name)) = player_tags.next() {
+                    if let Some(tag) = tags.iter_mut().find(|(tag, _)| tag == side) {
+                        tag.1 = name;
+                    }
+                }
+                tags.extend(player_tags);
+            }
+        }
+
+        if let Some(fen) = &state.start_fen {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen.clone()));
+        }
+        // Other programs read castling from rooks off the corners only in Chess960
+        if state.castling_files.is_some() {
+            tags.push(("Variant".to_string(), "Chess960".to_string()));
+        }
+        if let Some(opening) = &state.opening {
+            tags.push(("ECO".to_string(), opening.eco.clone()));
+            tags.push(("Opening".to_string(), opening.name.clone()));
+        }
+
+        Self {
+            tags,
+            moves: state.san_moves.iter().cloned().collect(),
+            comments: state.move_comments.iter().cloned().collect(),
+            qualities: state.move_qualities.iter().cloned().collect(),
+            clocks: state.move_clocks.iter().cloned().collect(),
+            evals: state.move_evals.iter().cloned().collect(),
+            marks: state.move_marks.iter().cloned().collect(),
+            variations: state.variations.iter().cloned().collect(),
+            result: Some(result.to_string()),
+        }
+    }
+
+    /// Records the times `clock` showed after the moves it ran for, the last moves of the game
+    pub fn set_clock(&mut self, clock: &Clock) {
+        let (_, white_first) = self.first_move();
+        let white_last = white_first == (self.moves.len() % 2 == 1);
+        let times = clock.left_after_moves(if white_last { PieceColor::White } else { PieceColor::Black });
+        let Some(start) = self.moves.len().checked_sub(times.len()) else { return };
+        self.clocks.resize(self.moves.len(), None);
+        for (slot, time) in self.clocks[start..].iter_mut().zip(times) {
+            *slot = Some(time);
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of a tag, adding the tag when missing
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Plays the mainline from the starting position (or the `FEN` tag) and returns the final position
+    pub fn replay(&self) -> Result<(GameState, Vec<Option<Piece>>), String> {
+        let mut positions = self.replay_positions()?;
+        Ok(positions.pop().unwrap())
+    }
+
+    /// Plays the mainline and returns every position from the start, one more than there are moves
+    pub fn replay_positions(&self) -> Result<Vec<Position>, String> {
+        let start = self.tag("FEN").unwrap_or(STARTING_FEN);
+        let (mut state, mut board) = parse_fen(start).map_err(|e| format!("invalid FEN tag: {}", e))?;
+        state.white_player = Player::from_tags(&self.tags, "White");
+        state.black_player = Player::from_tags(&self.tags, "Black");
+        let mut positions = vec![(state.clone(), board.clone())];
+
+        for (i, san) in self.moves.iter().enumerate() {
+            let move_number = state.fullmove_number;
+            let (from, to, promotion) = parse_san(&state, &board, san)
+                .ok_or_else(|| format!("illegal or ambiguous move '{}' at move {}", san, move_number))?;
+            if !state.make_move_with_promotion(from, to, promotion, &mut board) {
+                return Err(format!("could not play move {} ('{}')", i + 1, san));
+            }
+            if let Some(comment) = self.comments.get(i).cloned().flatten() {
+                state.move_comments.set(i, Some(comment));
+            }
+            if let Some(quality) = self.qualities.get(i).copied().flatten() {
+                state.move_qualities.set(i, Some(quality));
+            }
+            if let Some(time) = self.clocks.get(i).copied().flatten() {
+                state.move_clocks.set(i, Some(time));
+            }
+            if let Some(eval) = self.evals.get(i).copied().flatten() {
+                state.move_evals.set(i, Some(eval));
+            }
+            if let Some(marks) = self.marks.get(i).cloned().flatten() {
+                state.move_marks.set(i, Some(marks));
+            }
+            positions.push((state.clone(), board.clone()));
+        }
+        // The side lines belong to the game as a whole
+        if let Some((last, _)) = positions.last_mut() {
+            last.variations = self.variations.iter().cloned().collect();
+        }
+
+        Ok(positions)
+    }
+
+    /// Writes the game as PGN text, wrapping the movetext at 80 columns
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+        }
+        pgn.push('\n');
+
+        let (number, white_to_move) = self.first_move();
+        let mut tokens = Vec::new();
+        self.push_movetext(&mut tokens, None, &self.moves, number, white_to_move);
+        tokens.push(self.result.clone().unwrap_or_else(|| "*".to_string()));
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push('\n');
+        pgn
+    }
+
+    /// The number of the first move and whether White plays it
+    fn first_move(&self) -> (u32, bool) {
+        match self.tag("FEN").and_then(|fen| parse_fen(fen).ok()) {
+            Some((state, _)) => (state.fullmove_number, state.current_turn == PieceColor::White),
+            None => (1, true),
+        }
+    }
+
+    /// Adds the moves of a line, the mainline for None, to the movetext, each followed by its
+    /// annotations and then by the side lines replacing it in parentheses
+    fn push_movetext(&self, tokens: &mut Vec<String>, line: Option<usize>, moves: &[String], mut number: u32, mut white_to_move: bool) {
+        let mut after_aside = false; // Black's move is numbered again after a comment or a side line
+        for (i, san) in moves.iter().enumerate() {
+            if white_to_move {
+                tokens.push(format!("{}. {}", number, san));
+            } else if i == 0 || after_aside {
+                tokens.push(format!("{}... {}", number, san));
+            } else {
+                tokens.push(san.clone());
+            }
+            after_aside = false;
+            if line.is_none() {
+                if let Some(quality) = self.qualities.get(i).copied().flatten() {
+                    tokens.push(format!("${}", quality.nag()));
+                }
+                // Evaluations, clock times and marks go first in the comment, as commands such
+                // as "[%eval 0.43]", "[%clk 0:04:32]" and "[%cal Ge2e4]"
+                let eval = self.evals.get(i).copied().flatten().map(|eval| format!("[%eval {}]", eval.text()));
+                let clock = self.clocks.get(i).copied().flatten().map(|ms| format!("[%clk {}]", clock_time(ms)));
+                let marks = self.marks.get(i).cloned().flatten().map(|marks| marks.commands()).unwrap_or_default();
+                let comment = self.comments.get(i).cloned().flatten();
+                let text: Vec<String> = eval.into_iter().chain(clock).chain(marks).chain(comment).collect();
+                if !text.is_empty() {
+                    // Comments cannot contain a closing brace
+                    tokens.push(format!("{{{}}}", text.join(" ").replace('}', ")")));
+                    after_aside = true;
+                }
+            }
+            for (index, side_line) in self.variations.iter().enumerate() {
+                if side_line.parent != line || side_line.ply != i || side_line.moves.is_empty() {
+                    continue;
+                }
+                let start = tokens.len();
+                let side_moves: Vec<String> = side_line.moves.iter().cloned().collect();
+                self.push_movetext(tokens, Some(index), &side_moves, number, white_to_move);
+                tokens[start].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+                after_aside = true;
+            }
+            if !white_to_move {
+                number += 1;
+            }
+            white_to_move = !white_to_move;
+        }
+    }
+}
+
+/// Parses the first game of a PGN text. Comments, the evaluations, clock times and marks they
+/// carry (`[%eval 0.43]`, `[%clk 0:04:32]`, `[%cal Ge2e4]` and `[%csl Rd4]`) and move quality
+/// annotations (`$1` to `$6` or suffixes such as "?!") after mainline moves are kept, and the
+/// moves of the side lines; other NAGs and what is annotated in side lines are skipped.
+pub fn parse_pgn(text: &str) -> Result<PgnGame, String> {
+    let mut game = PgnGame::default();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && movetext.trim().is_empty() {
+            game.tags.push(parse_tag(line)?);
+        } else if line.starts_with('%') {
+            // Escaped line, ignored by definition
+            continue;
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    let mut chars = movetext.chars();
+    let mut token = String::new();
+    let mut open: Vec<usize> = Vec::new(); // Side lines entered and not yet closed, innermost last
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                push_token(&mut game, &mut token, open.last().copied());
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (commands, comment) = split_commands(&comment);
+                if let (true, Some(clock), Some(eval), Some(marks)) = (open.is_empty(), game.clocks.last_mut(), game.evals.last_mut(), game.marks.last_mut()) {
+                    for (name, value) in commands {
+                        match name.as_str() {
+                            "clk" => *clock = parse_clock_time(&value).or(*clock),
+                            "eval" => *eval = Evaluation::parse(&value).or(*eval),
+                            "cal" | "csl" => {
+                                let mut added = marks.take().unwrap_or_default();
+                                added.add_command(&name, &value);
+                                *marks = (!added.is_empty()).then_some(added);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // Keep mainline comments that follow a move; several in a row are joined
+                if let (true, Some(last)) = (open.is_empty(), game.comments.last_mut()) {
+                    if !comment.is_empty() {
+                        *last = Some(match last.take() {
+                            Some(previous) => format!("{} {}", previous, comment),
+                            None => comment,
+                        });
+                    }
+                }
+            }
+            ';' => {
+                push_token(&mut game, &mut token, open.last().copied());
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                push_token(&mut game, &mut token, open.last().copied());
+                // A side line replaces the move just played in the line it is in
+                let parent = open.last().copied();
+                let played = match parent {
+                    None => game.moves.len(),
+                    Some(index) => game.variations[index].moves.len(),
+                };
+                if played == 0 {
+                    return Err("side line before any move".to_string());
+                }
+                game.variations.push(SideLine { parent, ply: played - 1, moves: Vector::new() });
+                open.push(game.variations.len() - 1);
+            }
+            ')' => {
+                push_token(&mut game, &mut token, open.last().copied());
+                if open.pop().is_none() {
+                    return Err("unbalanced ')' in movetext".to_string());
+                }
+            }
+            c if c.is_whitespace() => push_token(&mut game, &mut token, open.last().copied()),
+            c => token.push(c),
+        }
+        if game.result.is_some() {
+            break;
+        }
+    }
+    push_token(&mut game, &mut token, open.last().copied());
+
+    if !open.is_empty() {
+        return Err("unterminated variation in movetext".to_string());
+    }
+    if game.moves.is_empty() && game.tags.is_empty() {
+        return Err("no tags or moves found".to_string());
+    }
+    Ok(game)
+}
+
+/// Parses every game of a PGN text such as a tournament or database export
+pub fn parse_pgn_games(text: &str) -> Result<Vec<PgnGame>, String> {
+    // A tag line after some movetext starts the next game
+    let mut chunks: Vec<String> = Vec::new();
+    let mut in_movetext = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && (in_movetext || chunks.is_empty()) {
+            chunks.push(String::new());
+            in_movetext = false;
+        } else if !trimmed.is_empty() && !trimmed.starts_with('[') && !trimmed.starts_with('%') {
+            if chunks.is_empty() {
+                chunks.push(String::new());
+            }
+            in_movetext = true;
+        }
+        if let Some(chunk) = chunks.last_mut() {
+            chunk.push_str(line);
+            chunk.push('\n');
+        }
+    }
+
+    chunks.iter()
+        .enumerate()
+        .map(|(i, chunk)| parse_pgn(chunk).map_err(|e| format!("game {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let inner = line.trim_start_matches('[').trim_end_matches(']').trim();
+    let (name, value) = inner.split_once(char::is_whitespace)
+        .ok_or_else(|| format!("malformed tag '{}'", line))?;
+    let value = value.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(format!("malformed tag '{}'", line));
+    }
+    let value = value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\");
+    Ok((name.to_string(), value))
+}
+
+/// Adds a word of movetext to `line`, a side line or the mainline for None
+fn push_token(game: &mut PgnGame, token: &mut String, line: Option<usize>) {
+    if token.is_empty() {
+        return;
+    }
+    let word = std::mem::take(token);
+    if let Some(index) = line {
+        // Only the moves of side lines are kept
+        let san = split_suffix(strip_move_number(&word)).0;
+        if !san.is_empty() && !san.starts_with('$') && !matches!(san, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            game.variations[index].moves.push_back(san.to_string());
+        }
+        return;
+    }
+    if let Some(nag) = word.strip_prefix('$') {
+        if let (Some(quality), Some(last)) = (nag.parse().ok().and_then(MoveQuality::from_nag), game.qualities.last_mut()) {
+            *last = Some(quality);
+        }
+        return;
+    }
+
+    if matches!(word.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+        game.result = Some(word);
+        return;
+    }
+
+    let (san, quality) = split_suffix(strip_move_number(&word));
+    if !san.is_empty() {
+        game.moves.push(san.to_string());
+        game.comments.push(None);
+        game.qualities.push(quality);
+        game.clocks.push(None);
+        game.evals.push(None);
+        game.marks.push(None);
+    }
+}
+
+/// Strips a leading move number such as "12." or "12..." from a word, possibly glued to the move
+fn strip_move_number(word: &str) -> &str {
+    let san = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    if san.len() < word.len() && san.starts_with('.') {
+        san.trim_start_matches('.')
+    } else {
+        word
+    }
+}
+
+/// Splits the commands embedded in a comment, such as `[%clk 0:04:32]`, into their name and
+/// value, returning them with the rest of the text with its whitespace collapsed
+fn split_commands(comment: &str) -> (Vec<(String, String)>, String) {
+    let mut commands = Vec::new();
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        let Some(end) = rest[start..].find(']') else { break };
+        text.push_str(&rest[..start]);
+        text.push(' ');
+        let command = rest[start + 2..start + end].trim();
+        let (name, value) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        commands.push((name.to_string(), value.trim().to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    (commands, text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// A clock time as "h:mm:ss", with tenths when there are any, as `[%clk]` gives it
+fn clock_time(ms: u64) -> String {
+    let (hours, minutes, seconds) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60);
+    match ms % 1000 / 100 {
+        0 => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+        tenths => format!("{}:{:02}:{:02}.{}", hours, minutes, seconds, tenths),
+    }
+}
+
+/// Reads a clock time such as "0:04:32" or "1:02:03.4" in milliseconds
+fn parse_clock_time(text: &str) -> Option<u64> {
+    let (hms, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let mut seconds = 0;
+    for part in hms.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    let fraction_ms = match fraction {
+        "" => 0,
+        digits => format!("{:0<3}", digits).get(..3)?.parse::<u64>().ok()?,
+    };
+    Some(seconds * 1000 + fraction_ms)
+}
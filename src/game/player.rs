@@ -0,0 +1,74 @@
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// A player of a game as named in its PGN tags
+#[derive(Clone, Debug, PartialEq, Data, Serialize, Deserialize)]
+pub struct Player {
+    pub name: String,
+    pub rating: Option<u32>,
+    pub title: Option<String>, // e.g. GM, IM or FM
+    pub country: Option<String>, // ISO or FIDE country code
+}
+
+impl Player {
+    /// Reads the player of one side from tags like `White`, `WhiteElo`, `WhiteTitle` and
+    /// `WhiteCountry`, where `side` is "White" or "Black". `None` when the name is unknown.
+    pub fn from_tags(tags: &[(String, String)], side: &str) -> Option<Self> {
+        let tag = |suffix: &str| {
+            let name = format!("{}{}", side, suffix);
+            tags.iter()
+                .find(|(tag, _)| *tag == name)
+                .map(|(_, value)| value.trim())
+                .filter(|value| !value.is_empty() && *value != "?" && *value != "-")
+        };
+        Some(Self {
+            name: tag("")?.to_string(),
+            rating: tag("Elo").and_then(|elo| elo.parse().ok()).filter(|&elo| elo > 0),
+            title: tag("Title").map(str::to_string),
+            country: tag("Country").map(str::to_string),
+        })
+    }
+
+    /// The player's tags for a PGN header, the inverse of `from_tags`
+    pub fn to_tags(&self, side: &str) -> Vec<(String, String)> {
+        let mut tags = vec![(side.to_string(), self.name.clone())];
+        if let Some(rating) = self.rating {
+            tags.push((format!("{}Elo", side), rating.to_string()));
+        }
+        if let Some(title) = &self.title {
+            tags.push((format!("{}Title", side), title.clone()));
+        }
+        if let Some(country) = &self.country {
+            tags.push((format!("{}Country", side), country.clone()));
+        }
+        tags
+    }
+
+    /// Title, name and rating, e.g. "GM Magnus Carlsen (2830)", then the country's flag
+    pub fn label(&self) -> String {
+        let mut label = match &self.title {
+            Some(title) => format!("{} {}", title, self.name),
+            None => self.name.clone(),
+        };
+        if let Some(rating) = self.rating {
+            label.push_str(&format!(" ({})", rating));
+        }
+        if let Some(country) = &self.country {
+            label.push(' ');
+            label.push_str(&flag(country));
+        }
+        label
+    }
+}
+
+/// The flag emoji of a two-letter ISO country code, which is a pair of regional indicator
+/// letters; other codes, like FIDE's three-letter ones, are shown as they are
+fn flag(country: &str) -> String {
+    if country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()) {
+        country.to_ascii_uppercase().chars()
+            .filter_map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+            .collect()
+    } else {
+        format!("[{}]", country)
+    }
+}
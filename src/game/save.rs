@@ -0,0 +1,55 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::pieces::{Piece, PieceType};
+use super::clock::Clock;
+use super::game_state::GameState;
+use super::pgn::Position;
+
+/// Version of the `.chess` format written by this build. Bump it when `GameState` changes in a
+/// way older files cannot be read into.
+const SAVE_VERSION: u32 = 3;
+
+/// Contents of a `.chess` file: the full game state and board as JSON, so that everything
+/// survives a restart, including what PGN cannot hold
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    version: u32,
+    state: GameState,
+    board: Vec<Option<Piece>>,
+    #[serde(default)]
+    clock: Option<Clock>, // Time left when the game was saved; format 3 on
+}
+
+/// Read before the rest so that files from newer versions get a clear error
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+pub fn save_game(path: &Path, state: &GameState, board: &[Option<Piece>], clock: Option<&Clock>) -> Result<(), String> {
+    let saved = SavedGame { version: SAVE_VERSION, state: state.clone(), board: board.to_vec(), clock: clock.cloned() };
+    let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// The game saved in `path`, with its clock when it was played with one
+pub fn load_game(path: &Path) -> Result<(Position, Option<Clock>), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let header: SaveHeader = serde_json::from_str(&text).map_err(|e| format!("not a saved game: {}", e))?;
+    if header.version > SAVE_VERSION {
+        return Err(format!("the game was saved by a newer version of the app (format {})", header.version));
+    }
+
+    let mut saved: SavedGame = serde_json::from_str(&text).map_err(|e| format!("corrupt saved game: {}", e))?;
+    if saved.board.len() != 64 {
+        return Err(format!("corrupt saved game: the board has {} squares", saved.board.len()));
+    }
+    // Format 1 kept the en passant square only as the last move, a pawn moving two squares
+    if let (1, Some((from, to))) = (header.version, saved.state.last_move) {
+        let pawn = saved.board[to.0 * 8 + to.1].is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+        if pawn && from.0.abs_diff(to.0) == 2 {
+            saved.state.en_passant = Some(((from.0 + to.0) / 2, to.1));
+        }
+    }
+    Ok(((saved.state, saved.board), saved.clock))
+}
@@ -0,0 +1,55 @@
+use druid::Data;
+use super::fen::STARTING_FEN;
+
+/// Where a new game starts from
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data)]
+pub enum StartPosition {
+    Standard,
+    Fen, // The FEN typed in the New game dialog
+    Chess960, // A random Fischer Random position
+}
+
+impl StartPosition {
+    pub const ALL: [StartPosition; 3] = [StartPosition::Standard, StartPosition::Fen, StartPosition::Chess960];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StartPosition::Standard => "Standard",
+            StartPosition::Fen => "From FEN",
+            StartPosition::Chess960 => "Chess960",
+        }
+    }
+}
+
+/// Pairs of the five squares left after the bishops and the queen that take the knights, in the
+/// order of the usual numbering of the Chess960 positions
+const KNIGHT_SQUARES: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+/// The FEN of Chess960 position `number` (0 to 959; 518 is the standard position), with the
+/// castling rights of X-FEN: each king may castle with either of its rooks.
+pub fn chess960_fen(number: u32) -> String {
+    let mut n = number as usize % 960;
+    let mut rank = [' '; 8];
+    rank[2 * (n % 4) + 1] = 'B'; // On a light square
+    n /= 4;
+    rank[2 * (n % 4)] = 'B'; // On a dark square
+    n /= 4;
+
+    let empty = |rank: &[char; 8]| (0..8).filter(|&file| rank[file] == ' ').collect::<Vec<_>>();
+    rank[empty(&rank)[n % 6]] = 'Q';
+    n /= 6;
+    let (first, second) = KNIGHT_SQUARES[n];
+    let files = empty(&rank);
+    rank[files[first]] = 'N';
+    rank[files[second]] = 'N';
+    // The king always stands between the rooks
+    for (file, piece) in empty(&rank).into_iter().zip(['R', 'K', 'R']) {
+        rank[file] = piece;
+    }
+
+    let white: String = rank.iter().collect();
+    if white == "RNBQKBNR" {
+        return STARTING_FEN.to_string();
+    }
+    format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", white.to_lowercase(), white)
+}
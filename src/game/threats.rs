@@ -0,0 +1,107 @@
+use crate::pieces::{Piece, PieceColor, PieceType};
+use super::game_state::GameState;
+
+/// Whether the piece on `from` attacks `to`, ignoring pins and whose turn it is
+pub fn attacks(board: &[Option<Piece>], from: (usize, usize), to: (usize, usize)) -> bool {
+    let piece = match board[from.0 * 8 + from.1] {
+        Some(piece) => piece,
+        None => return false,
+    };
+    let from_coords = (from.0 as i32, from.1 as i32);
+    let to_coords = (to.0 as i32, to.1 as i32);
+    if from_coords == to_coords || !piece.get_raw_moves(from_coords).contains(&to_coords) {
+        return false;
+    }
+
+    match piece.piece_type {
+        // Pawns only attack diagonally forward
+        PieceType::Pawn => {
+            let forward = if piece.color == PieceColor::White { -1 } else { 1 };
+            (to_coords.1 - from_coords.1).abs() == 1 && to_coords.0 - from_coords.0 == forward
+        }
+        PieceType::Knight | PieceType::King => true,
+        _ => {
+            // Sliding pieces need a clear path
+            let step_x = (to_coords.1 - from_coords.1).signum();
+            let step_y = (to_coords.0 - from_coords.0).signum();
+            let (mut x, mut y) = (from_coords.1 + step_x, from_coords.0 + step_y);
+            while (x, y) != (to_coords.1, to_coords.0) {
+                if board[(y as usize) * 8 + (x as usize)].is_some() {
+                    return false;
+                }
+                x += step_x;
+                y += step_y;
+            }
+            true
+        }
+    }
+}
+
+/// Squares of the pieces of `color` that attack `square`
+fn attackers(board: &[Option<Piece>], square: (usize, usize), color: PieceColor) -> Vec<(usize, usize)> {
+    (0..64)
+        .filter(|&i| board[i].is_some_and(|piece| piece.color == color))
+        .map(|i| (i / 8, i % 8))
+        .filter(|&from| attacks(board, from, square))
+        .collect()
+}
+
+/// Material value in pawns; the king's is high enough that capturing it ends any exchange
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight | PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 100,
+    }
+}
+
+/// The pieces taking part in a capture sequence on `square` started by `color`, in the order
+/// they capture: both sides always recapture with their least valuable piece until one runs out
+pub fn capture_sequence(board: &[Option<Piece>], square: (usize, usize), color: PieceColor) -> Vec<Piece> {
+    let mut board = board.to_vec();
+    let target = square.0 * 8 + square.1;
+    let mut capturers = Vec::new();
+    let mut side = color;
+
+    // Removing each capturer from its square uncovers x-ray attackers behind it
+    while board[target].is_some() {
+        let capturer = attackers(&board, square, side).into_iter()
+            .min_by_key(|&(row, col)| board[row * 8 + col].map(|piece| piece_value(piece.piece_type)));
+        let Some((row, col)) = capturer else { break };
+        board[target] = board[row * 8 + col].take();
+        capturers.extend(board[target]);
+        side = if side == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+    }
+    capturers
+}
+
+/// Material `color` wins by starting a capture sequence on `square`, with both sides free to
+/// stop when further captures lose. `None` when `color` cannot capture there at all.
+pub fn exchange_gain(board: &[Option<Piece>], square: (usize, usize), color: PieceColor) -> Option<i32> {
+    let target = board[square.0 * 8 + square.1]?;
+    let capturers = capture_sequence(board, square, color);
+    if capturers.is_empty() {
+        return None;
+    }
+
+    // Each capture wins the piece taken last; the final capturer is never taken back
+    let captured: Vec<i32> = std::iter::once(target).chain(capturers[..capturers.len() - 1].iter().copied())
+        .map(|piece| piece_value(piece.piece_type))
+        .collect();
+
+    // Work back from the last capture: each side only recaptures when it gains by doing so
+    let reply = captured[1..].iter().rev().fold(0, |next, &value| (value - next).max(0));
+    Some(captured[0] - reply)
+}
+
+/// Pieces of the side to move that the opponent could win material by capturing, because they
+/// are attacked and not defended well enough
+pub fn threatened_pieces(state: &GameState, board: &[Option<Piece>]) -> Vec<usize> {
+    let opponent = if state.current_turn == PieceColor::White { PieceColor::Black } else { PieceColor::White };
+    (0..64)
+        .filter(|&i| board[i].is_some_and(|piece| piece.color == state.current_turn && piece.piece_type != PieceType::King))
+        .filter(|&i| exchange_gain(board, (i / 8, i % 8), opponent).is_some_and(|gain| gain > 0))
+        .collect()
+}
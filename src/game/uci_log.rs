@@ -0,0 +1,176 @@
+use crate::pieces::{Piece, PieceColor};
+use super::fen::{parse_fen, STARTING_FEN};
+use super::game_state::GameState;
+use super::notation::parse_uci_move;
+use super::pgn::PgnGame;
+
+/// The deepest principal variation the engine reported for a position
+#[derive(Clone, Debug)]
+struct EngineLine {
+    depth: u32,
+    score: Option<(String, i32)>, // "cp" or "mate", from the engine's point of view
+    pv: Vec<String>,
+}
+
+/// Whether the text looks like a UCI transcript rather than a PGN or FEN
+pub fn is_uci_log(text: &str) -> bool {
+    text.lines().any(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        command_start(&tokens).is_some_and(|at| {
+            tokens[at] == "position" && matches!(tokens.get(at + 1), Some(&"startpos") | Some(&"fen"))
+        })
+    })
+}
+
+/// Reconstructs the game played in a UCI transcript (the GUI's `position` commands and the
+/// engine's `bestmove` replies). The engine's deepest PV for each move it played is kept as
+/// the comment of that move, e.g. `{d18 +0.31 e4 e5 Nf3}` with the score from White's side.
+pub fn parse_uci_log(text: &str) -> Result<PgnGame, String> {
+    let mut start: Option<String> = None;
+    let mut moves: Vec<String> = Vec::new();
+    let mut lines: Vec<Option<EngineLine>> = Vec::new();
+    let mut searching = None; // Ply of the position the engine was last asked to search
+    let mut found = false;
+
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let tokens = match command_start(&tokens) {
+            Some(at) => &tokens[at..],
+            None => continue,
+        };
+
+        match tokens[0] {
+            "ucinewgame" => {
+                moves.clear();
+                lines.clear();
+                searching = None;
+            }
+            "position" => {
+                let (fen, rest) = match tokens.get(1) {
+                    Some(&"startpos") => (None, &tokens[2..]),
+                    Some(&"fen") => {
+                        let end = tokens.iter().position(|&t| t == "moves").unwrap_or(tokens.len());
+                        (Some(tokens[2..end].join(" ")), &tokens[end..])
+                    }
+                    _ => continue,
+                };
+                let new_moves: Vec<String> = rest.iter().skip(1).map(|m| m.to_string()).collect();
+                // A position that does not continue the game so far starts over
+                if fen != start || !new_moves.starts_with(&moves) {
+                    lines.truncate(new_moves.len());
+                }
+                start = fen;
+                moves = new_moves;
+                searching = Some(moves.len());
+                found = true;
+            }
+            "info" => {
+                if let (Some(ply), Some(info)) = (searching, parse_info(&tokens[1..])) {
+                    if lines.len() <= ply {
+                        lines.resize(ply + 1, None);
+                    }
+                    if lines[ply].as_ref().is_none_or(|line| info.depth >= line.depth) {
+                        lines[ply] = Some(info);
+                    }
+                }
+            }
+            "bestmove" => {
+                if let (Some(ply), Some(&best)) = (searching, tokens.get(1)) {
+                    if ply == moves.len() && best != "(none)" {
+                        moves.push(best.to_string());
+                    }
+                }
+                searching = None;
+            }
+            _ => {}
+        }
+    }
+
+    if !found {
+        return Err("no 'position' command found".to_string());
+    }
+
+    let start_fen = start.as_deref().unwrap_or(STARTING_FEN);
+    let (mut state, mut board) = parse_fen(start_fen).map_err(|e| format!("invalid FEN: {}", e))?;
+    let mut game = PgnGame::default();
+    if let Some(fen) = &start {
+        game.tags.push(("SetUp".to_string(), "1".to_string()));
+        game.tags.push(("FEN".to_string(), fen.clone()));
+    }
+
+    for (ply, uci) in moves.iter().enumerate() {
+        let comment = lines.get(ply).cloned().flatten().map(|line| describe_line(&line, &state, &board));
+        let (from, to, promotion) = parse_uci_move(&state, &board, uci)
+            .ok_or_else(|| format!("illegal move '{}' at ply {}", uci, ply + 1))?;
+        state.make_move_with_promotion(from, to, promotion, &mut board);
+        game.moves.push(state.san_moves.last().cloned().unwrap_or_default());
+        game.comments.push(comment);
+        game.qualities.push(None);
+        game.clocks.push(None);
+        game.evals.push(None);
+        game.marks.push(None);
+    }
+
+    Ok(game)
+}
+
+/// Index of the UCI command in a line, allowing a short prefix such as a timestamp or `<<`
+fn command_start(tokens: &[&str]) -> Option<usize> {
+    tokens.iter()
+        .take(4)
+        .position(|t| matches!(*t, "position" | "info" | "bestmove" | "ucinewgame"))
+}
+
+/// Reads the depth, score and PV of an `info` line; lines without a PV or for other multi-PV
+/// slots are ignored
+fn parse_info(tokens: &[&str]) -> Option<EngineLine> {
+    let mut line = EngineLine { depth: 0, score: None, pv: Vec::new() };
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            "depth" => line.depth = tokens.get(i + 1)?.parse().ok()?,
+            "multipv" if tokens.get(i + 1) != Some(&"1") => return None,
+            "score" => {
+                let kind = tokens.get(i + 1)?;
+                let value = tokens.get(i + 2)?.parse().ok()?;
+                line.score = Some((kind.to_string(), value));
+            }
+            "pv" => {
+                line.pv = tokens[i + 1..].iter().map(|m| m.to_string()).collect();
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if line.pv.is_empty() { None } else { Some(line) }
+}
+
+/// Formats an engine line as depth, score from White's side and the PV in SAN
+fn describe_line(line: &EngineLine, state: &GameState, board: &[Option<Piece>]) -> String {
+    let mut text = format!("d{}", line.depth);
+
+    if let Some((kind, value)) = &line.score {
+        let value = if state.current_turn == PieceColor::White { *value } else { -value };
+        match kind.as_str() {
+            "cp" => text.push_str(&format!(" {:+.2}", value as f64 / 100.0)),
+            "mate" => text.push_str(&format!(" #{}", value)),
+            _ => {}
+        }
+    }
+
+    // Play the PV on a copy to write it in SAN, stopping at the first move that does not fit
+    let mut state = state.clone();
+    let mut board = board.to_vec();
+    for uci in &line.pv {
+        match parse_uci_move(&state, &board, uci) {
+            Some((from, to, promotion)) => {
+                state.make_move_with_promotion(from, to, promotion, &mut board);
+                text.push(' ');
+                text.push_str(state.san_moves.last().map(String::as_str).unwrap_or(uci));
+            }
+            None => break,
+        }
+    }
+    text
+}
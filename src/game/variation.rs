@@ -0,0 +1,36 @@
+use druid::im::Vector;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// A side line of a game, as read from the parenthesized lines of PGN or kept from trying moves.
+/// It replaces the move at `ply` of the line it branches from: the mainline, or an earlier side
+/// line when it is nested in one.
+#[derive(Clone, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub struct SideLine {
+    pub parent: Option<usize>, // Index of the side line it branches from; None for the mainline
+    pub ply: usize, // Index in the parent's moves of the move it replaces
+    pub moves: Vector<String>, // In SAN
+}
+
+/// The moves from the start of the game to the position after `count` moves of `line`, a side
+/// line or the mainline for None
+pub fn moves_to(mainline: &Vector<String>, lines: &Vector<SideLine>, line: Option<usize>, count: usize) -> Vec<String> {
+    match line.and_then(|index| lines.get(index)) {
+        None => mainline.iter().take(count).cloned().collect(),
+        Some(side_line) => {
+            let mut moves = moves_to(mainline, lines, side_line.parent, side_line.ply);
+            moves.extend(side_line.moves.iter().take(count).cloned());
+            moves
+        }
+    }
+}
+
+/// Where a side line leaves the mainline, through the lines it is nested in: the number of
+/// mainline moves the two share
+pub fn mainline_ply(lines: &Vector<SideLine>, line: usize) -> usize {
+    match lines.get(line) {
+        Some(SideLine { parent: Some(parent), .. }) => mainline_ply(lines, *parent),
+        Some(side_line) => side_line.ply,
+        None => 0,
+    }
+}
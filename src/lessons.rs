@@ -0,0 +1,78 @@
+use crate::game::game_state::{GameState, GameStatus};
+
+/// What the move played in a lesson has to achieve
+#[derive(Clone, Copy)]
+pub enum Goal {
+    Check,
+    Checkmate,
+    Castle,
+    Promote,
+    Move(&'static str), // A specific move in SAN, without check marks
+}
+
+impl Goal {
+    pub fn reached(self, state: &GameState) -> bool {
+        let san = state.san_moves.last().map(|san| san.trim_end_matches(['+', '#'])).unwrap_or("");
+        match self {
+            Goal::Check => matches!(state.status, GameStatus::Check | GameStatus::Checkmate),
+            Goal::Checkmate => state.status == GameStatus::Checkmate,
+            Goal::Castle => san.starts_with("O-O"),
+            Goal::Promote => san.contains('='),
+            Goal::Move(expected) => san == expected,
+        }
+    }
+}
+
+/// A rule explained on a position where the user has to play one move using it
+pub struct Lesson {
+    pub title: &'static str,
+    pub instruction: &'static str,
+    pub hint: &'static str, // Shown after a wrong move
+    pub explanation: &'static str, // Shown once the goal is reached
+    pub fen: &'static str,
+    pub goal: Goal,
+}
+
+/// The rules tutorial for beginners, in order
+pub const LESSONS: [Lesson; 5] = [
+    Lesson {
+        title: "Check",
+        instruction: "Attack the black king with your rook.",
+        hint: "A rook moves along ranks and files. Put it on the same line as the king with nothing in between.",
+        explanation: "The king is in check: Black must get it out of the attack on the next move.",
+        fen: "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        goal: Goal::Check,
+    },
+    Lesson {
+        title: "Checkmate",
+        instruction: "Give check so that the black king has no way out.",
+        hint: "The black king is boxed in by its own pawns. Which rank can it not leave?",
+        explanation: "Checkmate! The king is attacked and cannot escape, block or capture the attacker, so the game is over.",
+        fen: "6k1/5ppp/8/8/8/8/8/4R1K1 w - - 0 1",
+        goal: Goal::Checkmate,
+    },
+    Lesson {
+        title: "Castling",
+        instruction: "Castle: move your king two squares towards a rook.",
+        hint: "Click the king, then the square two steps to its left or right. The rook jumps over it.",
+        explanation: "Castling moves the king to safety and brings the rook into play. It is only allowed when neither piece has moved, the squares between are empty and the king does not pass through check.",
+        fen: "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1",
+        goal: Goal::Castle,
+    },
+    Lesson {
+        title: "En passant",
+        instruction: "Black's pawn just moved two squares past yours. Capture it en passant.",
+        hint: "Your e-pawn can take the d-pawn as if it had moved only one square: move it diagonally to d6.",
+        explanation: "En passant is only possible on the move right after the two-square pawn advance.",
+        fen: "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        goal: Goal::Move("exd6"),
+    },
+    Lesson {
+        title: "Promotion",
+        instruction: "Promote your pawn by moving it to the last rank.",
+        hint: "Pawns move straight forward. Push the a-pawn one more square.",
+        explanation: "A pawn reaching the last rank becomes a queen, rook, bishop or knight of your choice; here it became a queen.",
+        fen: "4k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+        goal: Goal::Promote,
+    },
+];
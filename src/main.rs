@@ -1,24 +1,73 @@
+mod alert;
+mod analysis_board;
 mod app;
 mod board;
+mod book;
+mod bulletin;
+mod cli;
+mod delegate;
+mod dialog;
+mod engine;
 mod pieces;
 mod game;
+mod lessons;
+mod menu;
+mod scoresheet;
+mod settings;
+mod share;
+mod tabs;
+mod theme;
+mod timeline;
 
 use app::AppState;
 use board::chess_board::ChessBoard;
-use druid::{AppLauncher, WindowDesc, Widget};
+use board::game_view::game_view;
+use board::variation_tree::VariationTree;
+use delegate::Delegate;
+use druid::widget::{Either, Flex, SizedBox};
+use druid::{AppLauncher, WindowDesc, Widget, WidgetExt};
+
+#[cfg(feature = "debug-hud")]
+#[global_allocator]
+static ALLOCATOR: board::hud::CountingAllocator = board::hud::CountingAllocator;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::run(&args) {
+        return;
+    }
+
     let main_window = WindowDesc::new(build_ui())
         .title("Chess Board")
+        .menu(menu::menu_bar)
         .window_size((400.0, 400.0));
 
-    let initial_state = AppState::new();
+    let mut initial_state = AppState::new();
+    initial_state.system_dark = theme::system_prefers_dark();
+    // A broken settings file leaves the defaults, and is replaced once the settings are saved
+    match settings::load_settings() {
+        Ok(Some(saved)) => saved.apply(&mut initial_state),
+        Ok(None) => {}
+        Err(message) => eprintln!("Settings not loaded: {}", message),
+    }
 
     AppLauncher::with_window(main_window)
+        .delegate(Delegate::new())
         .launch(initial_state)
         .expect("Failed to launch application");
 }
 
+/// Width of the variation tree beside the board
+const VARIATION_TREE_WIDTH: f64 = 260.0;
+
 fn build_ui() -> impl Widget<AppState> {
-    ChessBoard::new()
+    let variation_tree = Either::new(
+        |data: &AppState, _env| data.show_variation_tree,
+        VariationTree::new().scroll().vertical().fix_width(VARIATION_TREE_WIDTH).expand_height().background(theme::PANEL_COLOR),
+        SizedBox::empty(),
+    );
+    let content = Flex::column()
+        .with_child(tabs::tab_bar())
+        .with_flex_child(Flex::row().with_flex_child(game_view(ChessBoard::new()), 1.0).with_child(variation_tree), 1.0);
+    theme::themed(content)
 }
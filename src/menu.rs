@@ -0,0 +1,180 @@
+use std::path::Path;
+use druid::{Env, FileDialogOptions, FileInfo, FileSpec, Menu, MenuItem, Selector, SysMods, WindowId};
+use crate::app::AppState;
+
+/// Opens the New game dialog
+pub const SHOW_NEW_GAME: Selector = Selector::new("chess_rust.menu.new-game");
+
+/// Opens a new game in another tab
+pub const NEW_TAB: Selector = Selector::new("chess_rust.menu.new-tab");
+
+/// Closes the game shown
+pub const CLOSE_TAB: Selector = Selector::new("chess_rust.menu.close-tab");
+
+/// Opens the analysis board on the position shown
+pub const ANALYZE_POSITION: Selector = Selector::new("chess_rust.menu.analyze-position");
+
+/// Opens the settings
+pub const SHOW_SETTINGS: Selector = Selector::new("chess_rust.menu.settings");
+
+/// Shows or hides the panel with the game's side lines
+pub const TOGGLE_VARIATION_TREE: Selector = Selector::new("chess_rust.menu.toggle-variation-tree");
+
+/// Hides or shows the pieces
+pub const TOGGLE_BLINDFOLD: Selector = Selector::new("chess_rust.menu.toggle-blindfold");
+
+/// Turns the board around
+pub const FLIP_BOARD: Selector = Selector::new("chess_rust.menu.flip-board");
+
+/// Shows the open panel for a game or position file
+pub const OPEN_FILE: Selector = Selector::new("chess_rust.menu.open");
+
+/// Sent by the open panel with the file picked, for the board to load
+pub const OPEN_GAME: Selector<FileInfo> = Selector::new("chess_rust.open-game");
+
+/// Shows the save panel for the game
+pub const SAVE_FILE: Selector = Selector::new("chess_rust.menu.save");
+
+/// Sent by the save panel with the file to write, for the board to save
+pub const SAVE_GAME: Selector<FileInfo> = Selector::new("chess_rust.save-game");
+
+/// The app's own format, which keeps everything about the game
+pub const SAVED_GAME_FILE: FileSpec = FileSpec::new("Saved game", &["chess"]);
+
+pub const PGN_FILE: FileSpec = FileSpec::new("PGN", &["pgn"]);
+
+/// Copies the position as FEN
+pub const COPY_FEN: Selector = Selector::new("chess_rust.menu.copy-fen");
+
+/// Copies the position as an EPD record
+pub const COPY_EPD: Selector = Selector::new("chess_rust.menu.copy-epd");
+
+/// Copies the game as PGN
+pub const COPY_PGN: Selector = Selector::new("chess_rust.menu.copy-pgn");
+
+/// Sets up the position or game on the clipboard
+pub const PASTE_POSITION: Selector = Selector::new("chess_rust.menu.paste-position");
+
+/// Opens the position shown on the Lichess analysis board
+pub const OPEN_LICHESS: Selector = Selector::new("chess_rust.menu.open-lichess");
+
+/// Opens the position shown on the Chess.com analysis board
+pub const OPEN_CHESS_COM: Selector = Selector::new("chess_rust.menu.open-chess-com");
+
+/// Shows the save panel for a diagram of the position
+pub const SHOW_EXPORT_DIAGRAM: Selector = Selector::new("chess_rust.menu.export-diagram");
+
+/// Shows the save panel for the game as an animated GIF
+pub const SHOW_EXPORT_GIF: Selector = Selector::new("chess_rust.menu.export-gif");
+
+/// Shows the save panel for the game's scoresheet
+pub const SHOW_PRINT_SCORESHEET: Selector = Selector::new("chess_rust.menu.print-scoresheet");
+
+/// Lists the snapshots the game can go back to
+pub const SHOW_HISTORY: Selector = Selector::new("chess_rust.menu.history");
+
+/// Takes back the last move
+pub const UNDO: Selector = Selector::new("chess_rust.menu.undo");
+
+/// Plays the last move taken back again
+pub const REDO: Selector = Selector::new("chess_rust.menu.redo");
+
+/// Asks the engine for a move to play
+pub const SHOW_HINT: Selector = Selector::new("chess_rust.menu.hint");
+
+/// Starts or stops analysing the position shown
+pub const TOGGLE_ANALYSIS: Selector = Selector::new("chess_rust.menu.toggle-analysis");
+
+/// Reviews the moves of the game
+pub const REVIEW_GAME: Selector = Selector::new("chess_rust.menu.review-game");
+
+/// Shows or hides the opening book's move
+pub const TOGGLE_BOOK_HINT: Selector = Selector::new("chess_rust.menu.toggle-book-hint");
+
+/// Starts the lessons from the first
+pub const START_LESSONS: Selector = Selector::new("chess_rust.menu.lessons");
+
+/// Draws the pieces larger, for low vision
+pub const TOGGLE_LOW_VISION: Selector = Selector::new("chess_rust.menu.toggle-low-vision");
+
+/// Shows or hides the pieces under attack
+pub const TOGGLE_THREATS: Selector = Selector::new("chess_rust.menu.toggle-threats");
+
+/// Shows or hides the paint timings over the board
+pub const TOGGLE_HUD: Selector = Selector::new("chess_rust.menu.toggle-hud");
+
+/// The open panel for games and positions: saved games, PGN and FEN files. It starts in
+/// `directory` when one was used before.
+pub fn open_options(directory: Option<&Path>) -> FileDialogOptions {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![SAVED_GAME_FILE, PGN_FILE, FileSpec::new("FEN", &["fen"])])
+        .title("Open game")
+        .accept_command(OPEN_GAME);
+    in_directory(options, directory)
+}
+
+/// The save panel for the game, in the app's own format or as PGN
+pub fn save_options(directory: Option<&Path>) -> FileDialogOptions {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![SAVED_GAME_FILE, PGN_FILE])
+        .default_type(SAVED_GAME_FILE)
+        .default_name("game.chess")
+        .title("Save game as")
+        .accept_command(SAVE_GAME);
+    in_directory(options, directory)
+}
+
+fn in_directory(options: FileDialogOptions, directory: Option<&Path>) -> FileDialogOptions {
+    match directory {
+        Some(directory) => options.starting_directory(directory).force_starting_directory(),
+        None => options,
+    }
+}
+
+/// The menu bar of the main window and the analysis board. The items only send commands, to
+/// the window of the menu: the app delegate handles those that open windows or change
+/// settings, the board those about its position. The items carry the keyboard shortcuts.
+pub fn menu_bar(_window: Option<WindowId>, _data: &AppState, _env: &Env) -> Menu<AppState> {
+    let file = Menu::new("File")
+        .entry(MenuItem::new("New Game...").command(SHOW_NEW_GAME).hotkey(SysMods::Cmd, "n"))
+        .entry(MenuItem::new("New Tab").command(NEW_TAB).hotkey(SysMods::CmdShift, "T"))
+        .entry(MenuItem::new("Close Tab").command(CLOSE_TAB).hotkey(SysMods::Cmd, "w"))
+        .separator()
+        .entry(MenuItem::new("Open...").command(OPEN_FILE).hotkey(SysMods::Cmd, "o"))
+        .entry(MenuItem::new("Save As...").command(SAVE_FILE).hotkey(SysMods::Cmd, "s"))
+        .separator()
+        .entry(MenuItem::new("Export Diagram...").command(SHOW_EXPORT_DIAGRAM).hotkey(SysMods::Cmd, "d"))
+        .entry(MenuItem::new("Export GIF...").command(SHOW_EXPORT_GIF).hotkey(SysMods::Cmd, "g"))
+        .entry(MenuItem::new("Print Scoresheet...").command(SHOW_PRINT_SCORESHEET).hotkey(SysMods::Cmd, "p"));
+    let edit = Menu::new("Edit")
+        .entry(MenuItem::new("Undo Move").command(UNDO).hotkey(SysMods::Cmd, "z"))
+        .entry(MenuItem::new("Redo Move").command(REDO).hotkey(SysMods::CmdShift, "Z"))
+        .entry(MenuItem::new("History...").command(SHOW_HISTORY).hotkey(SysMods::Cmd, "y"))
+        .separator()
+        .entry(MenuItem::new("Copy FEN").command(COPY_FEN).hotkey(SysMods::Cmd, "c"))
+        .entry(MenuItem::new("Copy EPD").command(COPY_EPD).hotkey(SysMods::Cmd, "e"))
+        .entry(MenuItem::new("Copy PGN").command(COPY_PGN).hotkey(SysMods::CmdShift, "C"))
+        .entry(MenuItem::new("Paste Position").command(PASTE_POSITION).hotkey(SysMods::Cmd, "v"))
+        .separator()
+        .entry(MenuItem::new("Open in Lichess").command(OPEN_LICHESS).hotkey(SysMods::CmdShift, "L"))
+        .entry(MenuItem::new("Open in Chess.com").command(OPEN_CHESS_COM).hotkey(SysMods::CmdShift, "K"));
+    let game = Menu::new("Game")
+        .entry(MenuItem::new("Flip Board").command(FLIP_BOARD).hotkey(SysMods::Cmd, "f"))
+        .entry(MenuItem::new("Analyze Current Position").command(ANALYZE_POSITION))
+        .separator()
+        .entry(MenuItem::new("Hint").command(SHOW_HINT).hotkey(SysMods::CmdShift, "H"))
+        .entry(MenuItem::new("Book Move").command(TOGGLE_BOOK_HINT).hotkey(SysMods::Cmd, "b"))
+        .entry(MenuItem::new("Analysis").command(TOGGLE_ANALYSIS).hotkey(SysMods::CmdShift, "A").selected_if(|data: &AppState, _env| data.board_view.analysing))
+        .entry(MenuItem::new("Review Game").command(REVIEW_GAME).hotkey(SysMods::CmdShift, "E"))
+        .separator()
+        .entry(MenuItem::new("Lessons").command(START_LESSONS).hotkey(SysMods::CmdShift, "R"));
+    let view = Menu::new("View")
+        .entry(MenuItem::new("Variation Tree").command(TOGGLE_VARIATION_TREE).selected_if(|data: &AppState, _env| data.show_variation_tree))
+        .entry(MenuItem::new("Blindfold").command(TOGGLE_BLINDFOLD).selected_if(|data: &AppState, _env| data.blindfold))
+        .entry(MenuItem::new("Threats").command(TOGGLE_THREATS).hotkey(SysMods::Cmd, "t").selected_if(|data: &AppState, _env| data.show_threats))
+        .entry(MenuItem::new("Large Pieces").command(TOGGLE_LOW_VISION).hotkey(SysMods::Cmd, "l").selected_if(|data: &AppState, _env| data.low_vision))
+        .entry(MenuItem::new("Paint Timings").command(TOGGLE_HUD).hotkey(SysMods::CmdShift, "D"))
+        .separator()
+        .entry(MenuItem::new("Settings...").command(SHOW_SETTINGS).hotkey(SysMods::Cmd, ","));
+    Menu::empty().entry(file).entry(edit).entry(game).entry(view)
+}
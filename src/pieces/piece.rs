@@ -1,13 +1,14 @@
 use super::piece_type::PieceType;
 use druid::Data;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Data)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
 pub enum PieceColor {
     White,
     Black,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Data, Serialize, Deserialize)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub color: PieceColor,
@@ -86,4 +87,22 @@ impl Piece {
 
         moves
     }
+
+    /// Unicode chess symbol of the piece, outlined for White and filled for Black
+    pub fn figurine(&self) -> char {
+        match (self.color, self.piece_type) {
+            (PieceColor::White, PieceType::King) => '♔',
+            (PieceColor::White, PieceType::Queen) => '♕',
+            (PieceColor::White, PieceType::Rook) => '♖',
+            (PieceColor::White, PieceType::Bishop) => '♗',
+            (PieceColor::White, PieceType::Knight) => '♘',
+            (PieceColor::White, PieceType::Pawn) => '♙',
+            (PieceColor::Black, PieceType::King) => '♚',
+            (PieceColor::Black, PieceType::Queen) => '♛',
+            (PieceColor::Black, PieceType::Rook) => '♜',
+            (PieceColor::Black, PieceType::Bishop) => '♝',
+            (PieceColor::Black, PieceType::Knight) => '♞',
+            (PieceColor::Black, PieceType::Pawn) => '♟',
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use std::path::Path;
+use crate::game::fen::parse_fen;
+use crate::game::game_state::GameState;
+use crate::game::pgn::PgnGame;
+use crate::pieces::PieceColor;
+
+// A4 in points
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+const ROWS_PER_COLUMN: usize = 30;
+const ROW_HEIGHT: f64 = 20.0;
+
+/// Writes a printable scoresheet of the game to `path` as a PDF
+pub fn export_scoresheet(path: &Path, state: &GameState) -> Result<(), String> {
+    std::fs::write(path, scoresheet_pdf(state)).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// Lays out the header fields (left blank to fill in by hand when unknown), the numbered move
+/// pairs in two columns per page and the result
+fn scoresheet_pdf(state: &GameState) -> Vec<u8> {
+    let game = PgnGame::from_game(state);
+    let tag = |name: &str| match game.tag(name) {
+        Some("?") | Some("????.??.??") | None => String::new(),
+        Some(value) => value.to_string(),
+    };
+
+    // Move pairs, numbered from the starting position
+    let (mut number, white_to_move) = match state.start_fen.as_deref().and_then(|fen| parse_fen(fen).ok()) {
+        Some((start, _)) => (start.fullmove_number as usize, start.current_turn == PieceColor::White),
+        None => (1, true),
+    };
+    let mut rows: Vec<(usize, String, String)> = Vec::new();
+    let mut moves = game.moves.iter();
+    if !white_to_move {
+        rows.push((number, "...".to_string(), moves.next().cloned().unwrap_or_default()));
+        number += 1;
+    }
+    while let Some(white) = moves.next() {
+        rows.push((number, white.clone(), moves.next().cloned().unwrap_or_default()));
+        number += 1;
+    }
+
+    let rows_per_page = ROWS_PER_COLUMN * 2;
+    let page_count = rows.len().div_ceil(rows_per_page).max(1);
+    let mut pages = Vec::new();
+
+    for page in 0..page_count {
+        let mut content = String::new();
+        let mut y = PAGE_HEIGHT - MARGIN;
+
+        if page == 0 {
+            text(&mut content, MARGIN, y, 18.0, "Scoresheet");
+            y -= 30.0;
+            for (label, value) in [("White", tag("White")), ("Black", tag("Black"))] {
+                text(&mut content, MARGIN, y, 11.0, &format!("{}: {}", label, value));
+                line(&mut content, MARGIN + 45.0, y - 3.0, PAGE_WIDTH - MARGIN, y - 3.0);
+                y -= 22.0;
+            }
+            text(&mut content, MARGIN, y, 11.0, &format!("Event: {}", tag("Event")));
+            line(&mut content, MARGIN + 45.0, y - 3.0, 330.0, y - 3.0);
+            text(&mut content, 345.0, y, 11.0, &format!("Date: {}", tag("Date")));
+            line(&mut content, 380.0, y - 3.0, PAGE_WIDTH - MARGIN, y - 3.0);
+            y -= 22.0;
+            if let Some(opening) = &state.opening {
+                text(&mut content, MARGIN, y, 11.0, &format!("Opening: {} {}", opening.eco, opening.name));
+                y -= 22.0;
+            }
+            y -= 10.0;
+        } else {
+            text(&mut content, MARGIN, y, 11.0, &format!("Scoresheet, page {}", page + 1));
+            y -= 30.0;
+        }
+
+        // Two columns of numbered move pairs, each with a grid to write in by hand
+        let column_width = (PAGE_WIDTH - 2.0 * MARGIN - 20.0) / 2.0;
+        let page_rows = rows.iter().skip(page * rows_per_page).take(rows_per_page);
+        for (i, (number, white, black)) in page_rows.enumerate() {
+            let x = MARGIN + (i / ROWS_PER_COLUMN) as f64 * (column_width + 20.0);
+            let row_y = y - (i % ROWS_PER_COLUMN) as f64 * ROW_HEIGHT;
+            text(&mut content, x, row_y, 10.0, &format!("{}.", number));
+            text(&mut content, x + 35.0, row_y, 11.0, white);
+            text(&mut content, x + 35.0 + (column_width - 35.0) / 2.0, row_y, 11.0, black);
+            line(&mut content, x, row_y - 5.0, x + column_width, row_y - 5.0);
+        }
+
+        if page == page_count - 1 {
+            let result_y = y - (ROWS_PER_COLUMN as f64 + 1.0) * ROW_HEIGHT;
+            text(&mut content, MARGIN, result_y, 12.0, &format!("Result: {}", game.result.as_deref().unwrap_or("*")));
+        }
+        pages.push(content);
+    }
+
+    write_pdf(&pages)
+}
+
+fn text(content: &mut String, x: f64, y: f64, size: f64, value: &str) {
+    let escaped: String = value.chars()
+        .flat_map(|c| match c {
+            '(' | ')' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect();
+    content.push_str(&format!("BT /F1 {} Tf {:.1} {:.1} Td ({}) Tj ET\n", size, x, y, escaped));
+}
+
+fn line(content: &mut String, x1: f64, y1: f64, x2: f64, y2: f64) {
+    content.push_str(&format!("0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n", x1, y1, x2, y2));
+}
+
+/// Assembles a minimal PDF with one content stream per page, using the built-in Helvetica font
+fn write_pdf(pages: &[String]) -> Vec<u8> {
+    // Objects: 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", 4 + i * 2)).collect();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec());
+
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH, PAGE_HEIGHT, 5 + i * 2
+        ).into_bytes());
+        // WinAnsi covers Latin-1, which is enough for names like "Réti"
+        let bytes: Vec<u8> = content.chars().map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }).collect();
+        let mut stream = format!("<< /Length {} >>\nstream\n", bytes.len()).into_bytes();
+        stream.extend(bytes);
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes());
+    pdf
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::app::AppState;
+use crate::board::diagram::DiagramOptions;
+use crate::board::palette::{AnimationSpeed, BoardTheme, CustomColors, HighlightPalette, MoveIndicator};
+use crate::game::annotation::MarkColor;
+use crate::engine::ComputerOptions;
+use crate::theme::Appearance;
+
+/// The settings kept between runs. Anything missing from the file keeps its default, so that
+/// files written by older versions still load.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    appearance: Appearance,
+    low_vision: bool,
+    board_theme: BoardTheme,
+    highlight_palette: HighlightPalette,
+    piece_set: String,
+    glyph_pieces: bool,
+    move_indicator: MoveIndicator,
+    mark_color: MarkColor,
+    animation: AnimationSpeed,
+    auto_queen: bool,
+    figurine_notation: bool,
+    left_handed: bool,
+    show_coordinates: bool,
+    mirror_coordinates: bool,
+    show_threats: bool,
+    show_eval: bool,
+    show_exchanges: bool,
+    show_variation_tree: bool,
+    blindfold: bool,
+    auto_rotate: bool,
+    turn_notification: bool,
+    turn_sound: bool,
+    sound_effects: bool,
+    sound_volume: f64,
+    analysis_lines: usize,
+    tablebase_moves: bool,
+    // Tables come last, as TOML wants them after the plain values
+    custom_colors: CustomColors,
+    computer: ComputerOptions, // The engines themselves are kept in their own file
+    diagram: DiagramOptions,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_state(&AppState::new())
+    }
+}
+
+impl Settings {
+    pub fn from_state(data: &AppState) -> Self {
+        Self {
+            appearance: data.appearance,
+            low_vision: data.low_vision,
+            board_theme: data.board_theme,
+            custom_colors: data.custom_colors.clone(),
+            highlight_palette: data.highlight_palette,
+            piece_set: data.piece_set.clone(),
+            glyph_pieces: data.glyph_pieces,
+            move_indicator: data.move_indicator,
+            mark_color: data.mark_color,
+            animation: data.animation,
+            auto_queen: data.auto_queen,
+            figurine_notation: data.figurine_notation,
+            left_handed: data.left_handed,
+            show_coordinates: data.show_coordinates,
+            mirror_coordinates: data.mirror_coordinates,
+            show_threats: data.show_threats,
+            show_eval: data.show_eval,
+            show_exchanges: data.show_exchanges,
+            show_variation_tree: data.show_variation_tree,
+            blindfold: data.blindfold,
+            auto_rotate: data.auto_rotate,
+            turn_notification: data.turn_notification,
+            turn_sound: data.turn_sound,
+            sound_effects: data.sound_effects,
+            sound_volume: data.sound_volume,
+            analysis_lines: data.analysis_lines,
+            tablebase_moves: data.tablebase_moves,
+            computer: data.computer.clone(),
+            diagram: data.diagram.clone(),
+        }
+    }
+
+    pub fn apply(self, data: &mut AppState) {
+        data.appearance = self.appearance;
+        data.low_vision = self.low_vision;
+        data.board_theme = self.board_theme;
+        data.custom_colors = self.custom_colors;
+        data.highlight_palette = self.highlight_palette;
+        data.piece_set = self.piece_set;
+        data.glyph_pieces = self.glyph_pieces;
+        data.move_indicator = self.move_indicator;
+        data.mark_color = self.mark_color;
+        data.animation = self.animation;
+        data.auto_queen = self.auto_queen;
+        data.figurine_notation = self.figurine_notation;
+        data.left_handed = self.left_handed;
+        data.show_coordinates = self.show_coordinates;
+        data.mirror_coordinates = self.mirror_coordinates;
+        data.show_threats = self.show_threats;
+        data.show_eval = self.show_eval;
+        data.show_exchanges = self.show_exchanges;
+        data.show_variation_tree = self.show_variation_tree;
+        data.blindfold = self.blindfold;
+        data.auto_rotate = self.auto_rotate;
+        data.turn_notification = self.turn_notification;
+        data.turn_sound = self.turn_sound;
+        data.sound_effects = self.sound_effects;
+        data.sound_volume = self.sound_volume;
+        data.analysis_lines = self.analysis_lines;
+        data.tablebase_moves = self.tablebase_moves;
+        data.computer = ComputerOptions { engines: data.computer.engines.clone(), ..self.computer };
+        data.diagram = self.diagram;
+    }
+}
+
+/// Where the settings are kept: `chess_rust/settings.toml` in the platform's configuration
+/// folder, such as `~/.config` on Linux
+pub fn settings_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("chess_rust").join("settings.toml"))
+}
+
+/// The saved settings, none when nothing was saved yet
+pub fn load_settings() -> Result<Option<Settings>, String> {
+    let Some(path) = settings_path() else { return Ok(None) };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    toml::from_str(&text).map(Some).map_err(|e| format!("corrupt {}: {}", path.display(), e))
+}
+
+pub fn save_settings(settings: &Settings) -> Result<(), String> {
+    let path = settings_path().ok_or("no configuration folder on this system")?;
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).map_err(|e| format!("cannot create {}: {}", folder.display(), e))?;
+    }
+    let text = toml::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
@@ -0,0 +1,35 @@
+use crate::game::fen::to_fen;
+use crate::game::game_state::GameState;
+use crate::pieces::Piece;
+
+/// Lichess analysis board for the game. Games from the standard position are sent as their
+/// moves so the whole game can be stepped through, other positions as a FEN.
+pub fn lichess_analysis_url(state: &GameState, board: &[Option<Piece>]) -> String {
+    if state.start_fen.is_none() && !state.san_moves.is_empty() {
+        let moves: Vec<&str> = state.san_moves.iter().map(String::as_str).collect();
+        format!("https://lichess.org/analysis/pgn/{}", percent_encode(&moves.join(" ")))
+    } else {
+        format!("https://lichess.org/analysis/standard/{}", percent_encode(&to_fen(state, board).replace(' ', "_")))
+    }
+}
+
+/// Chess.com analysis board for the current position
+pub fn chess_com_analysis_url(state: &GameState, board: &[Option<Piece>]) -> String {
+    format!("https://www.chess.com/analysis?fen={}", percent_encode(&to_fen(state, board)))
+}
+
+/// Opens a URL in the default browser
+pub fn open_in_browser(url: &str) -> Result<(), String> {
+    open::that(url).map_err(|e| format!("Could not open the browser: {}", e))
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,34 @@
+use druid::widget::{Flex, Label, SizedBox, ViewSwitcher};
+use druid::{Selector, Widget, WidgetExt};
+use crate::app::AppState;
+use crate::menu::NEW_TAB;
+use crate::theme::{HIGHLIGHT_COLOR, PANEL_COLOR};
+
+/// Shows the game of the tab with this index
+pub const SWITCH_TAB: Selector<usize> = Selector::new("chess_rust.switch-tab");
+
+/// A row with a tab for every game open, above the board. It is left out while only one game
+/// is open, so that a single game looks as it always did.
+pub fn tab_bar() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _env| (data.tab_labels(), data.active_tab),
+        |(labels, active), _data, _env| {
+            if labels.len() < 2 {
+                return Box::new(SizedBox::empty());
+            }
+            let mut row = Flex::row();
+            for (index, label) in labels.iter().enumerate() {
+                let tab = Label::new(label.clone())
+                    .padding((12.0, 5.0))
+                    .background(if index == *active { HIGHLIGHT_COLOR } else { PANEL_COLOR })
+                    .on_click(move |ctx, _data: &mut AppState, _env| ctx.submit_command(SWITCH_TAB.with(index)));
+                row.add_child(tab);
+                row.add_spacer(2.0);
+            }
+            let add = Label::new("+")
+                .padding((10.0, 5.0))
+                .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(NEW_TAB));
+            Box::new(row.with_child(add).padding(4.0).expand_width())
+        },
+    )
+}
@@ -0,0 +1,76 @@
+use druid::widget::EnvScope;
+use druid::{theme, Color, Data, Env, Key, Widget};
+use serde::{Deserialize, Serialize};
+use crate::app::AppState;
+
+/// Background of the status bar, the player bars and the buttons around the board
+pub const PANEL_COLOR: Key<Color> = Key::new("chess_rust.theme.panel");
+
+/// Background of the move shown in the move list, and of the status bar once the game is over
+pub const HIGHLIGHT_COLOR: Key<Color> = Key::new("chess_rust.theme.highlight");
+
+/// Move numbers, the material lead and disabled buttons
+pub const MUTED_TEXT_COLOR: Key<Color> = Key::new("chess_rust.theme.muted-text");
+
+/// Light or dark windows
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Data, Serialize, Deserialize)]
+pub enum Appearance {
+    System, // Whichever the system was set to when the app started
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub const ALL: [Appearance; 3] = [Appearance::System, Appearance::Light, Appearance::Dark];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Appearance::System => "As the system",
+            Appearance::Light => "Light",
+            Appearance::Dark => "Dark",
+        }
+    }
+}
+
+/// Whether the system is set to dark mode; false where that cannot be told
+pub fn system_prefers_dark() -> bool {
+    dark_light::detect() == dark_light::Mode::Dark
+}
+
+/// Sets the colors of the board's own panels and of druid's widgets for a light or dark look
+pub fn set_colors(env: &mut Env, dark: bool) {
+    if dark {
+        env.set(theme::WINDOW_BACKGROUND_COLOR, Color::rgb8(40, 40, 40));
+        env.set(theme::TEXT_COLOR, Color::rgb8(230, 230, 230));
+        env.set(theme::PLACEHOLDER_COLOR, Color::rgb8(130, 130, 130));
+        env.set(theme::BACKGROUND_LIGHT, Color::rgb8(58, 58, 58));
+        env.set(theme::BACKGROUND_DARK, Color::rgb8(31, 31, 31));
+        env.set(theme::BUTTON_LIGHT, Color::rgb8(80, 80, 80));
+        env.set(theme::BUTTON_DARK, Color::rgb8(50, 50, 50));
+        env.set(theme::BORDER_LIGHT, Color::rgb8(120, 120, 120));
+        env.set(theme::BORDER_DARK, Color::rgb8(90, 90, 90));
+        env.set(PANEL_COLOR, Color::rgb8(60, 60, 60));
+        env.set(HIGHLIGHT_COLOR, Color::rgb8(45, 75, 120));
+        env.set(MUTED_TEXT_COLOR, Color::rgb8(150, 150, 150));
+        env.set(theme::DISABLED_TEXT_COLOR, Color::rgb8(150, 150, 150));
+    } else {
+        env.set(theme::WINDOW_BACKGROUND_COLOR, Color::rgb8(245, 245, 245));
+        env.set(theme::TEXT_COLOR, Color::BLACK);
+        env.set(theme::PLACEHOLDER_COLOR, Color::rgb8(140, 140, 140));
+        env.set(theme::BACKGROUND_LIGHT, Color::WHITE);
+        env.set(theme::BACKGROUND_DARK, Color::rgb8(230, 230, 230));
+        env.set(theme::BUTTON_LIGHT, Color::rgb8(250, 250, 250));
+        env.set(theme::BUTTON_DARK, Color::rgb8(215, 215, 215));
+        env.set(theme::BORDER_LIGHT, Color::rgb8(200, 200, 200));
+        env.set(theme::BORDER_DARK, Color::rgb8(170, 170, 170));
+        env.set(PANEL_COLOR, Color::rgb8(225, 225, 225));
+        env.set(HIGHLIGHT_COLOR, Color::rgb8(200, 220, 250));
+        env.set(MUTED_TEXT_COLOR, Color::rgb8(110, 110, 110));
+        env.set(theme::DISABLED_TEXT_COLOR, Color::rgb8(110, 110, 110));
+    }
+}
+
+/// The content of a window, following the appearance picked in the settings as it changes
+pub fn themed(content: impl Widget<AppState> + 'static) -> impl Widget<AppState> {
+    EnvScope::new(|env, data: &AppState| set_colors(env, data.dark_mode()), content)
+}
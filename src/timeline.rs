@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+use crate::game::fen::to_fen;
+use crate::game::game_state::GameState;
+use crate::game::pgn::Position;
+use crate::pieces::Piece;
+
+/// Minimum time between automatic snapshots
+const AUTOSNAPSHOT_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Oldest snapshots are dropped past this many
+const MAX_SNAPSHOTS: usize = 500;
+
+pub struct Snapshot {
+    pub taken: Instant,
+    pub label: String,
+    pub position: Position,
+}
+
+/// Positions the user worked on during this session, oldest first, so that they can get back
+/// to one without having saved it
+pub struct Timeline {
+    snapshots: Vec<Snapshot>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self { snapshots: Vec::new() }
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// Records the position, e.g. when a game is opened
+    pub fn record(&mut self, label: &str, state: &GameState, board: &[Option<Piece>]) {
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.remove(0);
+        }
+        self.snapshots.push(Snapshot {
+            taken: Instant::now(),
+            label: label.to_string(),
+            position: (state.clone(), board.to_vec()),
+        });
+    }
+
+    /// Records the position if it is new and the last snapshot is old enough
+    pub fn autosnapshot(&mut self, state: &GameState, board: &[Option<Piece>]) {
+        if let Some(last) = self.snapshots.last() {
+            let (last_state, last_board) = &last.position;
+            if last.taken.elapsed() < AUTOSNAPSHOT_INTERVAL || to_fen(last_state, last_board) == to_fen(state, board) {
+                return;
+            }
+        }
+        let label = match state.move_history.last() {
+            Some(moves) => format!("After {}", moves),
+            None => "Starting position".to_string(),
+        };
+        self.record(&label, state, board);
+    }
+}
+
+/// How long ago a snapshot was taken, e.g. "5 min ago"
+pub fn age(taken: Instant) -> String {
+    let seconds = taken.elapsed().as_secs();
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        _ => format!("{} h {} min ago", seconds / 3600, seconds % 3600 / 60),
+    }
+}